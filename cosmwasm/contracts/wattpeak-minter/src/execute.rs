@@ -79,6 +79,7 @@ pub fn execute(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn upload_project(
     deps: DepsMut,
     info: MessageInfo,
@@ -120,6 +121,7 @@ pub fn upload_project(
         .add_attribute("new_wattpeak", project.max_wattpeak.to_string()))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn edit_project(
     deps: DepsMut,
     info: MessageInfo,
@@ -245,7 +247,7 @@ pub fn mint_tokens_msg(
     // Search for the project by name
     let project = PROJECTS.load(deps.storage, project_id);
 
-    if !project.is_ok() {
+    if project.is_err() {
         return Err(ContractError::ProjectNotFound {});
     }
 
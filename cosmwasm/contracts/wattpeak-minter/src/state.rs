@@ -34,13 +34,13 @@ impl Location {
             .map_err(|_| StdError::generic_err("Invalid longitude format"))?;
 
         // Latitude must be between -90 and +90
-        if lat < -90.0 || lat > 90.0 {
+        if !(-90.0..=90.0).contains(&lat) {
             return Err(StdError::generic_err(
                 "Latitude must be between -90 and +90 degrees",
             ));
         }
         // Longitude must be between -180 and +180
-        if lon < -180.0 || lon > 180.0 {
+        if !(-180.0..=180.0).contains(&lon) {
             return Err(StdError::generic_err(
                 "Longitude must be between -180 and +180 degrees",
             ));
@@ -52,11 +52,9 @@ impl Location {
 // Implement a validation function for the config
 impl Config {
     pub fn validate(&self, deps: Deps) -> StdResult<()> {
-        deps.api.addr_validate(&self.admin.to_string())?;
-        deps.api
-            .addr_validate(&self.minting_payment_address.to_string())?;
-        deps.api
-            .addr_validate(&self.minting_fee_address.to_string())?;
+        deps.api.addr_validate(self.admin.as_str())?;
+        deps.api.addr_validate(self.minting_payment_address.as_str())?;
+        deps.api.addr_validate(self.minting_fee_address.as_str())?;
 
         if self.minting_price.amount.is_zero() {
             return Err(StdError::generic_err("minting_price cannot be zero"));
@@ -1,14 +1,397 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{
+    CheckedFromRatioError, CheckedMultiplyRatioError, Decimal, DecimalRangeExceeded,
+    DivideByZeroError, OverflowError, StdError,
+};
 use thiserror::Error;
 
+/// Errors of the staker. Every error is displayed with its code, e.g. "E201: Staker does not
+/// exist", the codes are stable so clients can match on them
 #[derive(Error, Debug, PartialEq)]
 pub enum ContractError {
-    #[error("{0}")]
+    #[error("E{code:03}: {0}", code = self.code())]
     Std(#[from] StdError),
 
-    #[error("unauthorized")]
+    #[error("E{code:03}: {0}", code = self.code())]
+    Overflow(#[from] OverflowError),
+
+    #[error("E{code:03}: {0}", code = self.code())]
+    DivideByZero(#[from] DivideByZeroError),
+
+    #[error("E{code:03}: {0}", code = self.code())]
+    CheckedFromRatio(#[from] CheckedFromRatioError),
+
+    #[error("E{code:03}: {0}", code = self.code())]
+    CheckedMultiplyRatio(#[from] CheckedMultiplyRatioError),
+
+    #[error("E{code:03}: {0}", code = self.code())]
+    DecimalRangeExceeded(#[from] DecimalRangeExceeded),
+
+    // Authorization and configuration
+    #[error("E{code:03}: Unauthorized", code = self.code())]
     Unauthorized {},
 
-    #[error("Insufficient funds")]
+    #[error("E{code:03}: Insufficient funds", code = self.code())]
     InsufficientFunds {},
-}
\ No newline at end of file
+
+    #[error("E{code:03}: epoch_length cannot be zero", code = self.code())]
+    EpochLengthZero {},
+
+    #[error("E{code:03}: rewards_percentage cannot be greater than 100%", code = self.code())]
+    RewardsPercentageTooHigh {},
+
+    #[error("E{code:03}: invalid reward denom: {denom}", code = self.code())]
+    InvalidRewardDenom { denom: String },
+
+    #[error("E{code:03}: duplicate reward denom: {denom}", code = self.code())]
+    DuplicateRewardDenom { denom: String },
+
+    #[error("E{code:03}: lock duration cannot be zero", code = self.code())]
+    LockDurationZero {},
+
+    #[error("E{code:03}: duplicate lock duration: {duration}", code = self.code())]
+    DuplicateLockDuration { duration: u64 },
+
+    #[error("E{code:03}: lock multiplier cannot be less than 1", code = self.code())]
+    LockMultiplierTooLow {},
+
+    #[error("E{code:03}: early_unlock_penalty cannot be greater than 100%", code = self.code())]
+    EarlyUnlockPenaltyTooHigh {},
+
+    #[error("E{code:03}: early unstake fee period cannot be zero", code = self.code())]
+    EarlyUnstakeFeePeriodZero {},
+
+    #[error("E{code:03}: early unstake fee cannot be greater than 100%", code = self.code())]
+    EarlyUnstakeFeeTooHigh {},
+
+    #[error("E{code:03}: loyalty duration cannot be zero", code = self.code())]
+    LoyaltyDurationZero {},
+
+    #[error("E{code:03}: duplicate loyalty duration: {duration}", code = self.code())]
+    DuplicateLoyaltyDuration { duration: u64 },
+
+    #[error("E{code:03}: loyalty multiplier cannot be less than 1", code = self.code())]
+    LoyaltyMultiplierTooLow {},
+
+    #[error("E{code:03}: reward expiry epochs cannot be zero", code = self.code())]
+    RewardExpiryEpochsZero {},
+
+    #[error("E{code:03}: staking_fee_percentage cannot be greater than {max}", code = self.code())]
+    StakingFeeTooHigh { max: Decimal },
+
+    #[error("E{code:03}: wattpeak_denom cannot be empty", code = self.code())]
+    WattpeakDenomEmpty {},
+
+    #[error("E{code:03}: wattpeak_denom cannot change while the contract holds wattpeak staked, unbonding or for rewards", code = self.code())]
+    WattpeakDenomInUse {},
+
+    #[error("E{code:03}: No admin change is pending", code = self.code())]
+    NoPendingAdmin {},
+
+    #[error("E{code:03}: invalid bridged denom: {denom}", code = self.code())]
+    InvalidBridgedDenom { denom: String },
+
+    #[error("E{code:03}: duplicate bridged denom: {denom}", code = self.code())]
+    DuplicateBridgedDenom { denom: String },
+
+    #[error("E{code:03}: wattpeak_denom is read from the minter", code = self.code())]
+    WattpeakDenomSetByMinter {},
+
+    // Staking
+    #[error("E{code:03}: Staker does not exist", code = self.code())]
+    StakerNotFound {},
+
+    #[error("E{code:03}: Must stake WattPeak tokens to the contract", code = self.code())]
+    NoWattpeakStaked {},
+
+    #[error("E{code:03}: Stake amount can't be zero", code = self.code())]
+    ZeroStake {},
+
+    #[error("E{code:03}: Unstake amount can't be zero", code = self.code())]
+    ZeroUnstake {},
+
+    #[error("E{code:03}: Insufficient staked wattpeak", code = self.code())]
+    InsufficientStake {},
+
+    #[error("E{code:03}: Insufficient unlocked wattpeak", code = self.code())]
+    InsufficientUnlockedStake {},
+
+    #[error("E{code:03}: No lock tier with a duration of {duration}", code = self.code())]
+    NoLockTier { duration: u64 },
+
+    #[error("E{code:03}: Lock {id} does not exist", code = self.code())]
+    LockNotFound { id: u64 },
+
+    #[error("E{code:03}: No unbonded wattpeak to withdraw", code = self.code())]
+    NoUnbondedWattpeak {},
+
+    #[error("E{code:03}: Transfer amount can't be zero", code = self.code())]
+    ZeroTransfer {},
+
+    #[error("E{code:03}: Cannot transfer stake to yourself", code = self.code())]
+    TransferToSelf {},
+
+    #[error("E{code:03}: Cannot transfer stake to the contract", code = self.code())]
+    TransferToContract {},
+
+    #[error("E{code:03}: Nothing to withdraw", code = self.code())]
+    NothingToWithdraw {},
+
+    #[error("E{code:03}: Insufficient wattpeak staked in {denom}", code = self.code())]
+    InsufficientDenomStake { denom: String },
+
+    #[error("E{code:03}: Only one denom can be sent at a time", code = self.code())]
+    MultipleDenomsStaked {},
+
+    #[error("E{code:03}: start_after_wattpeak_staked must be set with start_after when ordering by wattpeak staked", code = self.code())]
+    MissingStakersCursorAmount {},
+
+    // Rewards
+    #[error("E{code:03}: Must deposit WattPeak tokens to the contract", code = self.code())]
+    NoWattpeakDeposited {},
+
+    #[error("E{code:03}: {denom} is not a whitelisted reward denom", code = self.code())]
+    DenomNotWhitelisted { denom: String },
+
+    #[error("E{code:03}: Deposit amount can't be zero", code = self.code())]
+    ZeroDeposit {},
+
+    #[error("E{code:03}: No interest to distribute rewards to", code = self.code())]
+    NoInterest {},
+
+    #[error("E{code:03}: Stream cannot start in the past", code = self.code())]
+    StreamStartInPast {},
+
+    #[error("E{code:03}: Stream must end after it starts", code = self.code())]
+    StreamEndBeforeStart {},
+
+    #[error("E{code:03}: No rewards to claim", code = self.code())]
+    NoRewardsToClaim {},
+
+    #[error("E{code:03}: No rewards to restake", code = self.code())]
+    NoRewardsToRestake {},
+
+    #[error("E{code:03}: Cannot claim to a recipient and over IBC at once", code = self.code())]
+    ClaimToRecipientAndIbc {},
+
+    #[error("E{code:03}: Rewards do not expire", code = self.code())]
+    RewardsDoNotExpire {},
+
+    // Epochs and rate changes
+    #[error("E{code:03}: Epoch has not ended yet, next epoch starts at {next_epoch_time}", code = self.code())]
+    EpochNotEnded { next_epoch_time: u64 },
+
+    #[error("E{code:03}: Epoch {epoch} has already started", code = self.code())]
+    EpochAlreadyStarted { epoch: u64 },
+
+    #[error("E{code:03}: Rate change must take effect in the future", code = self.code())]
+    RateChangeNotInFuture {},
+
+    #[error("E{code:03}: Rate change at {time} has already taken effect", code = self.code())]
+    RateChangeTookEffect { time: u64 },
+
+    #[error("E{code:03}: No rate change scheduled at {time}", code = self.code())]
+    RateChangeNotFound { time: u64 },
+
+    // Hooks
+    #[error("E{code:03}: {address} is already a hook", code = self.code())]
+    HookExists { address: String },
+
+    #[error("E{code:03}: {address} is not a hook", code = self.code())]
+    HookNotFound { address: String },
+
+    // Liquid staking
+    #[error("E{code:03}: Liquid staking denom already exists", code = self.code())]
+    LiquidDenomExists {},
+
+    #[error("E{code:03}: Liquid staking is not enabled", code = self.code())]
+    LiquidStakingNotEnabled {},
+
+    #[error("E{code:03}: Stake amount is too small to mint stWP", code = self.code())]
+    LiquidStakeTooSmall {},
+
+    #[error("E{code:03}: Must send stWP tokens to unstake", code = self.code())]
+    NoLiquidTokensSent {},
+
+    #[error("E{code:03}: Unstake amount is too small", code = self.code())]
+    LiquidUnstakeTooSmall {},
+
+    // Pools
+    #[error("E{code:03}: Pool {project_id} already exists", code = self.code())]
+    PoolExists { project_id: u64 },
+
+    #[error("E{code:03}: Pool {project_id} does not exist", code = self.code())]
+    PoolNotFound { project_id: u64 },
+
+    #[error("E{code:03}: Pool {project_id} cannot hold more than the {minted} wattpeak minted for the project", code = self.code())]
+    PoolCapExceeded { project_id: u64, minted: u64 },
+
+    #[error("E{code:03}: No stake in pool {project_id}", code = self.code())]
+    NoPoolStake { project_id: u64 },
+
+    #[error("E{code:03}: Minter address is not set", code = self.code())]
+    MinterNotSet {},
+
+    #[error("E{code:03}: Project {project_id} does not exist in the minter", code = self.code())]
+    ProjectNotFound { project_id: u64 },
+
+    #[error("E{code:03}: Minter has no wattpeak denom", code = self.code())]
+    MinterDenomNotFound {},
+
+    // IBC
+    #[error("E{code:03}: IBC channel_id cannot be empty", code = self.code())]
+    EmptyIbcChannel {},
+
+    #[error("E{code:03}: IBC receiver cannot be empty", code = self.code())]
+    EmptyIbcReceiver {},
+
+    #[error("E{code:03}: IBC timeout cannot be zero", code = self.code())]
+    ZeroIbcTimeout {},
+
+    #[error("E{code:03}: IBC transfer reply holds no data", code = self.code())]
+    IbcReplyWithoutData {},
+
+    #[error("E{code:03}: No pending IBC transfer", code = self.code())]
+    NoPendingIbcTransfer {},
+
+    #[error("E{code:03}: Unknown IBC transfer {sequence} on channel {channel}", code = self.code())]
+    UnknownIbcTransfer { channel: String, sequence: u64 },
+
+    #[error("E{code:03}: Unknown reply id: {id}", code = self.code())]
+    UnknownReplyId { id: u64 },
+
+    // Pausing
+    #[error("E{code:03}: staking is paused", code = self.code())]
+    StakingPaused {},
+
+    #[error("E{code:03}: claiming is paused", code = self.code())]
+    ClaimingPaused {},
+
+    #[error("E{code:03}: epoch processing is paused", code = self.code())]
+    EpochsPaused {},
+
+    #[error("E{code:03}: emergency mode, only staked wattpeak can be withdrawn", code = self.code())]
+    EmergencyMode {},
+
+    #[error("E{code:03}: not in emergency mode", code = self.code())]
+    NotInEmergencyMode {},
+}
+
+impl ContractError {
+    /// Stable code of the error. Codes are grouped by the part of the contract the error comes
+    /// from and are never reused
+    pub fn code(&self) -> u32 {
+        match self {
+            ContractError::Std(_) => 1,
+            ContractError::Overflow(_) => 2,
+            ContractError::DivideByZero(_) => 3,
+            ContractError::CheckedFromRatio(_) => 4,
+            ContractError::CheckedMultiplyRatio(_) => 5,
+            ContractError::DecimalRangeExceeded(_) => 6,
+            ContractError::Unauthorized {} => 101,
+            ContractError::InsufficientFunds {} => 102,
+            ContractError::EpochLengthZero {} => 110,
+            ContractError::RewardsPercentageTooHigh {} => 111,
+            ContractError::InvalidRewardDenom { .. } => 112,
+            ContractError::DuplicateRewardDenom { .. } => 113,
+            ContractError::LockDurationZero {} => 114,
+            ContractError::DuplicateLockDuration { .. } => 115,
+            ContractError::LockMultiplierTooLow {} => 116,
+            ContractError::EarlyUnlockPenaltyTooHigh {} => 117,
+            ContractError::EarlyUnstakeFeePeriodZero {} => 118,
+            ContractError::EarlyUnstakeFeeTooHigh {} => 119,
+            ContractError::LoyaltyDurationZero {} => 120,
+            ContractError::DuplicateLoyaltyDuration { .. } => 121,
+            ContractError::LoyaltyMultiplierTooLow {} => 122,
+            ContractError::RewardExpiryEpochsZero {} => 123,
+            ContractError::StakingFeeTooHigh { .. } => 124,
+            ContractError::WattpeakDenomEmpty {} => 125,
+            ContractError::WattpeakDenomInUse {} => 126,
+            ContractError::NoPendingAdmin {} => 127,
+            ContractError::InvalidBridgedDenom { .. } => 128,
+            ContractError::DuplicateBridgedDenom { .. } => 129,
+            ContractError::WattpeakDenomSetByMinter {} => 130,
+            ContractError::StakerNotFound {} => 201,
+            ContractError::NoWattpeakStaked {} => 202,
+            ContractError::ZeroStake {} => 203,
+            ContractError::ZeroUnstake {} => 204,
+            ContractError::InsufficientStake {} => 205,
+            ContractError::InsufficientUnlockedStake {} => 206,
+            ContractError::NoLockTier { .. } => 207,
+            ContractError::LockNotFound { .. } => 208,
+            ContractError::NoUnbondedWattpeak {} => 209,
+            ContractError::ZeroTransfer {} => 210,
+            ContractError::TransferToSelf {} => 211,
+            ContractError::TransferToContract {} => 212,
+            ContractError::NothingToWithdraw {} => 213,
+            ContractError::InsufficientDenomStake { .. } => 214,
+            ContractError::MultipleDenomsStaked {} => 216,
+            ContractError::MissingStakersCursorAmount {} => 217,
+            ContractError::NoWattpeakDeposited {} => 301,
+            ContractError::DenomNotWhitelisted { .. } => 302,
+            ContractError::ZeroDeposit {} => 303,
+            ContractError::NoInterest {} => 304,
+            ContractError::StreamStartInPast {} => 305,
+            ContractError::StreamEndBeforeStart {} => 306,
+            ContractError::NoRewardsToClaim {} => 307,
+            ContractError::NoRewardsToRestake {} => 308,
+            ContractError::ClaimToRecipientAndIbc {} => 309,
+            ContractError::RewardsDoNotExpire {} => 310,
+            ContractError::EpochNotEnded { .. } => 401,
+            ContractError::EpochAlreadyStarted { .. } => 402,
+            ContractError::RateChangeNotInFuture {} => 403,
+            ContractError::RateChangeTookEffect { .. } => 404,
+            ContractError::RateChangeNotFound { .. } => 405,
+            ContractError::HookExists { .. } => 501,
+            ContractError::HookNotFound { .. } => 502,
+            ContractError::LiquidDenomExists {} => 601,
+            ContractError::LiquidStakingNotEnabled {} => 602,
+            ContractError::LiquidStakeTooSmall {} => 603,
+            ContractError::NoLiquidTokensSent {} => 604,
+            ContractError::LiquidUnstakeTooSmall {} => 605,
+            ContractError::PoolExists { .. } => 701,
+            ContractError::PoolNotFound { .. } => 702,
+            ContractError::PoolCapExceeded { .. } => 703,
+            ContractError::NoPoolStake { .. } => 704,
+            ContractError::MinterNotSet {} => 705,
+            ContractError::ProjectNotFound { .. } => 706,
+            ContractError::MinterDenomNotFound {} => 707,
+            ContractError::EmptyIbcChannel {} => 801,
+            ContractError::EmptyIbcReceiver {} => 802,
+            ContractError::ZeroIbcTimeout {} => 803,
+            ContractError::IbcReplyWithoutData {} => 804,
+            ContractError::NoPendingIbcTransfer {} => 805,
+            ContractError::UnknownIbcTransfer { .. } => 806,
+            ContractError::UnknownReplyId { .. } => 807,
+            ContractError::StakingPaused {} => 901,
+            ContractError::ClaimingPaused {} => 902,
+            ContractError::EpochsPaused {} => 903,
+            ContractError::EmergencyMode {} => 904,
+            ContractError::NotInEmergencyMode {} => 905,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_are_displayed_with_their_code() {
+        assert_eq!(
+            ContractError::Unauthorized {}.to_string(),
+            "E101: Unauthorized"
+        );
+        assert_eq!(
+            ContractError::StakerNotFound {}.to_string(),
+            "E201: Staker does not exist"
+        );
+        assert_eq!(
+            ContractError::PoolNotFound { project_id: 3 }.to_string(),
+            "E702: Pool 3 does not exist"
+        );
+
+        let err = ContractError::from(StdError::not_found("Staker"));
+        assert_eq!(err.code(), 1);
+        assert_eq!(err.to_string(), "E001: Staker not found");
+    }
+}
@@ -1,54 +1,233 @@
 use cosmwasm_std::{
-    entry_point, Addr, BankMsg, Coin, Decimal, DepsMut, Env, MessageInfo, Response, StdError,
-    StdResult, Uint128,
+    entry_point, to_json_string, Addr, BankMsg, Coin, Decimal, DepsMut, Env, Event, MessageInfo,
+    Order, Response, StdResult, Storage, Uint128,
 };
+use cw_storage_plus::Bound;
+use serde::Serialize;
+use token_bindings::TokenFactoryMsg;
 
 use crate::{
+    error::ContractError,
     helpers::{
-        calculate_interest_after_epoch, calculate_staker_share_of_reward, set_yearly_percentage,
+        accrual_time, accrue_interest, add_bridged_stake, add_coin, add_denom_reward,
+        add_reward_dust, add_stake, bridged_stake, calculate_interest_after_epoch,
+        calculate_staker_share_of_reward, canonical_stake, compound_staker,
+        create_liquid_denom_msg, load_liquid_pool, merge_reward_bucket, new_staker,
+        process_expiries, query_minter_denom, record_staking_fees, release_lock,
+        remove_bridged_stake, remove_staker_if_empty, save_staker, settle_staker,
+        sync_reward_buckets, take_expired_rewards, take_whole_rewards, to_decimal, update_loyalty,
+        wattpeak_denom_in_use,
+    },
+    ibc::ibc_transfer_msgs,
+    msg::{ExecuteMsg, IbcClaim, RateChangeStart},
+    pools::{distribute_pool_rewards, load_pool, load_pool_stake, query_minter_project},
+    query::{DEFAULT_LIMIT, MAX_LIMIT},
+    state::{
+        stakers, Config, EarlyUnstakeFee, LockPosition, LockTier, LoyaltyTier, Pool, RewardExpiry,
+        RewardStream, Staker, UnbondingEntry, BRIDGED_STAKES, CONFIG, EMERGENCY_TIME, EPOCH_COUNT,
+        HOOKS, LAST_EPOCH_TIME, LIQUID_DENOM, LIQUID_SUBDENOM, LIQUID_SUPPLY, LOCKS, LOCK_COUNT,
+        LOCK_EXPIRIES, PAUSE_FLAGS, PENDING_ADMIN, POOLS, POOL_STAKES, RATE_SCHEDULE,
+        REWARD_RESERVE, REWARD_STREAMS, REWARD_STREAM_COUNT, SWEEP_CURSOR, TOTAL_LOCK_BOOST,
+        TOTAL_WATTPEAK_STAKED, UNBONDINGS, UNBONDING_COUNT, WITHDRAW_ADDRESSES,
     },
-    msg::ExecuteMsg,
-    state::{Staker, CONFIG, STAKERS, TOTAL_WATTPEAK_STAKED},
 };
 
 #[entry_point]
-pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    check_paused(deps.storage, &msg)?;
     match msg {
         ExecuteMsg::UpdateConfig {
-            admin,
             rewards_percentage,
             epoch_length,
-        } => update_config(deps, env, info, admin, epoch_length, rewards_percentage),
-        ExecuteMsg::Stake {} => stake_wattpeak(deps, env, info),
-        ExecuteMsg::Unstake { amount } => unstake_wattpeak(deps, env, info, amount),
+            unbonding_period,
+            reward_denoms,
+            lock_tiers,
+            early_unstake_fee,
+            loyalty_tiers,
+            minter_address,
+            cap_interest_to_reserve,
+            reward_expiry,
+            guardian,
+            wattpeak_denom,
+            staking_fee_percentage,
+            staking_fee_address,
+            bridged_denoms,
+        } => update_config(
+            deps,
+            env,
+            info,
+            epoch_length,
+            rewards_percentage,
+            unbonding_period,
+            reward_denoms,
+            lock_tiers,
+            early_unstake_fee,
+            loyalty_tiers,
+            minter_address,
+            cap_interest_to_reserve,
+            reward_expiry,
+            guardian,
+            wattpeak_denom,
+            staking_fee_percentage,
+            staking_fee_address,
+            bridged_denoms,
+        ),
+        ExecuteMsg::ProposeAdmin { admin } => propose_admin(deps, info, admin),
+        ExecuteMsg::AcceptAdmin {} => accept_admin(deps, info),
+        ExecuteMsg::CancelAdminProposal {} => cancel_admin_proposal(deps, info),
+        ExecuteMsg::Stake {} => stake_wattpeak(deps, env, info, None),
+        ExecuteMsg::Lock { duration } => stake_wattpeak(deps, env, info, Some(duration)),
+        ExecuteMsg::Unlock { id } => unlock_early(deps, env, info, id),
+        ExecuteMsg::Unstake { amount, denom } => unstake_wattpeak(deps, env, info, amount, denom),
+        ExecuteMsg::WithdrawUnbonded {} => withdraw_unbonded(deps, env, info),
+        ExecuteMsg::TransferStake {
+            recipient,
+            amount,
+            include_rewards,
+        } => transfer_stake(deps, env, info, recipient, amount, include_rewards),
         ExecuteMsg::DepositRewards {} => deposit_rewards(deps, env, info),
-        ExecuteMsg::ClaimReward {} => claim_rewards(deps, env, info),
-        ExecuteMsg::NewEpoch {} => calculate_interest_after_epoch(deps, info),
+        ExecuteMsg::DepositRewardStream { start, end } => {
+            deposit_reward_stream(deps, env, info, start, end)
+        }
+        ExecuteMsg::FundReserve {} => fund_reserve(deps, info),
+        ExecuteMsg::ClaimReward { recipient, ibc } => {
+            claim_rewards(deps, env, info, recipient, ibc)
+        }
+        ExecuteMsg::SetWithdrawAddress { address } => set_withdraw_address(deps, info, address),
+        ExecuteMsg::Restake {} => restake_rewards(deps, env, info),
+        ExecuteMsg::AutoCompound { enabled } => set_auto_compound(deps, env, info, enabled),
+        ExecuteMsg::ScheduleRateChange {
+            rewards_percentage,
+            start,
+        } => schedule_rate_change(deps, env, info, rewards_percentage, start),
+        ExecuteMsg::CancelRateChange { time } => cancel_rate_change(deps, env, info, time),
+        ExecuteMsg::UpdateLoyalty { address } => update_staker_loyalty(deps, env, address),
+        ExecuteMsg::SweepExpired { limit } => sweep_expired(deps, env, limit),
+        ExecuteMsg::ProcessExpiries { limit } => process_due_expiries(deps, env, limit),
+        ExecuteMsg::NewEpoch {} => calculate_interest_after_epoch(deps, env),
+        ExecuteMsg::AddHook {
+            address,
+            fail_on_error,
+        } => add_hook(deps, info, address, fail_on_error),
+        ExecuteMsg::RemoveHook { address } => remove_hook(deps, info, address),
+        ExecuteMsg::CreateLiquidDenom {} => create_liquid_denom(deps, env, info),
+        ExecuteMsg::LiquidStake {} => liquid_stake(deps, env, info),
+        ExecuteMsg::LiquidUnstake {} => liquid_unstake(deps, env, info),
+        ExecuteMsg::CreatePool {
+            project_id,
+            rewards_percentage,
+        } => create_pool(deps, env, info, project_id, rewards_percentage),
+        ExecuteMsg::UpdatePool {
+            project_id,
+            rewards_percentage,
+        } => update_pool(deps, env, info, project_id, rewards_percentage),
+        ExecuteMsg::StakeInPool { project_id } => stake_in_pool(deps, env, info, project_id),
+        ExecuteMsg::UnstakeFromPool { project_id, amount } => {
+            unstake_from_pool(deps, env, info, project_id, amount)
+        }
+        ExecuteMsg::DepositPoolRewards { project_id } => {
+            deposit_pool_rewards(deps, env, info, project_id)
+        }
+        ExecuteMsg::ClaimPoolRewards { project_id } => {
+            claim_pool_rewards(deps, env, info, project_id)
+        }
+        ExecuteMsg::SetPause {
+            staking,
+            claiming,
+            epochs,
+        } => set_pause(deps, info, staking, claiming, epochs),
+        ExecuteMsg::EnableEmergencyMode {} => enable_emergency_mode(deps, env, info),
+        ExecuteMsg::EmergencyWithdraw { project_id } => {
+            emergency_withdraw(deps, env, info, project_id)
+        }
+    }
+}
+
+/// Rejects the messages of the parts of the contract that are paused. In emergency mode only the
+/// withdrawals of staked wattpeak, the processing of expiries and the messages of the admin and
+/// the guardian are open
+fn check_paused(storage: &dyn Storage, msg: &ExecuteMsg) -> Result<(), ContractError> {
+    if EMERGENCY_TIME.may_load(storage)?.is_some() {
+        return match msg {
+            ExecuteMsg::EmergencyWithdraw { .. }
+            | ExecuteMsg::WithdrawUnbonded {}
+            | ExecuteMsg::LiquidUnstake {}
+            | ExecuteMsg::ProcessExpiries { .. }
+            | ExecuteMsg::UpdateConfig { .. }
+            | ExecuteMsg::ProposeAdmin { .. }
+            | ExecuteMsg::AcceptAdmin {}
+            | ExecuteMsg::CancelAdminProposal {}
+            | ExecuteMsg::SetPause { .. } => Ok(()),
+            _ => Err(ContractError::EmergencyMode {}),
+        };
+    }
+
+    let paused = PAUSE_FLAGS.may_load(storage)?.unwrap_or_default();
+    match msg {
+        ExecuteMsg::Stake {}
+        | ExecuteMsg::Lock { .. }
+        | ExecuteMsg::TransferStake { .. }
+        | ExecuteMsg::AutoCompound { .. }
+        | ExecuteMsg::LiquidStake {}
+        | ExecuteMsg::StakeInPool { .. }
+            if paused.staking =>
+        {
+            Err(ContractError::StakingPaused {})
+        }
+        ExecuteMsg::ClaimReward { .. }
+        | ExecuteMsg::Restake {}
+        | ExecuteMsg::ClaimPoolRewards { .. }
+            if paused.claiming =>
+        {
+            Err(ContractError::ClaimingPaused {})
+        }
+        ExecuteMsg::NewEpoch {} if paused.epochs => Err(ContractError::EpochsPaused {}),
+        ExecuteMsg::EmergencyWithdraw { .. } => Err(ContractError::NotInEmergencyMode {}),
+        _ => Ok(()),
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn update_config(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
-    admin: Option<String>,
     epoch_length: Option<u64>,
     rewards_percentage: Option<Decimal>,
-) -> StdResult<Response> {
+    unbonding_period: Option<u64>,
+    reward_denoms: Option<Vec<String>>,
+    lock_tiers: Option<Vec<LockTier>>,
+    early_unstake_fee: Option<Option<EarlyUnstakeFee>>,
+    loyalty_tiers: Option<Vec<LoyaltyTier>>,
+    minter_address: Option<Option<String>>,
+    cap_interest_to_reserve: Option<bool>,
+    reward_expiry: Option<Option<RewardExpiry>>,
+    guardian: Option<Option<String>>,
+    wattpeak_denom: Option<String>,
+    staking_fee_percentage: Option<Decimal>,
+    staking_fee_address: Option<String>,
+    bridged_denoms: Option<Vec<String>>,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
     // Check if the sender is the admin
     let mut config = CONFIG.load(deps.storage)?;
     if info.sender != config.admin {
-        return Err(StdError::generic_err("Unauthorized"));
+        return Err(ContractError::Unauthorized {});
     }
+    let old_config = config.clone();
 
-    // Update the admin address if it was provided
-    if let Some(admin) = admin {
-        config.admin = Addr::unchecked(admin);
-    }
+    // Interest earned so far accrues at the old rate
+    accrue_interest(deps.storage, env.block.time.seconds())?;
 
-    // Update the rewards percentage if it was provided
+    // Update the rewards percentage from now on if it was provided, scheduled rate changes still
+    // take effect at their time
     if let Some(rewards_percentage) = rewards_percentage {
         config.rewards_percentage = rewards_percentage;
+        RATE_SCHEDULE.save(deps.storage, env.block.time.seconds(), &rewards_percentage)?;
     }
 
     // Update the epoch length if it was provided
@@ -56,1360 +235,7338 @@ fn update_config(
         config.epoch_length = epoch_length;
     }
 
+    // Update the unbonding period if it was provided, entries already unbonding keep their release time
+    if let Some(unbonding_period) = unbonding_period {
+        config.unbonding_period = unbonding_period;
+    }
+
+    // Update the reward denom whitelist if it was provided, rewards already claimable in a removed denom stay claimable
+    if let Some(reward_denoms) = reward_denoms {
+        config.reward_denoms = reward_denoms;
+    }
+
+    // Update the lock tiers if they were provided, existing locks keep their multiplier and penalty
+    if let Some(lock_tiers) = lock_tiers {
+        config.lock_tiers = lock_tiers;
+    }
+
+    // Update or remove the early unstake fee if it was provided
+    if let Some(early_unstake_fee) = early_unstake_fee {
+        config.early_unstake_fee = early_unstake_fee;
+    }
+
+    // Update the loyalty tiers if they were provided, stakers move to the new tiers when their
+    // record is next saved or their queued loyalty update is processed
+    if let Some(loyalty_tiers) = loyalty_tiers {
+        config.loyalty_tiers = loyalty_tiers;
+    }
+
+    // Update the minter address if it was provided, existing pools keep the project they were
+    // created for. The wattpeak denom becomes the denom the minter mints, unlinking the minter
+    // keeps the denom
+    if let Some(minter_address) = minter_address {
+        config.minter_address = match minter_address {
+            Some(minter_address) => {
+                let minter_address = deps.api.addr_validate(&minter_address)?;
+                config.wattpeak_denom = query_minter_denom(deps.as_ref(), &minter_address)?;
+                Some(minter_address)
+            }
+            None => None,
+        };
+    }
+
+    // Switch the cap of the interest to the reward reserve if it was provided, interest accrued
+    // so far stays promised
+    if let Some(cap_interest_to_reserve) = cap_interest_to_reserve {
+        config.cap_interest_to_reserve = cap_interest_to_reserve;
+    }
+
+    // Update or remove the claim window of rewards if it was provided, rewards already claimable
+    // expire with the new window
+    if let Some(reward_expiry) = reward_expiry {
+        config.reward_expiry = reward_expiry;
+    }
+
+    // Update or remove the guardian if it was provided
+    if let Some(guardian) = guardian {
+        config.guardian = guardian
+            .map(|guardian| deps.api.addr_validate(&guardian))
+            .transpose()?;
+    }
+
+    // Update the wattpeak denom if it was provided, the denom of a linked minter cannot be
+    // overridden
+    if let Some(wattpeak_denom) = wattpeak_denom {
+        if config.minter_address.is_some() && wattpeak_denom != config.wattpeak_denom {
+            return Err(ContractError::WattpeakDenomSetByMinter {});
+        }
+        config.wattpeak_denom = wattpeak_denom;
+    }
+
+    // The wattpeak denom, set directly or by a newly linked minter, can only change while the
+    // contract holds none of it for anyone, everything held is paid out in the denom it came in
+    if config.wattpeak_denom != old_config.wattpeak_denom
+        && wattpeak_denom_in_use(deps.storage, &old_config.wattpeak_denom)?
+    {
+        return Err(ContractError::WattpeakDenomInUse {});
+    }
+
+    // Update the bridged denoms if they were provided, stake in a removed denom can still be
+    // unstaked in that denom
+    if let Some(bridged_denoms) = bridged_denoms {
+        config.bridged_denoms = bridged_denoms;
+    }
+
+    // Update the staking fee if it was provided, fees are taken at the new percentage from now on
+    if let Some(staking_fee_percentage) = staking_fee_percentage {
+        config.staking_fee_percentage = staking_fee_percentage;
+    }
+
+    // Update the staking fee address if it was provided
+    if let Some(staking_fee_address) = staking_fee_address {
+        config.staking_fee_address = deps.api.addr_validate(&staking_fee_address)?;
+    }
+
     // Validate the updated configuration
     config.validate(deps.as_ref())?;
 
     // Save the updated configuration
     CONFIG.save(deps.storage, &config)?;
 
-    // If the epoch length was updated, update the yearly percentage
-    if let Some(epoch_length) = epoch_length {
-        set_yearly_percentage(deps, epoch_length)?;
+    Ok(Response::new()
+        .add_attribute("action", "update_config")
+        .add_event(config_change_event(&old_config, &config)?))
+}
+
+/// Lists the fields that changed between two configs with their old and new values, JSON encoded
+fn config_change_event(old: &Config, new: &Config) -> StdResult<Event> {
+    fn change<T: Serialize + PartialEq>(
+        event: Event,
+        field: &str,
+        old: &T,
+        new: &T,
+    ) -> StdResult<Event> {
+        if old == new {
+            return Ok(event);
+        }
+        Ok(event
+            .add_attribute(format!("old_{}", field), to_json_string(old)?)
+            .add_attribute(format!("new_{}", field), to_json_string(new)?))
     }
 
-    Ok(Response::new().add_attribute("action", "update_config"))
+    let event = Event::new("config_change");
+    let event = change(event, "admin", &old.admin, &new.admin)?;
+    let event = change(
+        event,
+        "rewards_percentage",
+        &old.rewards_percentage,
+        &new.rewards_percentage,
+    )?;
+    let event = change(event, "epoch_length", &old.epoch_length, &new.epoch_length)?;
+    let event = change(
+        event,
+        "wattpeak_denom",
+        &old.wattpeak_denom,
+        &new.wattpeak_denom,
+    )?;
+    let event = change(
+        event,
+        "staking_fee_percentage",
+        &old.staking_fee_percentage,
+        &new.staking_fee_percentage,
+    )?;
+    let event = change(
+        event,
+        "staking_fee_address",
+        &old.staking_fee_address,
+        &new.staking_fee_address,
+    )?;
+    let event = change(
+        event,
+        "unbonding_period",
+        &old.unbonding_period,
+        &new.unbonding_period,
+    )?;
+    let event = change(
+        event,
+        "reward_denoms",
+        &old.reward_denoms,
+        &new.reward_denoms,
+    )?;
+    let event = change(event, "lock_tiers", &old.lock_tiers, &new.lock_tiers)?;
+    let event = change(
+        event,
+        "early_unstake_fee",
+        &old.early_unstake_fee,
+        &new.early_unstake_fee,
+    )?;
+    let event = change(
+        event,
+        "loyalty_tiers",
+        &old.loyalty_tiers,
+        &new.loyalty_tiers,
+    )?;
+    let event = change(
+        event,
+        "minter_address",
+        &old.minter_address,
+        &new.minter_address,
+    )?;
+    let event = change(
+        event,
+        "cap_interest_to_reserve",
+        &old.cap_interest_to_reserve,
+        &new.cap_interest_to_reserve,
+    )?;
+    let event = change(
+        event,
+        "reward_expiry",
+        &old.reward_expiry,
+        &new.reward_expiry,
+    )?;
+    let event = change(event, "guardian", &old.guardian, &new.guardian)?;
+    change(
+        event,
+        "bridged_denoms",
+        &old.bridged_denoms,
+        &new.bridged_denoms,
+    )
 }
 
-fn stake_wattpeak(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
-    let staker_address = &info.sender;
+fn propose_admin(
+    deps: DepsMut,
+    info: MessageInfo,
+    admin: String,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
 
-    let amount = info
-        .funds
-        .iter()
-        //Change to correct contract address when minter is deployed
-        .find(|coin| coin.denom == config.wattpeak_denom)
-        .map(|coin| coin.amount)
-        .unwrap_or_else(Uint128::zero);
-    // Verify the correct amount of tokens was sent to the contract
-    //Change to correct contract address when minter is deployed
-    if !info
-        .funds
-        .iter()
-        .any(|coin| coin.denom == config.wattpeak_denom)
+    let pending_admin = deps.api.addr_validate(&admin)?;
+    PENDING_ADMIN.save(deps.storage, &pending_admin)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose_admin")
+        .add_attribute("admin", config.admin.to_string())
+        .add_attribute("pending_admin", pending_admin.to_string()))
+}
+
+fn accept_admin(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let pending_admin = PENDING_ADMIN
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoPendingAdmin {})?;
+    if info.sender != pending_admin {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut config = CONFIG.load(deps.storage)?;
+    let old_config = config.clone();
+    config.admin = pending_admin;
+    CONFIG.save(deps.storage, &config)?;
+    PENDING_ADMIN.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("action", "accept_admin")
+        .add_attribute("admin", config.admin.to_string())
+        .add_event(config_change_event(&old_config, &config)?))
+}
+
+fn cancel_admin_proposal(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    if info.sender != CONFIG.load(deps.storage)?.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    let pending_admin = PENDING_ADMIN
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoPendingAdmin {})?;
+    PENDING_ADMIN.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_admin_proposal")
+        .add_attribute("pending_admin", pending_admin.to_string()))
+}
+
+fn stake_wattpeak(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    lock_duration: Option<u64>,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let staker_address = &info.sender;
+    let config = CONFIG.load(deps.storage)?;
+    let lock_tier = lock_duration
+        .map(|duration| {
+            config
+                .lock_tiers
+                .iter()
+                .find(|tier| tier.duration == duration)
+                .cloned()
+                .ok_or(ContractError::NoLockTier { duration })
+        })
+        .transpose()?;
+
+    // Wattpeak is staked in wattpeak_denom or one of the bridged denoms, one denom at a time so
+    // nothing is sent without being credited. Locks only hold wattpeak_denom
+    let staked_coin = match info.funds.as_slice() {
+        [coin] => coin,
+        [] => return Err(ContractError::NoWattpeakStaked {}),
+        _ => return Err(ContractError::MultipleDenomsStaked {}),
+    };
+    if staked_coin.denom != config.wattpeak_denom
+        && (lock_tier.is_some() || !config.bridged_denoms.contains(&staked_coin.denom))
     {
-        return Err(StdError::generic_err(
-            "Must stake WattPeak tokens to the contract",
-        ));
+        return Err(ContractError::NoWattpeakStaked {});
     }
+    let amount = staked_coin.amount;
 
-    if !info.funds.iter().all(|coin| coin.amount > Uint128::zero()) {
-        return Err(StdError::generic_err("Stake amount can't be zero"));
+    if amount.is_zero() {
+        return Err(ContractError::ZeroStake {});
     }
 
-    // Check if the staker already exists
-    let mut staker = STAKERS.may_load(deps.storage, staker_address.clone())?;
+    // Accrue interest up to now before the total staked changes
+    let now = env.block.time.seconds();
+    accrue_interest(deps.storage, now)?;
 
-    match staker {
-        Some(ref mut s) => {
-            // If the staker exists, update their staked wattpeak
-            s.wattpeak_staked += amount;
-        }
-        None => {
-            // If the staker does not exist, create a new record
-            staker = Some(Staker {
-                wattpeak_staked: amount,
-                interest_wattpeak: Decimal::zero(),
-                stake_start_time: env.block.time.seconds(),
-                claimable_rewards: Decimal::zero(),
-            });
+    // Check if the staker already exists
+    let mut staking_fee = Uint128::zero();
+    let mut staker = match stakers().may_load(deps.storage, staker_address.clone())? {
+        Some(mut staker) => {
+            // Settle the interest earned on the previous stake before it changes
+            settle_staker(deps.storage, &mut staker, now)?;
+            if staker.auto_compound {
+                (_, staking_fee) =
+                    compound_staker(deps.storage, &mut staker, now, env.block.height)?;
+            }
+            staker
         }
+        // If the staker does not exist, create a new record
+        None => new_staker(deps.storage, now)?,
+    };
+
+    add_stake(&mut staker, amount, now)?;
+    if staked_coin.denom != config.wattpeak_denom {
+        add_bridged_stake(deps.storage, staker_address, &staked_coin.denom, amount)?;
+    }
+
+    let mut response = Response::new();
+    if let Some(tier) = lock_tier {
+        // Locked wattpeak earns interest as if the multiplier times the amount was staked
+        let boost = to_decimal(amount)?
+            .checked_mul(tier.multiplier - Decimal::one())?
+            .to_uint_floor();
+        let id = LOCK_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+        let unlock_time = now + tier.duration;
+        LOCKS.save(
+            deps.storage,
+            (staker_address.clone(), id),
+            &LockPosition {
+                amount,
+                multiplier: tier.multiplier,
+                early_unlock_penalty: tier.early_unlock_penalty,
+                boost,
+                start_time: now,
+                unlock_time,
+            },
+        )?;
+        LOCK_EXPIRIES.save(deps.storage, (unlock_time, id), staker_address)?;
+        staker.locked_wattpeak += amount;
+        staker.lock_boost += boost;
+        TOTAL_LOCK_BOOST.update(deps.storage, |total| -> StdResult<_> { Ok(total + boost) })?;
+
+        response = response
+            .add_attribute("lock_id", id.to_string())
+            .add_attribute("unlock_time", unlock_time.to_string());
     }
 
     // Save the updated or new staker record
-    STAKERS.save(deps.storage, staker_address.clone(), &staker.unwrap())?;
+    let hooks = save_staker(
+        deps.storage,
+        staker_address,
+        &mut staker,
+        now,
+        env.block.height,
+    )?;
 
     // Update the total wattpeak staked in the contract
-    TOTAL_WATTPEAK_STAKED.update(deps.storage, |total| -> StdResult<_> { Ok(total + amount) })?;
+    TOTAL_WATTPEAK_STAKED.update(deps.storage, env.block.height, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default() + amount)
+    })?;
 
     // Construct the response
-    Ok(Response::new()
+    Ok(response
+        .add_messages(staking_fee_msg(&config, staking_fee))
+        .add_submessages(hooks)
         .add_attribute("action", "stake_wattpeak")
         .add_attribute("from", info.sender.to_string())
-        .add_attribute("amount", amount.to_string()))
+        .add_attribute("amount", amount.to_string())
+        .add_attribute("denom", staked_coin.denom.clone()))
 }
 
-fn deposit_rewards(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
-    let wattpeak_denom = CONFIG.load(deps.storage)?.wattpeak_denom;
+fn unlock_early(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let staker_address = &info.sender;
+    let config = CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    // Locks that already expired are released while accruing
+    accrue_interest(deps.storage, now)?;
+    if !LOCKS.has(deps.storage, (staker_address.clone(), id)) {
+        return Err(ContractError::LockNotFound { id });
+    }
+    let lock = release_lock(deps.storage, staker_address, id, now)?;
+
+    // The penalty is paid from the unlocked stake
+    let penalty = to_decimal(lock.amount)?
+        .checked_mul(lock.early_unlock_penalty)?
+        .to_uint_floor();
+    let mut staker = stakers().load(deps.storage, staker_address.clone())?;
+    let mut staking_fee = Uint128::zero();
+    if staker.auto_compound {
+        (_, staking_fee) = compound_staker(deps.storage, &mut staker, now, env.block.height)?;
+    }
+    staker.wattpeak_staked -= penalty;
+    let hooks = save_staker(
+        deps.storage,
+        staker_address,
+        &mut staker,
+        now,
+        env.block.height,
+    )?;
+    TOTAL_WATTPEAK_STAKED.update(deps.storage, env.block.height, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default() - penalty)
+    })?;
+
+    let penalty_msg = (!penalty.is_zero()).then(|| BankMsg::Send {
+        to_address: config.staking_fee_address.to_string(),
+        amount: vec![Coin {
+            denom: config.wattpeak_denom.clone(),
+            amount: penalty,
+        }],
+    });
+
+    Ok(Response::new()
+        .add_messages(staking_fee_msg(&config, staking_fee))
+        .add_messages(penalty_msg)
+        .add_submessages(hooks)
+        .add_attribute("action", "unlock")
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("lock_id", id.to_string())
+        .add_attribute("penalty", penalty.to_string()))
+}
+
+fn deposit_rewards(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let wattpeak_denom = config.wattpeak_denom;
 
     let amount = info
         .funds
         .iter()
-        //Change to correct contract address when minter is deployed
         .find(|coin| coin.denom == wattpeak_denom)
         .map(|coin| coin.amount)
         .unwrap_or_else(Uint128::zero);
 
     // Check if the sender is the admin
     if info.sender != CONFIG.load(deps.storage)?.admin {
-        return Err(StdError::generic_err("Unauthorized"));
+        return Err(ContractError::Unauthorized {});
+    }
+    if info.funds.is_empty() {
+        return Err(ContractError::NoWattpeakDeposited {});
     }
-    //Change to correct contract address when minter is deployed
-    if !info.funds.iter().any(|coin| coin.denom == wattpeak_denom) {
-        return Err(StdError::generic_err(
-            "Must deposit WattPeak tokens to the contract",
-        ));
+    if let Some(coin) = info
+        .funds
+        .iter()
+        .find(|coin| coin.denom != wattpeak_denom && !config.reward_denoms.contains(&coin.denom))
+    {
+        return Err(ContractError::DenomNotWhitelisted {
+            denom: coin.denom.to_string(),
+        });
     }
 
     if !info.funds.iter().all(|coin| coin.amount > Uint128::zero()) {
-        return Err(StdError::generic_err("Deposit amount can't be zero"));
+        return Err(ContractError::ZeroDeposit {});
     }
+
+    let denom_amounts: Vec<Coin> = info
+        .funds
+        .iter()
+        .filter(|coin| coin.denom != wattpeak_denom)
+        .cloned()
+        .collect();
+
     // Calculate the share of rewards for each staker
-    calculate_staker_share_of_reward(deps, env, amount)
-        .map_err(|e| StdError::generic_err(format!("Failed to calculate staker shares: {}", e)))?;
+    calculate_staker_share_of_reward(deps, env, amount, &denom_amounts)?;
 
-    Ok(Response::new()
+    let mut response = Response::new()
         .add_attribute("method", "deposit_rewards")
         .add_attribute("from", info.sender.to_string())
-        .add_attribute("amount", amount.to_string()))
+        .add_attribute("amount", amount.to_string());
+    if !denom_amounts.is_empty() {
+        let denom_rewards: Vec<String> =
+            denom_amounts.iter().map(|coin| coin.to_string()).collect();
+        response = response.add_attribute("denom_rewards", denom_rewards.join(","));
+    }
+    Ok(response)
+}
+
+fn deposit_reward_stream(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    start: u64,
+    end: u64,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    if start < env.block.time.seconds() {
+        return Err(ContractError::StreamStartInPast {});
+    }
+    if end <= start {
+        return Err(ContractError::StreamEndBeforeStart {});
+    }
+    if info.funds.is_empty() {
+        return Err(ContractError::NoWattpeakDeposited {});
+    }
+    if let Some(coin) = info.funds.iter().find(|coin| {
+        coin.denom != config.wattpeak_denom && !config.reward_denoms.contains(&coin.denom)
+    }) {
+        return Err(ContractError::DenomNotWhitelisted {
+            denom: coin.denom.to_string(),
+        });
+    }
+    if !info.funds.iter().all(|coin| coin.amount > Uint128::zero()) {
+        return Err(ContractError::ZeroDeposit {});
+    }
+
+    // Every denom deposited is streamed on its own
+    let mut ids = vec![];
+    for coin in info.funds.iter() {
+        let id =
+            REWARD_STREAM_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+        REWARD_STREAMS.save(
+            deps.storage,
+            id,
+            &RewardStream {
+                denom: coin.denom.clone(),
+                amount: coin.amount,
+                released: Uint128::zero(),
+                start,
+                end,
+            },
+        )?;
+        ids.push(id.to_string());
+    }
+
+    let amounts: Vec<String> = info.funds.iter().map(|coin| coin.to_string()).collect();
+    Ok(Response::new()
+        .add_attribute("action", "deposit_reward_stream")
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("amount", amounts.join(","))
+        .add_attribute("stream_ids", ids.join(","))
+        .add_attribute("start", start.to_string())
+        .add_attribute("end", end.to_string()))
+}
+
+fn fund_reserve(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    // The reserve pays interest, which is paid in wattpeak only
+    let amount = match info.funds.as_slice() {
+        [coin] if coin.denom == config.wattpeak_denom => coin.amount,
+        _ => return Err(ContractError::NoWattpeakDeposited {}),
+    };
+    if amount.is_zero() {
+        return Err(ContractError::ZeroDeposit {});
+    }
+
+    let reserve = REWARD_RESERVE.update(deps.storage, |reserve| -> StdResult<_> {
+        Ok(reserve.checked_add(amount)?)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "fund_reserve")
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("amount", amount.to_string())
+        .add_attribute("reserve", reserve.to_string()))
 }
 
 fn unstake_wattpeak(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     amount: Uint128,
-) -> StdResult<Response> {
+    denom: Option<String>,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
     if amount == Uint128::zero() {
-        return Err(StdError::generic_err("Unstake amount can't be zero"));
+        return Err(ContractError::ZeroUnstake {});
     }
 
     let staker_address = &info.sender;
-    let wattpeak_denom = CONFIG.load(deps.storage)?.wattpeak_denom;
+    let config = CONFIG.load(deps.storage)?;
+    let denom = denom.unwrap_or_else(|| config.wattpeak_denom.clone());
+    let now = env.block.time.seconds();
 
-    let mut staker = STAKERS
+    // Accrue interest up to now before the total staked changes, expired locks are released
+    // before the staker is loaded
+    accrue_interest(deps.storage, now)?;
+    let mut staker = stakers()
         .load(deps.storage, staker_address.clone())
-        .map_err(|_| StdError::generic_err("Staker does not exist"))?;
+        .map_err(|_| ContractError::StakerNotFound {})?;
+    settle_staker(deps.storage, &mut staker, now)?;
+    let mut staking_fee = Uint128::zero();
+    if staker.auto_compound {
+        (_, staking_fee) = compound_staker(deps.storage, &mut staker, now, env.block.height)?;
+    }
 
-    // Check if the staker has enough wattpeak staked
+    // Check if the staker has enough wattpeak staked, in the denom it unstakes
     if staker.wattpeak_staked < amount {
-        return Err(StdError::generic_err("Insufficient staked wattpeak"));
+        return Err(ContractError::InsufficientStake {});
+    }
+    if denom == config.wattpeak_denom {
+        let canonical = canonical_stake(deps.storage, staker_address, &staker)?;
+        if canonical < amount {
+            return Err(ContractError::InsufficientDenomStake { denom });
+        }
+        // Locked wattpeak can only be unstaked once the lock expired or was unlocked early
+        if canonical.saturating_sub(staker.locked_wattpeak) < amount {
+            return Err(ContractError::InsufficientUnlockedStake {});
+        }
+    } else {
+        let bridged = BRIDGED_STAKES
+            .may_load(deps.storage, (staker_address.clone(), &denom))?
+            .unwrap_or_default();
+        if bridged < amount {
+            return Err(ContractError::InsufficientDenomStake { denom });
+        }
+        remove_bridged_stake(deps.storage, staker_address, &denom, amount)?;
     }
 
+    // Wattpeak unstaked shortly after it was staked pays the early unstake fee
+    let early_unstake_fee = early_unstake_fee(&config, &staker, amount, now)?;
     staker.wattpeak_staked -= amount;
 
     // Save the updated staker record
-    STAKERS.save(deps.storage, staker_address.clone(), &staker)?;
+    let hooks = save_staker(
+        deps.storage,
+        staker_address,
+        &mut staker,
+        now,
+        env.block.height,
+    )?;
 
     // Update the total wattpeak staked in the contract
-    TOTAL_WATTPEAK_STAKED.update(deps.storage, |total| -> StdResult<_> { Ok(total - amount) })?;
+    TOTAL_WATTPEAK_STAKED.update(deps.storage, env.block.height, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default() - amount)
+    })?;
+
+    remove_staker_if_empty(deps.storage, staker_address, &staker, env.block.height)?;
+
+    let response = Response::new()
+        .add_messages(staking_fee_msg(&config, staking_fee))
+        .add_messages(fee_msg(&config, &denom, early_unstake_fee))
+        .add_submessages(hooks)
+        .add_attribute("action", "unstake")
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("amount", amount.to_string())
+        .add_attribute("denom", denom.clone())
+        .add_attribute("early_unstake_fee", early_unstake_fee.to_string());
+
+    release_unstaked(
+        deps.storage,
+        &config,
+        staker_address,
+        &denom,
+        amount - early_unstake_fee,
+        now,
+        response,
+    )
+}
+
+/// Returns the early unstake fee on `amount` unstaked by a staker at `now`, declining linearly
+/// over the fee period from the stake start time
+fn early_unstake_fee(
+    config: &Config,
+    staker: &Staker,
+    amount: Uint128,
+    now: u64,
+) -> Result<Uint128, ContractError> {
+    let fee = match &config.early_unstake_fee {
+        Some(fee) => fee,
+        None => return Ok(Uint128::zero()),
+    };
+    let staked_for = now.saturating_sub(staker.stake_start_time);
+    if staked_for >= fee.period {
+        return Ok(Uint128::zero());
+    }
+    let remaining = Decimal::from_ratio(fee.period - staked_for, fee.period);
+    Ok(to_decimal(amount)?
+        .checked_mul(fee.percentage)?
+        .checked_mul(remaining)?
+        .to_uint_floor())
+}
+
+/// Holds the wattpeak of `address` unstaked in `denom` until the unbonding period ends, or sends
+/// it right away when there is no unbonding period
+fn release_unstaked(
+    storage: &mut dyn Storage,
+    config: &Config,
+    address: &Addr,
+    denom: &str,
+    amount: Uint128,
+    now: u64,
+    response: Response<TokenFactoryMsg>,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    if config.unbonding_period > 0 {
+        // The unstaked wattpeak no longer earns interest and is held until the unbonding period ends
+        let release_at = now + config.unbonding_period;
+        let id = UNBONDING_COUNT.update(storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+        UNBONDINGS.save(
+            storage,
+            (address.clone(), id),
+            &UnbondingEntry {
+                amount,
+                release_at,
+                denom: (denom != config.wattpeak_denom).then(|| denom.to_string()),
+            },
+        )?;
+
+        return Ok(response
+            .add_attribute("unbonding_id", id.to_string())
+            .add_attribute("release_at", release_at.to_string()));
+    }
 
     // Create a bank message to send tokens to the staker's address
     let payment_msg = BankMsg::Send {
-        to_address: staker_address.to_string(),
+        to_address: address.to_string(),
         amount: vec![Coin {
-            //Change to correct contract address when minter is deployed
-            denom: wattpeak_denom,
-            amount: amount,
+            denom: denom.to_string(),
+            amount,
         }],
     };
 
-    if staker.wattpeak_staked.is_zero()
-        && staker.claimable_rewards.is_zero()
-        && staker.interest_wattpeak.is_zero()
-    {
-        STAKERS.remove(deps.storage, staker_address.clone());
+    // Construct the response
+    Ok(response.add_message(payment_msg))
+}
+
+fn withdraw_unbonded(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let staker_address = &info.sender;
+    let now = env.block.time.seconds();
+
+    let released: Vec<_> = UNBONDINGS
+        .prefix(staker_address.clone())
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?
+        .into_iter()
+        .filter(|(_, entry)| entry.release_at <= now)
+        .collect();
+
+    if released.is_empty() {
+        return Err(ContractError::NoUnbondedWattpeak {});
     }
 
-    // Construct the response
+    // Every entry is paid in the denom it was unstaked in
+    let wattpeak_denom = CONFIG.load(deps.storage)?.wattpeak_denom;
+    let mut amount = Uint128::zero();
+    let mut coins = vec![];
+    for (id, entry) in released {
+        amount += entry.amount;
+        add_coin(
+            &mut coins,
+            Coin {
+                denom: entry.denom.unwrap_or_else(|| wattpeak_denom.clone()),
+                amount: entry.amount,
+            },
+        )?;
+        UNBONDINGS.remove(deps.storage, (staker_address.clone(), id));
+    }
+    coins.sort_by(|a, b| a.denom.cmp(&b.denom));
+
+    let payment_msg = BankMsg::Send {
+        to_address: staker_address.to_string(),
+        amount: coins,
+    };
+
     Ok(Response::new()
         .add_message(payment_msg)
-        .add_attribute("action", "unstake")
+        .add_attribute("action", "withdraw_unbonded")
         .add_attribute("from", info.sender.to_string())
         .add_attribute("amount", amount.to_string()))
 }
 
-fn claim_rewards(deps: DepsMut, _env: Env, info: MessageInfo) -> StdResult<Response> {
+fn transfer_stake(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    amount: Uint128,
+    include_rewards: bool,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::ZeroTransfer {});
+    }
     let staker_address = &info.sender;
+    let recipient_address = deps.api.addr_validate(&recipient)?;
+    if recipient_address == *staker_address {
+        return Err(ContractError::TransferToSelf {});
+    }
+    // The stake of the contract is the liquid staking pool, which is only entered through stWP
+    if recipient_address == env.contract.address {
+        return Err(ContractError::TransferToContract {});
+    }
 
-    // Check if the staker exists
-    let mut staker = STAKERS.load(deps.storage, staker_address.clone())?;
-    if staker.claimable_rewards.is_zero() {
-        return Err(StdError::generic_err("No rewards to claim"));
+    let config = CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    // Both records are settled up to now, expired locks are released before they are loaded
+    accrue_interest(deps.storage, now)?;
+    let mut staker = stakers()
+        .load(deps.storage, staker_address.clone())
+        .map_err(|_| ContractError::StakerNotFound {})?;
+    settle_staker(deps.storage, &mut staker, now)?;
+    let mut staking_fee = Uint128::zero();
+    if staker.auto_compound {
+        (_, staking_fee) = compound_staker(deps.storage, &mut staker, now, env.block.height)?;
     }
-    let rewards = staker.claimable_rewards;
 
-    let staking_fee = CONFIG.load(deps.storage)?.staking_fee_percentage;
+    if staker.wattpeak_staked < amount {
+        return Err(ContractError::InsufficientStake {});
+    }
+    // Locks and bridged stake stay with the staker, only unlocked wattpeak in wattpeak_denom can be
+    // transferred
+    if canonical_stake(deps.storage, staker_address, &staker)?
+        .saturating_sub(staker.locked_wattpeak)
+        < amount
+    {
+        return Err(ContractError::InsufficientUnlockedStake {});
+    }
 
-    let staking_fee = rewards.checked_mul(staking_fee).unwrap();
+    let mut recipient_staker = match stakers().may_load(deps.storage, recipient_address.clone())? {
+        Some(mut recipient_staker) => {
+            settle_staker(deps.storage, &mut recipient_staker, now)?;
+            recipient_staker
+        }
+        None => new_staker(deps.storage, now)?,
+    };
 
-    let reward_payment = rewards.checked_sub(staking_fee).unwrap();
+    // The transferred stake keeps its start time, so a transfer neither resets the loyalty of
+    // the stake nor avoids the early unstake fee
+    staker.wattpeak_staked -= amount;
+    add_stake(&mut recipient_staker, amount, staker.stake_start_time)?;
+
+    // Both records are settled in the current round, so the interest accrued in it moves as is.
+    // The rewards keep the epochs they expire with
+    if include_rewards {
+        sync_reward_buckets(deps.storage, &mut staker)?;
+        sync_reward_buckets(deps.storage, &mut recipient_staker)?;
+        for bucket in staker.reward_buckets.drain(..) {
+            merge_reward_bucket(&mut recipient_staker.reward_buckets, bucket)?;
+        }
+        recipient_staker.interest_wattpeak = recipient_staker
+            .interest_wattpeak
+            .checked_add(staker.interest_wattpeak)?;
+        recipient_staker.claimable_rewards = recipient_staker
+            .claimable_rewards
+            .checked_add(staker.claimable_rewards)?;
+        for reward in staker.claimable_denom_rewards.drain(..) {
+            add_denom_reward(
+                &mut recipient_staker.claimable_denom_rewards,
+                &reward.denom,
+                reward.amount,
+            )?;
+        }
+        staker.interest_wattpeak = Decimal::zero();
+        staker.claimable_rewards = Decimal::zero();
+    }
 
-    let reward_denom = CONFIG.load(deps.storage)?.wattpeak_denom;
+    let mut hooks = save_staker(
+        deps.storage,
+        staker_address,
+        &mut staker,
+        now,
+        env.block.height,
+    )?;
+    hooks.extend(save_staker(
+        deps.storage,
+        &recipient_address,
+        &mut recipient_staker,
+        now,
+        env.block.height,
+    )?);
+    remove_staker_if_empty(deps.storage, staker_address, &staker, env.block.height)?;
 
-    // Convert the rewards from Decimal to Uint128
-    let rewards_amount = if let Some(amount) = reward_payment.to_string().parse::<f64>().ok() {
-        Uint128::from(amount as u128) // safely truncating the decimal part
-    } else {
-        return Err(StdError::generic_err("Failed to parse rewards amount"));
-    };
+    Ok(Response::new()
+        .add_messages(staking_fee_msg(&config, staking_fee))
+        .add_submessages(hooks)
+        .add_attribute("action", "transfer_stake")
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("to", recipient_address.to_string())
+        .add_attribute("amount", amount.to_string())
+        .add_attribute("include_rewards", include_rewards.to_string()))
+}
 
-    // Convert the rewards from Decimal to Uint128
-    let staking_fee_amount = if let Some(amount) = staking_fee.to_string().parse::<f64>().ok() {
-        Uint128::from(amount as u128) // safely truncating the decimal part
-    } else {
-        return Err(StdError::generic_err("Failed to parse rewards amount"));
+fn claim_rewards(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: Option<String>,
+    ibc: Option<IbcClaim>,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let staker_address = &info.sender;
+    let now = env.block.time.seconds();
+    if recipient.is_some() && ibc.is_some() {
+        return Err(ContractError::ClaimToRecipientAndIbc {});
+    }
+    // Rewards go to the recipient of the claim, else to the staker's withdraw address
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => WITHDRAW_ADDRESSES
+            .may_load(deps.storage, staker_address.clone())?
+            .unwrap_or_else(|| staker_address.clone()),
     };
 
-    // Create a bank message to send tokens to the staker's address
-    let payment_msg = BankMsg::Send {
-        to_address: staker_address.to_string(),
-        amount: vec![Coin {
-            //Change to correct contract address when minter is deployed
-            denom: reward_denom.clone(),
-            amount: rewards_amount,
-        }],
-    };
+    accrue_interest(deps.storage, now)?;
+    // Check if the staker exists
+    let mut staker = stakers()
+        .may_load(deps.storage, staker_address.clone())?
+        .ok_or(ContractError::StakerNotFound {})?;
+    settle_staker(deps.storage, &mut staker, now)?;
 
-    let staking_fee_msg = BankMsg::Send {
-        to_address: CONFIG.load(deps.storage)?.staking_fee_address.to_string(),
-        amount: vec![Coin {
-            //Change to correct contract address when minter is deployed
-            denom: reward_denom.clone(),
-            amount: staking_fee_amount,
-        }],
-    };
+    let config = CONFIG.load(deps.storage)?;
 
-    // Update the staker's claimable rewards
-    staker.claimable_rewards = Decimal::zero();
+    // Only whole units are paid out, the fraction of every denom carries over to the next claim
+    let (rewards_amount, staking_fee_amount) =
+        take_whole_rewards(&mut staker.claimable_rewards, config.staking_fee_percentage)?;
+    let rewards = rewards_amount + staking_fee_amount;
+
+    let mut payment = vec![Coin {
+        denom: config.wattpeak_denom.clone(),
+        amount: rewards_amount,
+    }];
+    let mut staking_fees = vec![Coin {
+        denom: config.wattpeak_denom.clone(),
+        amount: staking_fee_amount,
+    }];
+
+    // The staking fee is taken from every reward denom as well
+    for reward in staker.claimable_denom_rewards.iter_mut() {
+        let (amount, fee) = take_whole_rewards(&mut reward.amount, config.staking_fee_percentage)?;
+        payment.push(Coin {
+            denom: reward.denom.clone(),
+            amount,
+        });
+        staking_fees.push(Coin {
+            denom: reward.denom.clone(),
+            amount: fee,
+        });
+    }
+    staker
+        .claimable_denom_rewards
+        .retain(|reward| !reward.amount.is_zero());
+    payment.retain(|coin| !coin.amount.is_zero());
+    staking_fees.retain(|coin| !coin.amount.is_zero());
+
+    if payment.is_empty() && staking_fees.is_empty() {
+        return Err(ContractError::NoRewardsToClaim {});
+    }
+    record_staking_fees(deps.storage, &staking_fees)?;
+
+    let mut messages = vec![];
+    let mut transfers = vec![];
+    // Send the rewards over IBC, or with a bank message to the recipient
+    if !payment.is_empty() {
+        match &ibc {
+            Some(claim) => {
+                transfers = ibc_transfer_msgs(deps.storage, &env, staker_address, claim, payment)?;
+            }
+            None => messages.push(BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: payment,
+            }),
+        }
+    }
+    if !staking_fees.is_empty() {
+        messages.push(BankMsg::Send {
+            to_address: config.staking_fee_address.to_string(),
+            amount: staking_fees,
+        });
+    }
 
     // Save the updated staker record
-    STAKERS.save(deps.storage, staker_address.clone(), &staker)?;
+    let hooks = save_staker(
+        deps.storage,
+        staker_address,
+        &mut staker,
+        now,
+        env.block.height,
+    )?;
+    remove_staker_if_empty(deps.storage, staker_address, &staker, env.block.height)?;
 
-    if staker.wattpeak_staked.is_zero()
-        && staker.claimable_rewards.is_zero()
-        && staker.interest_wattpeak.is_zero()
+    // Construct the response
+    let recipient = match ibc {
+        Some(claim) => format!("{}/{}", claim.channel_id, claim.receiver),
+        None => recipient.to_string(),
+    };
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_submessages(transfers)
+        .add_submessages(hooks)
+        .add_attribute("action", "claim_rewards")
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("recipient", recipient)
+        .add_attribute("amount", rewards.to_string()))
+}
+
+fn set_withdraw_address(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: Option<String>,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let response = Response::new()
+        .add_attribute("action", "set_withdraw_address")
+        .add_attribute("from", info.sender.to_string());
+    match address {
+        Some(address) => {
+            let withdraw_address = deps.api.addr_validate(&address)?;
+            WITHDRAW_ADDRESSES.save(deps.storage, info.sender, &withdraw_address)?;
+            Ok(response.add_attribute("withdraw_address", withdraw_address))
+        }
+        None => {
+            WITHDRAW_ADDRESSES.remove(deps.storage, info.sender);
+            Ok(response)
+        }
+    }
+}
+
+fn restake_rewards(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let staker_address = &info.sender;
+    let now = env.block.time.seconds();
+
+    // Accrue interest up to now before the total staked changes
+    accrue_interest(deps.storage, now)?;
+    let mut staker = stakers()
+        .load(deps.storage, staker_address.clone())
+        .map_err(|_| ContractError::StakerNotFound {})?;
+    settle_staker(deps.storage, &mut staker, now)?;
+
+    let (restaked, staking_fee) =
+        compound_staker(deps.storage, &mut staker, now, env.block.height)?;
+    if restaked.is_zero() && staking_fee.is_zero() {
+        return Err(ContractError::NoRewardsToRestake {});
+    }
+
+    let hooks = save_staker(
+        deps.storage,
+        staker_address,
+        &mut staker,
+        now,
+        env.block.height,
+    )?;
+
+    let config = CONFIG.load(deps.storage)?;
+    Ok(Response::new()
+        .add_messages(staking_fee_msg(&config, staking_fee))
+        .add_submessages(hooks)
+        .add_attribute("action", "restake")
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("amount", restaked.to_string())
+        .add_attribute("staking_fee", staking_fee.to_string()))
+}
+
+fn set_auto_compound(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    enabled: bool,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let staker_address = &info.sender;
+    let now = env.block.time.seconds();
+
+    accrue_interest(deps.storage, now)?;
+    let mut staker = stakers()
+        .load(deps.storage, staker_address.clone())
+        .map_err(|_| ContractError::StakerNotFound {})?;
+    settle_staker(deps.storage, &mut staker, now)?;
+
+    // Rewards claimable at the time auto compounding is enabled are restaked right away
+    let mut staking_fee = Uint128::zero();
+    if enabled {
+        (_, staking_fee) = compound_staker(deps.storage, &mut staker, now, env.block.height)?;
+    }
+    staker.auto_compound = enabled;
+
+    let hooks = save_staker(
+        deps.storage,
+        staker_address,
+        &mut staker,
+        now,
+        env.block.height,
+    )?;
+
+    let config = CONFIG.load(deps.storage)?;
+    Ok(Response::new()
+        .add_messages(staking_fee_msg(&config, staking_fee))
+        .add_submessages(hooks)
+        .add_attribute("action", "auto_compound")
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("enabled", enabled.to_string()))
+}
+
+fn schedule_rate_change(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rewards_percentage: Decimal,
+    start: RateChangeStart,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    if rewards_percentage > Decimal::percent(100) {
+        return Err(ContractError::RewardsPercentageTooHigh {});
+    }
+
+    let time = match start {
+        RateChangeStart::Time(time) => time,
+        RateChangeStart::Epoch(epoch) => {
+            // Epochs keep their schedule from the start of the epoch in progress
+            let current_epoch = EPOCH_COUNT.load(deps.storage)? + 1;
+            if epoch <= current_epoch {
+                return Err(ContractError::EpochAlreadyStarted { epoch });
+            }
+            LAST_EPOCH_TIME.load(deps.storage)? + (epoch - current_epoch) * config.epoch_length
+        }
+    };
+    if time <= env.block.time.seconds() {
+        return Err(ContractError::RateChangeNotInFuture {});
+    }
+    RATE_SCHEDULE.save(deps.storage, time, &rewards_percentage)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "schedule_rate_change")
+        .add_attribute("rewards_percentage", rewards_percentage.to_string())
+        .add_attribute("time", time.to_string()))
+}
+
+fn cancel_rate_change(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    time: u64,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    if info.sender != CONFIG.load(deps.storage)?.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    if time <= env.block.time.seconds() {
+        return Err(ContractError::RateChangeTookEffect { time });
+    }
+    if !RATE_SCHEDULE.has(deps.storage, time) {
+        return Err(ContractError::RateChangeNotFound { time });
+    }
+    RATE_SCHEDULE.remove(deps.storage, time);
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_rate_change")
+        .add_attribute("time", time.to_string()))
+}
+
+fn update_staker_loyalty(
+    deps: DepsMut,
+    env: Env,
+    address: String,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let staker_address = deps.api.addr_validate(&address)?;
+    let now = env.block.time.seconds();
+
+    // Anyone can bring the loyalty of a staker up to date, e.g. after the loyalty tiers changed
+    accrue_interest(deps.storage, now)?;
+    let mut staker = stakers()
+        .load(deps.storage, staker_address.clone())
+        .map_err(|_| ContractError::StakerNotFound {})?;
+    settle_staker(deps.storage, &mut staker, now)?;
+    update_loyalty(deps.storage, &staker_address, &mut staker, now)?;
+    stakers().save(deps.storage, staker_address, &staker)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_loyalty")
+        .add_attribute("address", address)
+        .add_attribute("loyalty_boost", staker.loyalty_boost.to_string()))
+}
+
+fn sweep_expired(
+    deps: DepsMut,
+    env: Env,
+    limit: Option<u32>,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let expiry = config
+        .reward_expiry
+        .ok_or(ContractError::RewardsDoNotExpire {})?;
+    let now = env.block.time.seconds();
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    // Every sweep goes through the next batch of stakers, the one after the last starts over
+    let start = SWEEP_CURSOR.may_load(deps.storage)?.map(Bound::exclusive);
+    let addresses = stakers()
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+    match addresses.last() {
+        Some(last) if addresses.len() == limit => SWEEP_CURSOR.save(deps.storage, last)?,
+        _ => SWEEP_CURSOR.remove(deps.storage),
+    }
+
+    accrue_interest(deps.storage, now)?;
+    let epoch = EPOCH_COUNT.load(deps.storage)? + 1;
+    let mut expired = vec![];
+    let mut hooks = vec![];
+    let mut removed = 0u64;
+    for address in &addresses {
+        let mut staker = stakers().load(deps.storage, address.clone())?;
+        settle_staker(deps.storage, &mut staker, now)?;
+        sync_reward_buckets(deps.storage, &mut staker)?;
+        for reward in
+            take_expired_rewards(&mut staker, expiry.epochs, epoch, &config.wattpeak_denom)?
+        {
+            add_denom_reward(&mut expired, &reward.denom, reward.amount)?;
+        }
+        hooks.extend(save_staker(
+            deps.storage,
+            address,
+            &mut staker,
+            now,
+            env.block.height,
+        )?);
+        remove_staker_if_empty(deps.storage, address, &staker, env.block.height)?;
+        if !stakers().has(deps.storage, address.clone()) {
+            removed += 1;
+        }
+    }
+
+    // Whole units go to the treasury, the fractions become reward dust
+    let mut swept = vec![];
+    for reward in expired {
+        let amount = reward.amount.to_uint_floor();
+        add_reward_dust(
+            deps.storage,
+            &reward.denom,
+            reward.amount.checked_sub(to_decimal(amount)?)?,
+        )?;
+        if !amount.is_zero() {
+            swept.push(Coin {
+                denom: reward.denom,
+                amount,
+            });
+        }
+    }
+
+    let mut response = Response::new();
+    if !swept.is_empty() {
+        response = response.add_message(BankMsg::Send {
+            to_address: expiry.treasury.to_string(),
+            amount: swept.clone(),
+        });
+    }
+    Ok(response
+        .add_submessages(hooks)
+        .add_attribute("action", "sweep_expired")
+        .add_attribute("stakers", addresses.len().to_string())
+        .add_attribute("removed", removed.to_string())
+        .add_attribute(
+            "swept",
+            swept
+                .iter()
+                .map(|coin| coin.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        ))
+}
+
+fn process_due_expiries(
+    deps: DepsMut,
+    env: Env,
+    limit: Option<u32>,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let now = accrual_time(deps.storage, env.block.time.seconds())?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+
+    // Anyone can work through a backlog of expired locks and loyalty updates longer than the
+    // accrual of a message processes, interest accrues up to the last one processed
+    let (processed, done) = process_expiries(deps.storage, now, limit)?;
+    if done {
+        accrue_interest(deps.storage, now)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "process_expiries")
+        .add_attribute("processed", processed.to_string())
+        .add_attribute("done", done.to_string()))
+}
+
+fn add_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    fail_on_error: bool,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    if info.sender != CONFIG.load(deps.storage)?.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let address = deps.api.addr_validate(&address)?;
+    if HOOKS.has(deps.storage, address.clone()) {
+        return Err(ContractError::HookExists {
+            address: address.to_string(),
+        });
+    }
+    HOOKS.save(deps.storage, address.clone(), &fail_on_error)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_hook")
+        .add_attribute("address", address)
+        .add_attribute("fail_on_error", fail_on_error.to_string()))
+}
+
+fn remove_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    if info.sender != CONFIG.load(deps.storage)?.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let address = deps.api.addr_validate(&address)?;
+    if !HOOKS.has(deps.storage, address.clone()) {
+        return Err(ContractError::HookNotFound {
+            address: address.to_string(),
+        });
+    }
+    HOOKS.remove(deps.storage, address.clone());
+
+    Ok(Response::new()
+        .add_attribute("action", "remove_hook")
+        .add_attribute("address", address))
+}
+
+fn create_liquid_denom(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    if info.sender != CONFIG.load(deps.storage)?.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    if LIQUID_DENOM.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::LiquidDenomExists {});
+    }
+
+    let full_denom = format!("factory/{}/{}", env.contract.address, LIQUID_SUBDENOM);
+    LIQUID_DENOM.save(deps.storage, &full_denom)?;
+    LIQUID_SUPPLY.save(deps.storage, &Uint128::zero())?;
+
+    Ok(Response::new()
+        .add_message(create_liquid_denom_msg(full_denom.clone()))
+        .add_attribute("action", "create_liquid_denom")
+        .add_attribute("denom", full_denom))
+}
+
+fn liquid_stake(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let liquid_denom = LIQUID_DENOM
+        .may_load(deps.storage)?
+        .ok_or(ContractError::LiquidStakingNotEnabled {})?;
+
+    // Only wattpeak is sent, any other coin next to it would not be credited
+    let amount = match info.funds.as_slice() {
+        [coin] if coin.denom == config.wattpeak_denom => coin.amount,
+        [] | [_] => return Err(ContractError::NoWattpeakStaked {}),
+        _ => return Err(ContractError::MultipleDenomsStaked {}),
+    };
+    if amount.is_zero() {
+        return Err(ContractError::NoWattpeakStaked {});
+    }
+
+    // Accrue interest up to now before the total staked changes
+    let now = env.block.time.seconds();
+    accrue_interest(deps.storage, now)?;
+    let pool_address = env.contract.address;
+    let (mut pool, staking_fee) =
+        load_liquid_pool(deps.storage, &pool_address, now, env.block.height)?;
+
+    // stWP is minted at the exchange rate before the deposit
+    let supply = LIQUID_SUPPLY.load(deps.storage)?;
+    let minted = if supply.is_zero() || pool.wattpeak_staked.is_zero() {
+        amount
+    } else {
+        amount.multiply_ratio(supply, pool.wattpeak_staked)
+    };
+    if minted.is_zero() {
+        return Err(ContractError::LiquidStakeTooSmall {});
+    }
+
+    add_stake(&mut pool, amount, now)?;
+    let hooks = save_staker(
+        deps.storage,
+        &pool_address,
+        &mut pool,
+        now,
+        env.block.height,
+    )?;
+    TOTAL_WATTPEAK_STAKED.update(deps.storage, env.block.height, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default() + amount)
+    })?;
+    LIQUID_SUPPLY.save(deps.storage, &(supply + minted))?;
+
+    Ok(Response::new()
+        .add_messages(staking_fee_msg(&config, staking_fee))
+        .add_submessages(hooks)
+        .add_message(TokenFactoryMsg::MintTokens {
+            denom: liquid_denom,
+            amount: minted,
+            mint_to_address: info.sender.to_string(),
+        })
+        .add_attribute("action", "liquid_stake")
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("amount", amount.to_string())
+        .add_attribute("minted", minted.to_string()))
+}
+
+fn liquid_unstake(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let liquid_denom = LIQUID_DENOM
+        .may_load(deps.storage)?
+        .ok_or(ContractError::LiquidStakingNotEnabled {})?;
+
+    // Only stWP is sent, any other coin next to it would not be returned
+    let burned = match info.funds.as_slice() {
+        [coin] if coin.denom == liquid_denom => coin.amount,
+        [] | [_] => return Err(ContractError::NoLiquidTokensSent {}),
+        _ => return Err(ContractError::MultipleDenomsStaked {}),
+    };
+    if burned.is_zero() {
+        return Err(ContractError::NoLiquidTokensSent {});
+    }
+
+    // Accrue interest up to now before the total staked changes
+    let now = env.block.time.seconds();
+    accrue_interest(deps.storage, now)?;
+    let pool_address = env.contract.address;
+    let (mut pool, staking_fee) =
+        load_liquid_pool(deps.storage, &pool_address, now, env.block.height)?;
+
+    // stWP is redeemed at the exchange rate including the rewards just compounded. The pool has
+    // no start time per holder, so no early unstake fee applies
+    let supply = LIQUID_SUPPLY.load(deps.storage)?;
+    let amount = burned.multiply_ratio(pool.wattpeak_staked, supply);
+    if amount.is_zero() {
+        return Err(ContractError::LiquidUnstakeTooSmall {});
+    }
+
+    // Rewards in other denoms than wattpeak can't be restaked, the stWP redeemed claims its share
+    // of them with the staking fee taken the same as a claim
+    let share = Decimal::from_ratio(burned, supply);
+    let mut payment = vec![];
+    let mut staking_fees = vec![];
+    for reward in pool.claimable_denom_rewards.iter_mut() {
+        let mut claimed = reward.amount.checked_mul(share)?;
+        let (amount, fee) = take_whole_rewards(&mut claimed, config.staking_fee_percentage)?;
+        reward.amount = reward.amount.checked_sub(to_decimal(amount + fee)?)?;
+        payment.push(Coin {
+            denom: reward.denom.clone(),
+            amount,
+        });
+        staking_fees.push(Coin {
+            denom: reward.denom.clone(),
+            amount: fee,
+        });
+    }
+    pool.claimable_denom_rewards
+        .retain(|reward| !reward.amount.is_zero());
+    payment.retain(|coin| !coin.amount.is_zero());
+    staking_fees.retain(|coin| !coin.amount.is_zero());
+    record_staking_fees(deps.storage, &staking_fees)?;
+
+    let mut messages = vec![];
+    if !payment.is_empty() {
+        messages.push(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: payment,
+        });
+    }
+    if !staking_fees.is_empty() {
+        messages.push(BankMsg::Send {
+            to_address: config.staking_fee_address.to_string(),
+            amount: staking_fees,
+        });
+    }
+
+    pool.wattpeak_staked -= amount;
+    let hooks = save_staker(
+        deps.storage,
+        &pool_address,
+        &mut pool,
+        now,
+        env.block.height,
+    )?;
+    TOTAL_WATTPEAK_STAKED.update(deps.storage, env.block.height, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default() - amount)
+    })?;
+    LIQUID_SUPPLY.save(deps.storage, &(supply - burned))?;
+
+    let response = Response::new()
+        .add_messages(staking_fee_msg(&config, staking_fee))
+        .add_messages(messages)
+        .add_submessages(hooks)
+        .add_message(TokenFactoryMsg::BurnTokens {
+            denom: liquid_denom,
+            amount: burned,
+            burn_from_address: pool_address.to_string(),
+        })
+        .add_attribute("action", "liquid_unstake")
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("burned", burned.to_string())
+        .add_attribute("amount", amount.to_string());
+
+    release_unstaked(
+        deps.storage,
+        &config,
+        &info.sender,
+        &config.wattpeak_denom,
+        amount,
+        now,
+        response,
+    )
+}
+
+fn create_pool(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    project_id: u64,
+    rewards_percentage: Decimal,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    if info.sender != CONFIG.load(deps.storage)?.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    if POOLS.has(deps.storage, project_id) {
+        return Err(ContractError::PoolExists { project_id });
+    }
+    if rewards_percentage > Decimal::one() {
+        return Err(ContractError::RewardsPercentageTooHigh {});
+    }
+
+    // The project has to exist in the minter, the pool takes its name
+    let project = query_minter_project(deps.as_ref(), project_id)?;
+
+    POOLS.save(
+        deps.storage,
+        project_id,
+        &Pool {
+            project_id,
+            name: project.name.clone(),
+            rewards_percentage,
+            total_staked: Uint128::zero(),
+            interest_index: Decimal::zero(),
+            last_accrual_time: env.block.time.seconds(),
+            total_interest: Decimal::zero(),
+            reward_round: 0,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "create_pool")
+        .add_attribute("project_id", project_id.to_string())
+        .add_attribute("name", project.name)
+        .add_attribute("rewards_percentage", rewards_percentage.to_string()))
+}
+
+fn update_pool(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    project_id: u64,
+    rewards_percentage: Decimal,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    if info.sender != CONFIG.load(deps.storage)?.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    if rewards_percentage > Decimal::one() {
+        return Err(ContractError::RewardsPercentageTooHigh {});
+    }
+
+    // Interest earned in the pool so far accrues at the old rate
+    let mut pool = load_pool(deps.storage, project_id, env.block.time.seconds())?;
+    pool.rewards_percentage = rewards_percentage;
+    POOLS.save(deps.storage, project_id, &pool)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_pool")
+        .add_attribute("project_id", project_id.to_string())
+        .add_attribute("rewards_percentage", rewards_percentage.to_string()))
+}
+
+fn stake_in_pool(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    project_id: u64,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    // Pools only hold wattpeak_denom, any other coin next to it would not be credited
+    let amount = match info.funds.as_slice() {
+        [coin] if coin.denom == config.wattpeak_denom => coin.amount,
+        [] | [_] => return Err(ContractError::NoWattpeakStaked {}),
+        _ => return Err(ContractError::MultipleDenomsStaked {}),
+    };
+    if amount.is_zero() {
+        return Err(ContractError::ZeroStake {});
+    }
+
+    let mut pool = load_pool(deps.storage, project_id, env.block.time.seconds())?;
+    let mut stake = load_pool_stake(deps.storage, &pool, &info.sender)?;
+    stake.wattpeak_staked += amount;
+    pool.total_staked += amount;
+
+    // A pool backs the wattpeak minted for its project, it cannot hold more than that
+    let project = query_minter_project(deps.as_ref(), project_id)?;
+    if pool.total_staked > Uint128::from(project.minted_wattpeak_count) {
+        return Err(ContractError::PoolCapExceeded {
+            project_id,
+            minted: project.minted_wattpeak_count,
+        });
+    }
+    // Pool stakes skip save_staker on purpose: counted in TOTAL_WATTPEAK_STAKED they would take
+    // a share of the contract's interest on top of the pool's, so they get no voting power
+    // snapshot and send no hooks either
+    POOL_STAKES.save(deps.storage, (project_id, info.sender.clone()), &stake)?;
+    POOLS.save(deps.storage, project_id, &pool)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "stake_in_pool")
+        .add_attribute("project_id", project_id.to_string())
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("amount", amount.to_string()))
+}
+
+fn unstake_from_pool(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    project_id: u64,
+    amount: Uint128,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::ZeroUnstake {});
+    }
+    let config = CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    let mut pool = load_pool(deps.storage, project_id, now)?;
+    let mut stake = load_pool_stake(deps.storage, &pool, &info.sender)?;
+    if stake.wattpeak_staked < amount {
+        return Err(ContractError::InsufficientStake {});
+    }
+    // Apart from the stake in the contract, see stake_in_pool
+    stake.wattpeak_staked -= amount;
+    pool.total_staked -= amount;
+    POOLS.save(deps.storage, project_id, &pool)?;
+
+    // The stake is kept while it still has interest or rewards to claim
+    if stake.wattpeak_staked.is_zero()
+        && stake.interest_wattpeak.is_zero()
+        && stake.claimable_rewards < Decimal::one()
     {
-        STAKERS.remove(deps.storage, staker_address.clone());
+        POOL_STAKES.remove(deps.storage, (project_id, info.sender.clone()));
+        add_reward_dust(
+            deps.storage,
+            &config.wattpeak_denom,
+            stake.claimable_rewards,
+        )?;
+    } else {
+        POOL_STAKES.save(deps.storage, (project_id, info.sender.clone()), &stake)?;
+    }
+
+    let response = Response::new()
+        .add_attribute("action", "unstake_from_pool")
+        .add_attribute("project_id", project_id.to_string())
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("amount", amount.to_string());
+
+    release_unstaked(
+        deps.storage,
+        &config,
+        &info.sender,
+        &config.wattpeak_denom,
+        amount,
+        now,
+        response,
+    )
+}
+
+fn deposit_pool_rewards(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    project_id: u64,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    // Pool rewards are paid in wattpeak only
+    let amount = match info.funds.as_slice() {
+        [coin] if coin.denom == config.wattpeak_denom => coin.amount,
+        _ => return Err(ContractError::NoWattpeakDeposited {}),
+    };
+    if amount.is_zero() {
+        return Err(ContractError::ZeroDeposit {});
+    }
+
+    let mut pool = load_pool(deps.storage, project_id, env.block.time.seconds())?;
+    distribute_pool_rewards(deps.storage, &mut pool, amount)?;
+    POOLS.save(deps.storage, project_id, &pool)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "deposit_pool_rewards")
+        .add_attribute("project_id", project_id.to_string())
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("amount", amount.to_string()))
+}
+
+fn claim_pool_rewards(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    project_id: u64,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let pool = load_pool(deps.storage, project_id, env.block.time.seconds())?;
+    if !POOL_STAKES.has(deps.storage, (project_id, info.sender.clone())) {
+        return Err(ContractError::StakerNotFound {});
+    }
+    let mut stake = load_pool_stake(deps.storage, &pool, &info.sender)?;
+
+    // Only whole units are paid out, the fraction carries over to the next claim
+    let (rewards_amount, staking_fee_amount) =
+        take_whole_rewards(&mut stake.claimable_rewards, config.staking_fee_percentage)?;
+    if rewards_amount.is_zero() && staking_fee_amount.is_zero() {
+        return Err(ContractError::NoRewardsToClaim {});
+    }
+    record_staking_fees(
+        deps.storage,
+        &[Coin {
+            denom: config.wattpeak_denom.clone(),
+            amount: staking_fee_amount,
+        }],
+    )?;
+    POOL_STAKES.save(deps.storage, (project_id, info.sender.clone()), &stake)?;
+    POOLS.save(deps.storage, project_id, &pool)?;
+
+    let recipient = WITHDRAW_ADDRESSES
+        .may_load(deps.storage, info.sender.clone())?
+        .unwrap_or_else(|| info.sender.clone());
+    let mut messages = vec![];
+    if !rewards_amount.is_zero() {
+        messages.push(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin {
+                denom: config.wattpeak_denom.clone(),
+                amount: rewards_amount,
+            }],
+        });
+    }
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_messages(staking_fee_msg(&config, staking_fee_amount))
+        .add_attribute("action", "claim_pool_rewards")
+        .add_attribute("project_id", project_id.to_string())
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("recipient", recipient)
+        .add_attribute("amount", (rewards_amount + staking_fee_amount).to_string()))
+}
+
+fn set_pause(
+    deps: DepsMut,
+    info: MessageInfo,
+    staking: Option<bool>,
+    claiming: Option<bool>,
+    epochs: Option<bool>,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    if !CONFIG.load(deps.storage)?.can_pause(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut flags = PAUSE_FLAGS.may_load(deps.storage)?.unwrap_or_default();
+    if let Some(staking) = staking {
+        flags.staking = staking;
+    }
+    if let Some(claiming) = claiming {
+        flags.claiming = claiming;
+    }
+    if let Some(epochs) = epochs {
+        flags.epochs = epochs;
+    }
+    PAUSE_FLAGS.save(deps.storage, &flags)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_pause")
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("staking", flags.staking.to_string())
+        .add_attribute("claiming", flags.claiming.to_string())
+        .add_attribute("epochs", flags.epochs.to_string()))
+}
+
+fn enable_emergency_mode(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    if !CONFIG.load(deps.storage)?.can_pause(&info.sender) {
+        return Err(ContractError::Unauthorized {});
     }
 
-    // Construct the response
-    Ok(Response::new()
-        .add_message(payment_msg)
-        .add_message(staking_fee_msg)
-        .add_attribute("action", "claim_rewards")
-        .add_attribute("from", info.sender.to_string())
-        .add_attribute("amount", rewards.to_string()))
-}
+    // Interest accrued so far is kept, none accrues after now
+    let now = env.block.time.seconds();
+    accrue_interest(deps.storage, now)?;
+    EMERGENCY_TIME.save(deps.storage, &now)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "enable_emergency_mode")
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("time", now.to_string()))
+}
+
+fn emergency_withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    project_id: Option<u64>,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let staker_address = &info.sender;
+    let now = env.block.time.seconds();
+    let wattpeak_denom = CONFIG.load(deps.storage)?.wattpeak_denom;
+    let mut amount = Uint128::zero();
+    let mut coins = vec![];
+    let mut hooks = vec![];
+
+    match project_id {
+        Some(project_id) => {
+            let mut pool = load_pool(deps.storage, project_id, now)?;
+            let stake = POOL_STAKES
+                .may_load(deps.storage, (project_id, staker_address.clone()))?
+                .ok_or(ContractError::NoPoolStake { project_id })?;
+            amount = stake.wattpeak_staked;
+            add_coin(
+                &mut coins,
+                Coin {
+                    denom: wattpeak_denom,
+                    amount,
+                },
+            )?;
+            pool.total_staked = pool.total_staked.checked_sub(amount)?;
+            POOLS.save(deps.storage, project_id, &pool)?;
+            POOL_STAKES.remove(deps.storage, (project_id, staker_address.clone()));
+        }
+        None => {
+            // Every lock is released without a penalty
+            accrue_interest(deps.storage, now)?;
+            let lock_ids = LOCKS
+                .prefix(staker_address.clone())
+                .keys(deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?;
+            for id in lock_ids {
+                release_lock(deps.storage, staker_address, id, now)?;
+            }
+
+            if let Some(mut staker) = stakers().may_load(deps.storage, staker_address.clone())? {
+                settle_staker(deps.storage, &mut staker, now)?;
+                let staked = staker.wattpeak_staked;
+                // Bridged stake is paid back in the denom it was staked in
+                let mut canonical = staked;
+                for coin in bridged_stake(deps.storage, staker_address)? {
+                    remove_bridged_stake(deps.storage, staker_address, &coin.denom, coin.amount)?;
+                    canonical = canonical.checked_sub(coin.amount)?;
+                    add_coin(&mut coins, coin)?;
+                }
+                add_coin(
+                    &mut coins,
+                    Coin {
+                        denom: wattpeak_denom.clone(),
+                        amount: canonical,
+                    },
+                )?;
+                staker.wattpeak_staked = Uint128::zero();
+                hooks = save_staker(
+                    deps.storage,
+                    staker_address,
+                    &mut staker,
+                    now,
+                    env.block.height,
+                )?;
+                TOTAL_WATTPEAK_STAKED.update(
+                    deps.storage,
+                    env.block.height,
+                    |total| -> StdResult<_> { Ok(total.unwrap_or_default() - staked) },
+                )?;
+                remove_staker_if_empty(deps.storage, staker_address, &staker, env.block.height)?;
+                amount += staked;
+            }
+
+            // Wattpeak still unbonding is released right away
+            let unbondings = UNBONDINGS
+                .prefix(staker_address.clone())
+                .range(deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?;
+            for (id, entry) in unbondings {
+                amount += entry.amount;
+                add_coin(
+                    &mut coins,
+                    Coin {
+                        denom: entry.denom.unwrap_or_else(|| wattpeak_denom.clone()),
+                        amount: entry.amount,
+                    },
+                )?;
+                UNBONDINGS.remove(deps.storage, (staker_address.clone(), id));
+            }
+        }
+    }
+    if amount.is_zero() {
+        return Err(ContractError::NothingToWithdraw {});
+    }
+    coins.sort_by(|a, b| a.denom.cmp(&b.denom));
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: staker_address.to_string(),
+            amount: coins,
+        })
+        .add_submessages(hooks)
+        .add_attribute("action", "emergency_withdraw")
+        .add_attribute("from", info.sender.to_string())
+        .add_attribute("amount", amount.to_string()))
+}
+
+/// Pays a fee in wattpeak, such as the staking fee taken from restaked rewards, to the staking
+/// fee address
+fn staking_fee_msg(config: &Config, staking_fee: Uint128) -> Option<BankMsg> {
+    fee_msg(config, &config.wattpeak_denom, staking_fee)
+}
+
+/// Pays a fee in `denom`, such as the early unstake fee of bridged wattpeak, to the staking fee
+/// address
+fn fee_msg(config: &Config, denom: &str, fee: Uint128) -> Option<BankMsg> {
+    if fee.is_zero() {
+        return None;
+    }
+    Some(BankMsg::Send {
+        to_address: config.staking_fee_address.to_string(),
+        amount: vec![Coin {
+            denom: denom.to_string(),
+            amount: fee,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{instantiate, msg::InstantiateMsg};
+    use cosmwasm_std::{
+        testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage},
+        OwnedDeps,
+    };
+
+    /// Config the test modules start from, each module overrides the fields it tests
+    fn default_config() -> Config {
+        Config {
+            admin: Addr::unchecked("admin"),
+            rewards_percentage: Decimal::percent(10),
+            epoch_length: 86400,
+            wattpeak_denom: "watt".to_string(),
+            staking_fee_address: Addr::unchecked("staking_fee_address"),
+            staking_fee_percentage: Decimal::percent(5),
+            unbonding_period: 0,
+            reward_denoms: vec![],
+            lock_tiers: vec![],
+            early_unstake_fee: None,
+            loyalty_tiers: vec![],
+            minter_address: None,
+            cap_interest_to_reserve: false,
+            reward_expiry: None,
+            guardian: None,
+            bridged_denoms: vec![],
+        }
+    }
+
+    /// Instantiates the staker with `config`
+    fn instantiate_with(config: Config) -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env) {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let msg = InstantiateMsg { config };
+        instantiate(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+        (deps, env)
+    }
+
+    /// Starts the next epoch, returns the response of the epoch
+    fn new_epoch(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        env: &Env,
+    ) -> Response<TokenFactoryMsg> {
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::NewEpoch {},
+        )
+        .unwrap()
+    }
+
+    /// Returns the value of the attribute `key` of a response
+    fn attribute(res: &Response<TokenFactoryMsg>, key: &str) -> String {
+        res.attributes
+            .iter()
+            .find(|attr| attr.key == key)
+            .map(|attr| attr.value.clone())
+            .unwrap()
+    }
+
+    mod update_config {
+        use cosmwasm_std::{
+            from_json,
+            testing::{mock_dependencies, mock_env, mock_info},
+            to_json_vec, Addr, Binary, ContractResult, Decimal, SystemError, SystemResult,
+            WasmQuery,
+        };
+
+        use crate::error::ContractError;
+        use crate::execute::execute;
+        use crate::msg::ExecuteMsg;
+        use crate::{
+            instantiate,
+            msg::InstantiateMsg,
+            state::{Config, EarlyUnstakeFee, RewardExpiry},
+        };
+
+        use super::{default_config, CONFIG};
+
+        #[test]
+        fn proper_update_config() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+            let msg = ExecuteMsg::UpdateConfig {
+                rewards_percentage: Some(Decimal::percent(7)),
+                epoch_length: Some(3600),
+                unbonding_period: Some(600),
+                reward_denoms: Some(vec!["usdc".to_string()]),
+                lock_tiers: None,
+                early_unstake_fee: None,
+                loyalty_tiers: None,
+                minter_address: None,
+                cap_interest_to_reserve: Some(true),
+                reward_expiry: None,
+                guardian: None,
+                wattpeak_denom: None,
+                staking_fee_percentage: Some(Decimal::percent(2)),
+                staking_fee_address: Some("new_fee_address".to_string()),
+                bridged_denoms: None,
+            };
+            execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            // The fields that were set changed, the others kept their value
+            let updated_config = CONFIG.load(deps.as_ref().storage).unwrap();
+            assert_eq!(
+                updated_config,
+                Config {
+                    rewards_percentage: Decimal::percent(7),
+                    epoch_length: 3600,
+                    unbonding_period: 600,
+                    reward_denoms: vec!["usdc".to_string()],
+                    cap_interest_to_reserve: true,
+                    staking_fee_percentage: Decimal::percent(2),
+                    staking_fee_address: Addr::unchecked("new_fee_address"),
+                    ..default_config()
+                }
+            );
+        }
+        #[test]
+        fn unauthorized_update_config() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+            let msg = ExecuteMsg::UpdateConfig {
+                rewards_percentage: Some(Decimal::percent(5)),
+                epoch_length: Some(86400),
+                unbonding_period: None,
+                reward_denoms: None,
+                lock_tiers: None,
+                early_unstake_fee: None,
+                loyalty_tiers: None,
+                minter_address: None,
+                cap_interest_to_reserve: None,
+                reward_expiry: None,
+                guardian: None,
+                wattpeak_denom: None,
+                staking_fee_percentage: None,
+                staking_fee_address: None,
+                bridged_denoms: None,
+            };
+            let res = execute(deps.as_mut(), env.clone(), mock_info("random", &[]), msg);
+            assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+        }
+        #[test]
+        fn validate_config_epoch() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+            let msg = ExecuteMsg::UpdateConfig {
+                rewards_percentage: Some(Decimal::percent(5)),
+                epoch_length: Some(0),
+                unbonding_period: None,
+                reward_denoms: None,
+                lock_tiers: None,
+                early_unstake_fee: None,
+                loyalty_tiers: None,
+                minter_address: None,
+                cap_interest_to_reserve: None,
+                reward_expiry: None,
+                guardian: None,
+                wattpeak_denom: None,
+                staking_fee_percentage: None,
+                staking_fee_address: None,
+                bridged_denoms: None,
+            };
+            let res = execute(deps.as_mut(), env.clone(), info, msg);
+            assert_eq!(res.unwrap_err(), ContractError::EpochLengthZero {});
+        }
+        #[test]
+        fn validate_config_rewards_percentage() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+            let msg = ExecuteMsg::UpdateConfig {
+                rewards_percentage: Some(Decimal::percent(101)),
+                epoch_length: Some(86400),
+                unbonding_period: None,
+                reward_denoms: None,
+                lock_tiers: None,
+                early_unstake_fee: None,
+                loyalty_tiers: None,
+                minter_address: None,
+                cap_interest_to_reserve: None,
+                reward_expiry: None,
+                guardian: None,
+                wattpeak_denom: None,
+                staking_fee_percentage: None,
+                staking_fee_address: None,
+                bridged_denoms: None,
+            };
+            let res = execute(deps.as_mut(), env.clone(), info, msg);
+            assert_eq!(res.unwrap_err(), ContractError::RewardsPercentageTooHigh {});
+        }
+        #[test]
+        fn validate_config_reward_denoms() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+            let msg = ExecuteMsg::UpdateConfig {
+                rewards_percentage: None,
+                epoch_length: None,
+                unbonding_period: None,
+                reward_denoms: Some(vec!["usdc".to_string(), "usdc".to_string()]),
+                lock_tiers: None,
+                early_unstake_fee: None,
+                loyalty_tiers: None,
+                minter_address: None,
+                cap_interest_to_reserve: None,
+                reward_expiry: None,
+                guardian: None,
+                wattpeak_denom: None,
+                staking_fee_percentage: None,
+                staking_fee_address: None,
+                bridged_denoms: None,
+            };
+            let res = execute(deps.as_mut(), env.clone(), info.clone(), msg);
+            assert_eq!(
+                res.unwrap_err(),
+                ContractError::DuplicateRewardDenom {
+                    denom: "usdc".to_string()
+                }
+            );
+
+            let msg = ExecuteMsg::UpdateConfig {
+                rewards_percentage: None,
+                epoch_length: None,
+                unbonding_period: None,
+                reward_denoms: Some(vec!["watt".to_string()]),
+                lock_tiers: None,
+                early_unstake_fee: None,
+                loyalty_tiers: None,
+                minter_address: None,
+                cap_interest_to_reserve: None,
+                reward_expiry: None,
+                guardian: None,
+                wattpeak_denom: None,
+                staking_fee_percentage: None,
+                staking_fee_address: None,
+                bridged_denoms: None,
+            };
+            let res = execute(deps.as_mut(), env.clone(), info, msg);
+            assert_eq!(
+                res.unwrap_err(),
+                ContractError::InvalidRewardDenom {
+                    denom: "watt".to_string()
+                }
+            );
+        }
+        #[test]
+        fn optional_settings_are_set_and_cleared() {
+            let (mut deps, env) = super::instantiate_with(default_config());
+            deps.querier.update_wasm(|query| match query {
+                WasmQuery::Raw { contract_addr, key }
+                    if contract_addr == "minter" && key.as_slice() == b"token_full_denom" =>
+                {
+                    SystemResult::Ok(ContractResult::Ok(Binary::from(br#""uwatt""#.as_slice())))
+                }
+                _ => SystemResult::Err(SystemError::Unknown {}),
+            });
+            let update = |early_unstake_fee, minter_address, reward_expiry, guardian| {
+                ExecuteMsg::UpdateConfig {
+                    rewards_percentage: None,
+                    epoch_length: None,
+                    unbonding_period: None,
+                    reward_denoms: None,
+                    lock_tiers: None,
+                    early_unstake_fee,
+                    loyalty_tiers: None,
+                    minter_address,
+                    cap_interest_to_reserve: None,
+                    reward_expiry,
+                    guardian,
+                    wattpeak_denom: None,
+                    staking_fee_percentage: None,
+                    staking_fee_address: None,
+                    bridged_denoms: None,
+                }
+            };
+            let early_unstake_fee = EarlyUnstakeFee {
+                period: 86400,
+                percentage: Decimal::percent(10),
+            };
+            let reward_expiry = RewardExpiry {
+                epochs: 10,
+                treasury: Addr::unchecked("treasury"),
+            };
+
+            let msg = update(
+                Some(Some(early_unstake_fee.clone())),
+                Some(Some("minter".to_string())),
+                Some(Some(reward_expiry.clone())),
+                Some(Some("guardian".to_string())),
+            );
+            execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+            let config = CONFIG.load(deps.as_ref().storage).unwrap();
+            assert_eq!(config.early_unstake_fee, Some(early_unstake_fee));
+            assert_eq!(config.minter_address, Some(Addr::unchecked("minter")));
+            assert_eq!(config.wattpeak_denom, "uwatt");
+            assert_eq!(config.reward_expiry, Some(reward_expiry));
+            assert_eq!(config.guardian, Some(Addr::unchecked("guardian")));
+
+            // Fields left out keep their value
+            let msg = update(None, None, None, None);
+            execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+            assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap(), config);
+
+            // Null clears them, unlinking the minter keeps its denom
+            let msg = update(Some(None), Some(None), Some(None), Some(None));
+            execute(deps.as_mut(), env, mock_info("admin", &[]), msg).unwrap();
+            assert_eq!(
+                CONFIG.load(deps.as_ref().storage).unwrap(),
+                Config {
+                    wattpeak_denom: "uwatt".to_string(),
+                    ..default_config()
+                }
+            );
+        }
+        #[test]
+        fn optional_settings_are_cleared_with_null() {
+            let msg: ExecuteMsg = from_json(br#"{"update_config":{"guardian":null}}"#).unwrap();
+            let ExecuteMsg::UpdateConfig {
+                guardian,
+                early_unstake_fee,
+                ..
+            } = msg
+            else {
+                panic!("not an update_config message");
+            };
+            assert_eq!(guardian, Some(None));
+            assert_eq!(early_unstake_fee, None);
+
+            // Settings left unchanged are left out, not sent as null
+            let msg = ExecuteMsg::UpdateConfig {
+                rewards_percentage: None,
+                epoch_length: None,
+                unbonding_period: None,
+                reward_denoms: None,
+                lock_tiers: None,
+                early_unstake_fee: None,
+                loyalty_tiers: None,
+                minter_address: None,
+                cap_interest_to_reserve: None,
+                reward_expiry: None,
+                guardian: Some(None),
+                wattpeak_denom: None,
+                staking_fee_percentage: None,
+                staking_fee_address: None,
+                bridged_denoms: None,
+            };
+            let json = String::from_utf8(to_json_vec(&msg).unwrap()).unwrap();
+            assert!(json.contains(r#""guardian":null"#));
+            assert!(!json.contains("early_unstake_fee"));
+            assert_eq!(from_json::<ExecuteMsg>(json.as_bytes()).unwrap(), msg);
+        }
+    }
+    mod staking_tests {
+        use super::*;
+        use crate::{instantiate, msg::InstantiateMsg, state::Config};
+        use cosmwasm_std::{
+            testing::{mock_dependencies, mock_env, mock_info},
+            Coin, Decimal, Uint128,
+        };
+
+        #[test]
+        fn stake_wattpeak() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("creator", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            let staker_info = mock_info("staker", &[Coin::new(100u128, "watt")]);
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            assert_eq!(res.messages.len(), 0);
+
+            let staker = stakers()
+                .load(deps.as_ref().storage, staker_info.sender)
+                .unwrap();
+            assert_eq!(staker.wattpeak_staked, Uint128::from(100u128));
+
+            let total_wattpeak: Uint128 =
+                TOTAL_WATTPEAK_STAKED.load(deps.as_ref().storage).unwrap();
+            assert_eq!(total_wattpeak, Uint128::from(100u128));
+        }
+
+        #[test]
+        fn stake_wattpeak_existing_staker() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: default_config(),
+            };
+
+            let info = mock_info("creator", &[Coin::new(100u128, "watt")]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            let staker_info = mock_info("staker", &[Coin::new(100u128, "watt")]);
+
+            let _res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            let staker_info2 = mock_info("staker", &[Coin::new(100u128, "watt")]);
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info2,
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            assert_eq!(res.messages.len(), 0);
+
+            let staker = stakers()
+                .load(deps.as_ref().storage, staker_info.sender)
+                .unwrap();
+            assert_eq!(staker.wattpeak_staked, Uint128::from(200u128));
+
+            let total_wattpeak: Uint128 =
+                TOTAL_WATTPEAK_STAKED.load(deps.as_ref().storage).unwrap();
+            assert_eq!(total_wattpeak, Uint128::from(200u128));
+        }
+        #[test]
+        fn stake_zero_amount() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: default_config(),
+            };
+
+            let info = mock_info("creator", &[Coin::new(100u128, "watt")]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            let staker_info = mock_info("staker", &[Coin::new(0u128, "watt")]);
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Stake {},
+            );
+
+            assert_eq!(res.err().unwrap(), ContractError::ZeroStake {});
+        }
+        #[test]
+        fn stake_incorrect_denom() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: default_config(),
+            };
+
+            let info = mock_info("creator", &[Coin::new(100u128, "watt")]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            let staker_info = mock_info("staker", &[Coin::new(100u128, "random")]);
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Stake {},
+            );
+
+            assert_eq!(res.err().unwrap(), ContractError::NoWattpeakStaked {});
+        }
+    }
+
+    mod unstake_test {
+        use super::*;
+        use crate::{instantiate, msg::InstantiateMsg, state::Config};
+        use cosmwasm_std::{
+            testing::{mock_dependencies, mock_env, mock_info},
+            BankMsg, Coin, CosmosMsg, Decimal, Uint128,
+        };
+
+        #[test]
+        fn unstake_wattpeak() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("creator", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            let staker_info = mock_info("staker", &[Coin::new(100u128, "watt")]);
+
+            let _res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            let amount = Uint128::from(50u128);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Unstake {
+                    amount,
+                    denom: None,
+                },
+            )
+            .unwrap();
+
+            assert_eq!(res.messages.len(), 1);
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: staker_info.sender.to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount,
+                    }],
+                })
+            );
+
+            let staker = stakers()
+                .load(deps.as_ref().storage, staker_info.sender)
+                .unwrap();
+            assert_eq!(staker.wattpeak_staked, Uint128::from(50u128));
+
+            let total_wattpeak: Uint128 =
+                TOTAL_WATTPEAK_STAKED.load(deps.as_ref().storage).unwrap();
+            assert_eq!(total_wattpeak, Uint128::from(50u128));
+        }
+        #[test]
+        fn unstake_insufficient_stake() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("creator", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            let staker_info = mock_info("staker", &[Coin::new(100u128, "watt")]);
+
+            let _res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::from(200u128),
+                    denom: None,
+                },
+            );
+
+            assert_eq!(res.err().unwrap(), ContractError::InsufficientStake {});
+        }
+        #[test]
+        fn unstake_staker_doesnt_exist() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("creator", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            let staker_info = mock_info("staker", &[]);
+            let amount = Uint128::from(100u128);
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Unstake {
+                    amount,
+                    denom: None,
+                },
+            );
+
+            assert_eq!(res.err().unwrap(), ContractError::StakerNotFound {});
+        }
+        #[test]
+        fn unstake_amount_is_zero() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("creator", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            let staker_info = mock_info("staker", &[Coin::new(0u128, "watt")]);
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::zero(),
+                    denom: None,
+                },
+            );
+
+            assert_eq!(res.err().unwrap(), ContractError::ZeroUnstake {});
+        }
+        #[test]
+        fn remove_staker() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("creator", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            let staker_info = mock_info("staker", &[Coin::new(100u128, "watt")]);
+
+            let _res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            let amount = Uint128::from(100u128);
+            let _res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Unstake {
+                    amount,
+                    denom: None,
+                },
+            )
+            .unwrap();
+
+            let staker = stakers()
+                .may_load(deps.as_ref().storage, staker_info.sender.clone())
+                .unwrap();
+            assert_eq!(staker, None);
+        }
+        #[test]
+        fn unstake_with_unbonding_period() {
+            let mut deps = mock_dependencies();
+            let mut env = mock_env();
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    unbonding_period: 1000,
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("creator", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            let staker_info = mock_info("staker", &[Coin::new(100u128, "watt")]);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            // Unstaking queues the tokens instead of sending them
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::from(60u128),
+                    denom: None,
+                },
+            )
+            .unwrap();
+            assert_eq!(res.messages.len(), 0);
+            let total_wattpeak: Uint128 =
+                TOTAL_WATTPEAK_STAKED.load(deps.as_ref().storage).unwrap();
+            assert_eq!(total_wattpeak, Uint128::from(40u128));
+
+            env.block.time = env.block.time.plus_seconds(500);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::from(40u128),
+                    denom: None,
+                },
+            )
+            .unwrap();
+
+            // Nothing has been released yet
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::WithdrawUnbonded {},
+            );
+            assert_eq!(res.err().unwrap(), ContractError::NoUnbondedWattpeak {});
+
+            // Only the first entry has been released
+            env.block.time = env.block.time.plus_seconds(500);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::WithdrawUnbonded {},
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: staker_info.sender.to_string(),
+                    amount: vec![Coin::new(60u128, "watt")],
+                })
+            );
+
+            env.block.time = env.block.time.plus_seconds(500);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info.clone(),
+                ExecuteMsg::WithdrawUnbonded {},
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: staker_info.sender.to_string(),
+                    amount: vec![Coin::new(40u128, "watt")],
+                })
+            );
+            assert!(UNBONDINGS.is_empty(deps.as_ref().storage));
+        }
+    }
+    mod deposit_rewards_tests {
+        use super::*;
+        use crate::{instantiate, msg::InstantiateMsg, state::Config};
+        use cosmwasm_std::{
+            testing::{mock_dependencies, mock_env, mock_info},
+            Coin, Decimal, Uint128,
+        };
+
+        #[test]
+        fn deposit_rewards() {}
+        #[test]
+        fn deposit_rewards_unauthorized() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("creator", &[Coin::new(100u128, "watt")]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+            let deposit_amount = Uint128::from(574u128);
+            let funds = Coin {
+                denom: "watt".to_string(),
+                amount: deposit_amount,
+            };
+            let info = mock_info("random", &[funds]);
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                ExecuteMsg::DepositRewards {},
+            );
+
+            assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
+        }
+
+        #[test]
+        fn deposit_rewards_zero_amount() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[Coin::new(0u128, "watt")]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                ExecuteMsg::DepositRewards {},
+            );
+
+            assert_eq!(res.err().unwrap(), ContractError::ZeroDeposit {});
+        }
+
+        #[test]
+        fn deposit_rewards_not_whitelisted() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    reward_denoms: vec!["usdc".to_string()],
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            let info = mock_info(
+                "admin",
+                &[Coin::new(100u128, "usdc"), Coin::new(100u128, "atom")],
+            );
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                ExecuteMsg::DepositRewards {},
+            );
+
+            assert_eq!(
+                res.err().unwrap(),
+                ContractError::DenomNotWhitelisted {
+                    denom: "atom".to_string()
+                }
+            );
+        }
+    }
+
+    mod claim_rewards_test {
+        use super::*;
+        use crate::{
+            helpers::{calculate_interest_after_epoch, settle_staker},
+            instantiate,
+            msg::InstantiateMsg,
+            state::{Config, REWARD_DUST},
+        };
+        use cosmwasm_std::{
+            testing::{mock_dependencies, mock_env, mock_info},
+            Addr, BankMsg, Coin, CosmosMsg, Decimal, Uint128,
+        };
+
+        #[test]
+        fn claim_rewards() {
+            let mut deps = mock_dependencies();
+            let mut env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+            let staker_info1 = mock_info("addr1", &[Coin::new(100u128, "watt")]);
+            let staker_info2 = mock_info("addr2", &[Coin::new(200u128, "watt")]);
+            let staker_info3 = mock_info("addr3", &[Coin::new(300u128, "watt")]);
+            let deposit_amount = Uint128::from(574u128);
+
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info1.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info2.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info3.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            env.block.time = env.block.time.plus_seconds(86400);
+
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+
+            let funds = Coin {
+                denom: "watt".to_string(),
+                amount: deposit_amount,
+            };
+
+            let info = mock_info("admin", &[funds]);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+
+            assert_eq!(res.messages.len(), 0);
+
+            let claimer = mock_info("addr1", &[]);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                claimer.clone(),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            )
+            .unwrap();
+            println!("{:?}", res);
+            assert_eq!(res.messages.len(), 2);
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: staker_info1.sender.to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount: Uint128::from(91u128),
+                    }],
+                })
+            );
+            assert_eq!(
+                res.messages[1].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "staking_fee_address".to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount: Uint128::from(4u128),
+                    }],
+                })
+            );
+
+            let staker = stakers()
+                .load(deps.as_ref().storage, staker_info1.sender)
+                .unwrap();
+            // The fraction that could not be paid out carries over to the next claim
+            assert_eq!(staker.claimable_rewards, Decimal::from_ratio(2u128, 3u128));
+        }
+        #[test]
+        fn claim_rewards_multiple_epochs() {
+            let mut deps = mock_dependencies();
+            let mut env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+            let staker_info1 = mock_info("addr1", &[Coin::new(100u128, "watt")]);
+            let staker_info2 = mock_info("addr2", &[Coin::new(200u128, "watt")]);
+            let staker_info3 = mock_info("addr3", &[Coin::new(300u128, "watt")]);
+            let deposit_amount = Uint128::from(574u128);
+
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info1.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info2.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info3.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            env.block.time = env.block.time.plus_seconds(86400);
+
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            env.block.time = env.block.time.plus_seconds(86400);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            env.block.time = env.block.time.plus_seconds(86400);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            env.block.time = env.block.time.plus_seconds(86400);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            env.block.time = env.block.time.plus_seconds(86400);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+
+            let funds = Coin {
+                denom: "watt".to_string(),
+                amount: deposit_amount,
+            };
+
+            let info = mock_info("admin", &[funds]);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+
+            assert_eq!(res.messages.len(), 0);
+
+            let claimer = mock_info("addr1", &[]);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                claimer.clone(),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            )
+            .unwrap();
+
+            let claimer2 = mock_info("addr2", &[]);
+            let res2 = execute(
+                deps.as_mut(),
+                env.clone(),
+                claimer2.clone(),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            )
+            .unwrap();
+
+            let claimer3 = mock_info("addr3", &[]);
+            let res3 = execute(
+                deps.as_mut(),
+                env.clone(),
+                claimer3.clone(),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            )
+            .unwrap();
+
+            assert_eq!(res.messages.len(), 2);
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: staker_info1.sender.to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount: Uint128::from(91u128),
+                    }],
+                })
+            );
+            assert_eq!(
+                res.messages[1].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "staking_fee_address".to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount: Uint128::from(4u128),
+                    }],
+                })
+            );
+
+            assert_eq!(res2.messages.len(), 2);
+            assert_eq!(
+                res2.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: staker_info2.sender.to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount: Uint128::from(182u128),
+                    }],
+                })
+            );
+            assert_eq!(
+                res2.messages[1].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "staking_fee_address".to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount: Uint128::from(9u128),
+                    }],
+                })
+            );
+
+            assert_eq!(res3.messages.len(), 2);
+            assert_eq!(
+                res3.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: staker_info3.sender.to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount: Uint128::from(272u128),
+                    }],
+                })
+            );
+
+            assert_eq!(
+                res3.messages[1].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "staking_fee_address".to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount: Uint128::from(14u128),
+                    }],
+                })
+            );
+
+            let staker = stakers()
+                .load(deps.as_ref().storage, staker_info1.sender)
+                .unwrap();
+            // The fraction that could not be paid out carries over to the next claim
+            assert_eq!(staker.claimable_rewards, Decimal::from_ratio(2u128, 3u128));
+        }
+
+        #[test]
+        fn claim_when_staker_doesnt_exist() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("creator", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            let claimer = mock_info("addr1", &[]);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                claimer.clone(),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            );
+
+            assert_eq!(res.err().unwrap(), ContractError::StakerNotFound {});
+        }
+        #[test]
+        fn claim_rewards_multiple_denoms() {
+            let mut deps = mock_dependencies();
+            let mut env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    reward_denoms: vec!["usdc".to_string(), "atom".to_string()],
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+            let staker_info1 = mock_info("addr1", &[Coin::new(1000000u128, "watt")]);
+            let staker_info2 = mock_info("addr2", &[Coin::new(3000000u128, "watt")]);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info1.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info2.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            // The first round only pays out usdc
+            env.block.time = env.block.time.plus_seconds(86400);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            let info = mock_info("admin", &[Coin::new(4000000u128, "usdc")]);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                info,
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+
+            // The second round pays out wattpeak and atom
+            env.block.time = env.block.time.plus_seconds(86400);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            let info = mock_info(
+                "admin",
+                &[Coin::new(400000u128, "watt"), Coin::new(800000u128, "atom")],
+            );
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                info,
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            )
+            .unwrap();
+
+            assert_eq!(res.messages.len(), 2);
+            let CosmosMsg::Bank(BankMsg::Send { to_address, amount }) = &res.messages[0].msg else {
+                panic!("expected a bank send");
+            };
+            assert_eq!(to_address, "addr1");
+            let denoms: Vec<&str> = amount.iter().map(|coin| coin.denom.as_str()).collect();
+            assert_eq!(denoms, vec!["watt", "usdc", "atom"]);
+
+            // addr1 holds a quarter of the stake, minus the 5% staking fee
+            let expected = [95000u128, 950000u128, 190000u128];
+            for (coin, expected) in amount.iter().zip(expected) {
+                assert!(coin.amount <= Uint128::from(expected));
+                assert!(coin.amount + Uint128::one() >= Uint128::from(expected));
+            }
+
+            let CosmosMsg::Bank(BankMsg::Send { to_address, amount }) = &res.messages[1].msg else {
+                panic!("expected a bank send");
+            };
+            assert_eq!(to_address, "staking_fee_address");
+            assert_eq!(amount.len(), 3);
+
+            let staker = stakers()
+                .load(deps.as_ref().storage, staker_info1.sender)
+                .unwrap();
+            // Only fractions are left over for the next claim
+            assert!(staker.claimable_rewards < Decimal::one());
+            assert!(staker
+                .claimable_denom_rewards
+                .iter()
+                .all(|reward| reward.amount < Decimal::one()));
+        }
+        #[test]
+        fn try_to_claim_twice_in_a_row() {
+            let mut deps = mock_dependencies();
+            let mut env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+            let staker_info1 = mock_info("addr1", &[Coin::new(100u128, "watt")]);
+            let staker_info2 = mock_info("addr2", &[Coin::new(200u128, "watt")]);
+            let staker_info3 = mock_info("addr3", &[Coin::new(300u128, "watt")]);
+            let deposit_amount = Uint128::from(574u128);
+
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info1.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info2.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info3.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            env.block.time = env.block.time.plus_seconds(86400);
+
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+
+            let funds = Coin {
+                denom: "watt".to_string(),
+                amount: deposit_amount,
+            };
+
+            let info = mock_info("admin", &[funds]);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+
+            assert_eq!(res.messages.len(), 0);
+
+            let claimer = mock_info("addr1", &[]);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                claimer.clone(),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            )
+            .unwrap();
+
+            assert_eq!(res.messages.len(), 2);
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: staker_info1.sender.to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount: Uint128::from(91u128),
+                    }],
+                })
+            );
+
+            assert_eq!(
+                res.messages[1].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "staking_fee_address".to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount: Uint128::from(4u128),
+                    }],
+                })
+            );
+
+            let staker = stakers()
+                .load(deps.as_ref().storage, staker_info1.sender)
+                .unwrap();
+            // The fraction that could not be paid out carries over to the next claim
+            assert_eq!(staker.claimable_rewards, Decimal::from_ratio(2u128, 3u128));
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                claimer.clone(),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            );
+            assert_eq!(res.err().unwrap(), ContractError::NoRewardsToClaim {});
+        }
+        #[test]
+        fn no_rewards_created_or_lost_over_many_rounds() {
+            let mut deps = mock_dependencies();
+            let mut env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    staking_fee_percentage: Decimal::percent(7),
+                    reward_denoms: vec!["usdc".to_string()],
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            for (address, amount) in [
+                ("addr1", 1000003u128),
+                ("addr2", 2333337u128),
+                ("addr3", 777781u128),
+            ] {
+                execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info(address, &[Coin::new(amount, "watt")]),
+                    ExecuteMsg::Stake {},
+                )
+                .unwrap();
+            }
+
+            let mut deposited = [Uint128::zero(), Uint128::zero()];
+            let mut paid = [Uint128::zero(), Uint128::zero()];
+            let denoms = ["watt", "usdc"];
+
+            for round in 0..25u128 {
+                env.block.time = env
+                    .block
+                    .time
+                    .plus_seconds(86400 + (round as u64 * 3571) % 5000);
+
+                let deposit = [
+                    Uint128::from(100003u128 + round * 7919),
+                    Uint128::from(33331u128 + round * 1013),
+                ];
+                execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info(
+                        "admin",
+                        &[
+                            Coin::new(deposit[0].u128(), "watt"),
+                            Coin::new(deposit[1].u128(), "usdc"),
+                        ],
+                    ),
+                    ExecuteMsg::DepositRewards {},
+                )
+                .unwrap();
+                deposited[0] += deposit[0];
+                deposited[1] += deposit[1];
+
+                // addr3 leaves halfway through, the others claim at different intervals
+                if round == 10 {
+                    execute(
+                        deps.as_mut(),
+                        env.clone(),
+                        mock_info("addr3", &[]),
+                        ExecuteMsg::Unstake {
+                            amount: Uint128::from(777781u128),
+                            denom: None,
+                        },
+                    )
+                    .unwrap();
+                }
+                let mut claimers = vec!["addr1"];
+                if round % 3 == 0 {
+                    claimers.push("addr2");
+                }
+                if round == 12 {
+                    claimers.push("addr3");
+                }
+
+                for claimer in claimers {
+                    let res = execute(
+                        deps.as_mut(),
+                        env.clone(),
+                        mock_info(claimer, &[]),
+                        ExecuteMsg::ClaimReward {
+                            recipient: None,
+                            ibc: None,
+                        },
+                    )
+                    .unwrap();
+                    for msg in res.messages {
+                        let CosmosMsg::Bank(BankMsg::Send { amount, .. }) = msg.msg else {
+                            panic!("expected a bank send");
+                        };
+                        for coin in amount {
+                            let i = denoms.iter().position(|d| *d == coin.denom).unwrap();
+                            paid[i] += coin.amount;
+                        }
+                    }
+                }
+            }
+
+            // addr3 closed its position, the fractions it left behind became dust
+            assert!(stakers()
+                .may_load(deps.as_ref().storage, Addr::unchecked("addr3"))
+                .unwrap()
+                .is_none());
+
+            let mut outstanding = [Decimal::zero(), Decimal::zero()];
+            for address in ["addr1", "addr2"] {
+                let mut staker = stakers()
+                    .load(deps.as_ref().storage, Addr::unchecked(address))
+                    .unwrap();
+                settle_staker(deps.as_ref().storage, &mut staker, env.block.time.seconds())
+                    .unwrap();
+                outstanding[0] += staker.claimable_rewards;
+                for reward in staker.claimable_denom_rewards {
+                    assert_eq!(reward.denom, "usdc");
+                    outstanding[1] += reward.amount;
+                }
+            }
+
+            for (i, denom) in denoms.iter().enumerate() {
+                let dust = REWARD_DUST
+                    .may_load(deps.as_ref().storage, denom)
+                    .unwrap()
+                    .unwrap_or_default();
+                assert!(!dust.is_zero());
+
+                // Everything deposited is paid out, still claimable or accounted for as dust,
+                // up to the rounding of the reward indices
+                let accounted = Decimal::from_atomics(paid[i], 0).unwrap() + outstanding[i] + dust;
+                let deposited = Decimal::from_atomics(deposited[i], 0).unwrap();
+                assert!(accounted <= deposited);
+                assert!(deposited - accounted < Decimal::from_ratio(1u128, 1000000000u128));
+            }
+        }
+        #[test]
+        fn one_staker_three_epochs_deposit_and_claim() {
+            let mut deps = mock_dependencies();
+            let mut env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    epoch_length: 1,
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+            let staker_info1 = mock_info("addr1", &[Coin::new(100u128, "watt")]);
+            let deposit_amount = Uint128::from(5000000u128);
+
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info1.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            env.block.time = env.block.time.plus_seconds(1);
+
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            env.block.time = env.block.time.plus_seconds(1);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            env.block.time = env.block.time.plus_seconds(1);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            env.block.time = env.block.time.plus_seconds(1);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            env.block.time = env.block.time.plus_seconds(1);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+
+            let funds = Coin {
+                denom: "watt".to_string(),
+                amount: deposit_amount,
+            };
+
+            let info = mock_info("admin", &[funds]);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+
+            assert_eq!(res.messages.len(), 0);
+
+            let claimer = mock_info("addr1", &[]);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                claimer.clone(),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            )
+            .unwrap();
+
+            assert_eq!(res.messages.len(), 2);
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: staker_info1.sender.to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount: Uint128::from(4750000u128),
+                    }],
+                })
+            );
+            assert_eq!(
+                res.messages[1].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "staking_fee_address".to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount: Uint128::from(249999u128),
+                    }],
+                })
+            );
+        }
+    }
+
+    mod restake_test {
+        use super::*;
+        use crate::{
+            helpers::calculate_interest_after_epoch, instantiate, msg::InstantiateMsg,
+            state::Config,
+        };
+        use cosmwasm_std::{
+            testing::{mock_dependencies, mock_env, mock_info},
+            BankMsg, Coin, CosmosMsg, Decimal, Uint128,
+        };
+
+        #[test]
+        fn restake_rewards() {
+            let mut deps = mock_dependencies();
+            let mut env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+            let staker_info1 = mock_info("addr1", &[Coin::new(1000000u128, "watt")]);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info1.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            let restaker = mock_info("addr1", &[]);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                restaker.clone(),
+                ExecuteMsg::Restake {},
+            );
+            assert_eq!(res.unwrap_err(), ContractError::NoRewardsToRestake {});
+
+            env.block.time = env.block.time.plus_seconds(86400);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+
+            let info = mock_info("admin", &[Coin::new(100000u128, "watt")]);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                info,
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                restaker.clone(),
+                ExecuteMsg::Restake {},
+            )
+            .unwrap();
+            // The whole wattpeak of the rewards is restaked minus the staking fee
+            assert_eq!(res.messages.len(), 1);
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "staking_fee_address".to_string(),
+                    amount: vec![Coin {
+                        denom: "watt".to_string(),
+                        amount: Uint128::from(4999u128),
+                    }],
+                })
+            );
+            assert_eq!(res.attributes[2].value, "95000");
+
+            let staker = stakers()
+                .load(deps.as_ref().storage, restaker.sender)
+                .unwrap();
+            assert_eq!(staker.wattpeak_staked, Uint128::from(1095000u128));
+            assert!(staker.claimable_rewards < Decimal::one());
+            assert_eq!(
+                TOTAL_WATTPEAK_STAKED.load(deps.as_ref().storage).unwrap(),
+                Uint128::from(1095000u128)
+            );
+        }
+
+        #[test]
+        fn auto_compound_restakes_on_stake() {
+            let mut deps = mock_dependencies();
+            let mut env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+            let staker_info1 = mock_info("addr1", &[Coin::new(1000000u128, "watt")]);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info1.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::AutoCompound { enabled: true },
+            )
+            .unwrap();
+
+            env.block.time = env.block.time.plus_seconds(86400);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+
+            let info = mock_info("admin", &[Coin::new(100000u128, "watt")]);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                info,
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+
+            // Staking again restakes the rewards along with the new stake
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info1.clone(),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            assert_eq!(res.messages.len(), 1);
+
+            let staker = stakers()
+                .load(deps.as_ref().storage, staker_info1.sender.clone())
+                .unwrap();
+            assert!(staker.auto_compound);
+            assert_eq!(staker.wattpeak_staked, Uint128::from(2095000u128));
+            assert_eq!(
+                TOTAL_WATTPEAK_STAKED.load(deps.as_ref().storage).unwrap(),
+                Uint128::from(2095000u128)
+            );
+
+            // Disabling auto compounding leaves the rewards claimable
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::AutoCompound { enabled: false },
+            )
+            .unwrap();
+            let staker = stakers()
+                .load(deps.as_ref().storage, staker_info1.sender)
+                .unwrap();
+            assert!(!staker.auto_compound);
+            assert_eq!(staker.wattpeak_staked, Uint128::from(2095000u128));
+        }
+    }
+
+    mod hooks_test {
+        use super::*;
+        use crate::{
+            helpers::STAKE_CHANGED_HOOK_REPLY_ID,
+            instantiate,
+            msg::{InstantiateMsg, StakeChangedHookMsg},
+            reply,
+            state::Config,
+        };
+        use cosmwasm_std::{
+            testing::{mock_dependencies, mock_env, mock_info},
+            to_json_binary, Addr, Coin, CosmosMsg, Decimal, Reply, ReplyOn, SubMsgResult, Uint128,
+            WasmMsg,
+        };
+
+        #[test]
+        fn stake_changes_notify_hooks() {
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+            let info = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("random", &[]),
+                ExecuteMsg::AddHook {
+                    address: "governance".to_string(),
+                    fail_on_error: true,
+                },
+            );
+            assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+            for (address, fail_on_error) in [("governance", true), ("boost", false)] {
+                execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    info.clone(),
+                    ExecuteMsg::AddHook {
+                        address: address.to_string(),
+                        fail_on_error,
+                    },
+                )
+                .unwrap();
+            }
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                ExecuteMsg::AddHook {
+                    address: "boost".to_string(),
+                    fail_on_error: true,
+                },
+            );
+            assert_eq!(
+                res.unwrap_err(),
+                ContractError::HookExists {
+                    address: "boost".to_string()
+                }
+            );
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(100u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            let hook_msg = to_json_binary(&StakeChangedHookMsg::StakeChangedHook {
+                address: Addr::unchecked("addr1"),
+                old_amount: Uint128::zero(),
+                new_amount: Uint128::new(100),
+            })
+            .unwrap();
+            // Hooks are notified in address order, only the failing hook can revert the stake
+            assert_eq!(res.messages.len(), 2);
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: "boost".to_string(),
+                    msg: hook_msg.clone(),
+                    funds: vec![],
+                })
+            );
+            assert_eq!(res.messages[0].reply_on, ReplyOn::Error);
+            assert_eq!(res.messages[0].id, STAKE_CHANGED_HOOK_REPLY_ID);
+            assert_eq!(
+                res.messages[1].msg,
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: "governance".to_string(),
+                    msg: hook_msg,
+                    funds: vec![],
+                })
+            );
+            assert_eq!(res.messages[1].reply_on, ReplyOn::Never);
+
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                ExecuteMsg::RemoveHook {
+                    address: "governance".to_string(),
+                },
+            )
+            .unwrap();
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::new(40),
+                    denom: None,
+                },
+            )
+            .unwrap();
+            assert_eq!(res.messages.len(), 2);
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: "boost".to_string(),
+                    msg: to_json_binary(&StakeChangedHookMsg::StakeChangedHook {
+                        address: Addr::unchecked("addr1"),
+                        old_amount: Uint128::new(100),
+                        new_amount: Uint128::new(60),
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                })
+            );
+
+            // The error of a hook that does not fail on error is caught
+            let res = reply(
+                deps.as_mut(),
+                env,
+                Reply {
+                    id: STAKE_CHANGED_HOOK_REPLY_ID,
+                    result: SubMsgResult::Err("out of gas".to_string()),
+                },
+            )
+            .unwrap();
+            assert_eq!(res.attributes[1].value, "out of gas");
+        }
+    }
+
+    mod liquid_staking_test {
+        use super::*;
+        use crate::{
+            helpers::calculate_interest_after_epoch,
+            instantiate,
+            msg::{
+                InstantiateMsg, LiquidInfoResponse, QueryMsg, TotalPowerAtHeightResponse,
+                VotingPowerAtHeightResponse,
+            },
+            query::query,
+            state::Config,
+        };
+        use cosmwasm_std::{
+            from_json,
+            testing::{mock_dependencies, mock_env, mock_info},
+            Addr, BankMsg, Coin, CosmosMsg, Decimal, Uint128,
+        };
+
+        #[test]
+        fn liquid_stake_and_unstake() {
+            let mut deps = mock_dependencies();
+            let mut env = mock_env();
+
+            let msg = InstantiateMsg {
+                config: Config {
+                    rewards_percentage: Decimal::percent(5),
+                    ..default_config()
+                },
+            };
+            let admin = mock_info("admin", &[]);
+            let _res = instantiate(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+
+            let staker_info1 = mock_info("addr1", &[Coin::new(1000u128, "watt")]);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info1.clone(),
+                ExecuteMsg::LiquidStake {},
+            );
+            assert_eq!(res.unwrap_err(), ContractError::LiquidStakingNotEnabled {});
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("random", &[]),
+                ExecuteMsg::CreateLiquidDenom {},
+            );
+            assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                admin.clone(),
+                ExecuteMsg::CreateLiquidDenom {},
+            )
+            .unwrap();
+            let liquid_denom = "factory/cosmos2contract/ustwattpeak".to_string();
+            assert_eq!(res.messages.len(), 1);
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Custom(create_liquid_denom_msg(liquid_denom.clone()))
+            );
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                admin.clone(),
+                ExecuteMsg::CreateLiquidDenom {},
+            );
+            assert_eq!(res.unwrap_err(), ContractError::LiquidDenomExists {});
+
+            // The first stWP is minted one to one
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                staker_info1.clone(),
+                ExecuteMsg::LiquidStake {},
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Custom(TokenFactoryMsg::MintTokens {
+                    denom: liquid_denom.clone(),
+                    amount: Uint128::new(1000),
+                    mint_to_address: "addr1".to_string(),
+                })
+            );
+
+            // The pool is the only staker and earns the whole deposit
+            env.block.time = env.block.time.plus_seconds(86400);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+
+            // The rewards are compounded minus the staking fee before stWP is minted
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr2", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::LiquidStake {},
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "staking_fee_address".to_string(),
+                    amount: vec![Coin::new(49u128, "watt")],
+                })
+            );
+            assert_eq!(
+                res.messages[1].msg,
+                CosmosMsg::Custom(TokenFactoryMsg::MintTokens {
+                    denom: liquid_denom.clone(),
+                    amount: Uint128::new(512),
+                    mint_to_address: "addr2".to_string(),
+                })
+            );
+
+            let res: LiquidInfoResponse =
+                from_json(query(deps.as_ref(), env.clone(), QueryMsg::LiquidInfo {}).unwrap())
+                    .unwrap();
+            assert_eq!(res.denom, Some(liquid_denom.clone()));
+            assert_eq!(res.supply, Uint128::new(1512));
+            assert_eq!(res.wattpeak_staked, Uint128::new(2950));
+            assert_eq!(res.exchange_rate, Decimal::from_ratio(2950u128, 1512u128));
+
+            // The first stWP holder unstakes its share of the compounded rewards
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, liquid_denom.clone())]),
+                ExecuteMsg::LiquidUnstake {},
+            )
+            .unwrap();
+            assert_eq!(res.messages.len(), 2);
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Custom(TokenFactoryMsg::BurnTokens {
+                    denom: liquid_denom,
+                    amount: Uint128::new(1000),
+                    burn_from_address: "cosmos2contract".to_string(),
+                })
+            );
+            assert_eq!(
+                res.messages[1].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "addr1".to_string(),
+                    amount: vec![Coin::new(1951u128, "watt")],
+                })
+            );
+
+            let pool = stakers()
+                .load(&deps.storage, Addr::unchecked("cosmos2contract"))
+                .unwrap();
+            assert_eq!(pool.wattpeak_staked, Uint128::new(999));
+            assert_eq!(
+                TOTAL_WATTPEAK_STAKED.load(&deps.storage).unwrap(),
+                Uint128::new(999)
+            );
+            assert_eq!(
+                LIQUID_SUPPLY.load(&deps.storage).unwrap(),
+                Uint128::new(512)
+            );
+        }
+
+        /// Creates the liquid denom, stakes 1000 for addr1 and liquid stakes 1000 for addr2
+        fn liquid_setup() -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env, String) {
+            let (mut deps, env) = instantiate_with(Config {
+                reward_denoms: vec!["usdc".to_string()],
+                ..default_config()
+            });
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                ExecuteMsg::CreateLiquidDenom {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr2", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::LiquidStake {},
+            )
+            .unwrap();
+            (deps, env, "factory/cosmos2contract/ustwattpeak".to_string())
+        }
+
+        #[test]
+        fn liquid_unstake_pays_out_other_reward_denoms() {
+            let (mut deps, mut env, liquid_denom) = liquid_setup();
+
+            // The pool earns half of the usdc deposit, which it can't restake
+            env.block.time = env.block.time.plus_seconds(86400);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(
+                    "admin",
+                    &[Coin::new(100u128, "watt"), Coin::new(1000u128, "usdc")],
+                ),
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+
+            // Every stWP redeemed takes its share minus the staking fee, the fraction of the first
+            // half stays with the pool for the last stWP redeemed
+            for paid in [237u128, 238] {
+                let res = execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info("addr2", &[Coin::new(500u128, liquid_denom.clone())]),
+                    ExecuteMsg::LiquidUnstake {},
+                )
+                .unwrap();
+                let sends: Vec<_> = res
+                    .messages
+                    .iter()
+                    .filter_map(|msg| match &msg.msg {
+                        CosmosMsg::Bank(BankMsg::Send { to_address, amount })
+                            if amount[0].denom == "usdc" =>
+                        {
+                            Some((to_address.as_str(), amount.clone()))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                assert_eq!(
+                    sends,
+                    vec![
+                        ("addr2", vec![Coin::new(paid, "usdc")]),
+                        ("staking_fee_address", vec![Coin::new(12u128, "usdc")]),
+                    ]
+                );
+            }
+
+            let pool = stakers()
+                .load(&deps.storage, Addr::unchecked("cosmos2contract"))
+                .unwrap();
+            // Only the fraction no one can be paid is left
+            assert!(pool
+                .claimable_denom_rewards
+                .iter()
+                .all(|reward| reward.amount < Decimal::one()));
+        }
+
+        #[test]
+        fn liquid_stake_and_unstake_take_a_single_denom() {
+            let (mut deps, env, liquid_denom) = liquid_setup();
+
+            // A coin sent next to the wattpeak or stWP would not be credited or returned
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(
+                    "addr3",
+                    &[Coin::new(1000u128, "watt"), Coin::new(1000u128, "usdc")],
+                ),
+                ExecuteMsg::LiquidStake {},
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::MultipleDenomsStaked {});
+
+            let err = execute(
+                deps.as_mut(),
+                env,
+                mock_info(
+                    "addr2",
+                    &[
+                        Coin::new(500u128, liquid_denom),
+                        Coin::new(1000u128, "watt"),
+                    ],
+                ),
+                ExecuteMsg::LiquidUnstake {},
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::MultipleDenomsStaked {});
+            assert_eq!(
+                LIQUID_SUPPLY.load(&deps.storage).unwrap(),
+                Uint128::new(1000)
+            );
+        }
+
+        #[test]
+        fn liquid_pool_has_no_voting_power() {
+            let (deps, env, _) = liquid_setup();
+            let height = Some(env.block.height + 1);
+
+            let res: VotingPowerAtHeightResponse = from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::VotingPowerAtHeight {
+                        address: "cosmos2contract".to_string(),
+                        height,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            assert_eq!(res.power, Uint128::zero());
+
+            // Only the stake of addr1 counts towards the quorum
+            let res: TotalPowerAtHeightResponse = from_json(
+                query(deps.as_ref(), env, QueryMsg::TotalPowerAtHeight { height }).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(res.power, Uint128::new(1000));
+        }
+    }
+
+    mod lock_test {
+        use super::*;
+        use crate::{
+            helpers::{
+                calculate_interest_after_epoch, settle_staker, MAX_ACCRUAL_EXPIRIES,
+                SECONDS_PER_YEAR,
+            },
+            state::{Config, LockTier},
+        };
+        use cosmwasm_std::{
+            testing::{mock_dependencies, mock_info},
+            Addr, BankMsg, Coin, CosmosMsg, Decimal, Uint128,
+        };
+
+        const LOCK_DURATION: u64 = 90 * 86400;
+
+        fn lock_config() -> Config {
+            Config {
+                lock_tiers: vec![LockTier {
+                    duration: LOCK_DURATION,
+                    multiplier: Decimal::percent(200),
+                    early_unlock_penalty: Decimal::percent(10),
+                }],
+                ..default_config()
+            }
+        }
+
+        #[test]
+        fn validate_config_lock_tiers() {
+            let deps = mock_dependencies();
+            let mut config = lock_config();
+            config.lock_tiers.push(config.lock_tiers[0].clone());
+            assert_eq!(
+                config.validate(deps.as_ref()).unwrap_err(),
+                ContractError::DuplicateLockDuration {
+                    duration: LOCK_DURATION
+                }
+            );
+
+            let mut config = lock_config();
+            config.lock_tiers[0].multiplier = Decimal::percent(50);
+            assert_eq!(
+                config.validate(deps.as_ref()).unwrap_err(),
+                ContractError::LockMultiplierTooLow {}
+            );
+        }
+
+        #[test]
+        fn locked_stake_earns_boosted_interest() {
+            let (mut deps, mut env) = instantiate_with(lock_config());
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr2", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Lock { duration: 86400 },
+            );
+            assert_eq!(
+                res.unwrap_err(),
+                ContractError::NoLockTier { duration: 86400 }
+            );
+
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr2", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Lock {
+                    duration: LOCK_DURATION,
+                },
+            )
+            .unwrap();
+            assert_eq!(res.attributes[0].value, "1");
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr2", &[]),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::new(100),
+                    denom: None,
+                },
+            );
+            assert_eq!(
+                res.unwrap_err(),
+                ContractError::InsufficientUnlockedStake {}
+            );
+
+            // The locked stake earns interest on twice its amount
+            env.block.time = env.block.time.plus_seconds(86400);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            let now = env.block.time.seconds();
+            let mut staker1 = stakers()
+                .load(&deps.storage, Addr::unchecked("addr1"))
+                .unwrap();
+            settle_staker(&deps.storage, &mut staker1, now).unwrap();
+            let mut staker2 = stakers()
+                .load(&deps.storage, Addr::unchecked("addr2"))
+                .unwrap();
+            settle_staker(&deps.storage, &mut staker2, now).unwrap();
+            assert_eq!(
+                staker2.interest_wattpeak,
+                staker1.interest_wattpeak * Decimal::percent(200)
+            );
+
+            // Unlocking early pays the penalty from the stake to the fee address
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr2", &[]),
+                ExecuteMsg::Unlock { id: 1 },
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "staking_fee_address".to_string(),
+                    amount: vec![Coin::new(100u128, "watt")],
+                })
+            );
+            let staker2 = stakers()
+                .load(&deps.storage, Addr::unchecked("addr2"))
+                .unwrap();
+            assert_eq!(staker2.wattpeak_staked, Uint128::new(900));
+            assert_eq!(staker2.locked_wattpeak, Uint128::zero());
+            assert_eq!(staker2.lock_boost, Uint128::zero());
+            assert_eq!(
+                TOTAL_WATTPEAK_STAKED.load(&deps.storage).unwrap(),
+                Uint128::new(1900)
+            );
+            assert_eq!(
+                TOTAL_LOCK_BOOST.load(&deps.storage).unwrap(),
+                Uint128::zero()
+            );
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr2", &[]),
+                ExecuteMsg::Unlock { id: 1 },
+            );
+            assert_eq!(res.unwrap_err(), ContractError::LockNotFound { id: 1 });
+            execute(
+                deps.as_mut(),
+                env,
+                mock_info("addr2", &[]),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::new(900),
+                    denom: None,
+                },
+            )
+            .unwrap();
+        }
+
+        #[test]
+        fn expired_lock_boost_ends_at_unlock_time() {
+            let (mut deps, mut env) = instantiate_with(lock_config());
+
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr2", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Lock {
+                    duration: LOCK_DURATION,
+                },
+            )
+            .unwrap();
+
+            // Nothing happens until well after the lock expired
+            env.block.time = env.block.time.plus_seconds(LOCK_DURATION + 10 * 86400);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+
+            assert!(LOCKS
+                .may_load(&deps.storage, (Addr::unchecked("addr2"), 1))
+                .unwrap()
+                .is_none());
+            assert_eq!(
+                TOTAL_LOCK_BOOST.load(&deps.storage).unwrap(),
+                Uint128::zero()
+            );
+
+            // The boost only earned until the unlock time
+            let now = env.block.time.seconds();
+            let mut staker1 = stakers()
+                .load(&deps.storage, Addr::unchecked("addr1"))
+                .unwrap();
+            settle_staker(&deps.storage, &mut staker1, now).unwrap();
+            let mut staker2 = stakers()
+                .load(&deps.storage, Addr::unchecked("addr2"))
+                .unwrap();
+            settle_staker(&deps.storage, &mut staker2, now).unwrap();
+            assert_eq!(staker2.locked_wattpeak, Uint128::zero());
+            let boost_interest = Decimal::from_ratio(1000u128, 1u128)
+                * Decimal::percent(10)
+                * Decimal::from_ratio(LOCK_DURATION, SECONDS_PER_YEAR);
+            assert_eq!(
+                (staker2.interest_wattpeak - staker1.interest_wattpeak).to_uint_floor(),
+                boost_interest.to_uint_floor()
+            );
+
+            // The stake can be unstaked once the lock expired
+            execute(
+                deps.as_mut(),
+                env,
+                mock_info("addr2", &[]),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::new(1000),
+                    denom: None,
+                },
+            )
+            .unwrap();
+        }
+
+        #[test]
+        fn expiry_backlog_is_released_in_batches() {
+            let (mut deps, mut env) = instantiate_with(lock_config());
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            // More locks expire at once than three accruals release
+            let locks = 3 * MAX_ACCRUAL_EXPIRIES + 1;
+            for _ in 0..locks {
+                execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info("addr2", &[Coin::new(10u128, "watt")]),
+                    ExecuteMsg::Lock {
+                        duration: LOCK_DURATION,
+                    },
+                )
+                .unwrap();
+            }
+            let boost_per_lock =
+                TOTAL_LOCK_BOOST.load(&deps.storage).unwrap().u128() / locks as u128;
+            env.block.time = env.block.time.plus_seconds(LOCK_DURATION + 1);
+
+            // Ordinary messages still go through, each releasing part of the backlog
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            assert_eq!(
+                TOTAL_LOCK_BOOST.load(&deps.storage).unwrap().u128(),
+                boost_per_lock * (2 * MAX_ACCRUAL_EXPIRIES + 1) as u128
+            );
+
+            // A new epoch accrues twice, for the end of the epoch and for now
+            new_epoch(&mut deps, &env);
+            assert_eq!(
+                TOTAL_LOCK_BOOST.load(&deps.storage).unwrap().u128(),
+                boost_per_lock
+            );
+
+            // Anyone can release the rest of the backlog
+            let process = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, env: &Env| {
+                execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info("anyone", &[]),
+                    ExecuteMsg::ProcessExpiries { limit: Some(1) },
+                )
+                .unwrap()
+            };
+            let res = process(&mut deps, &env);
+            assert_eq!(attribute(&res, "processed"), "1");
+            assert_eq!(attribute(&res, "done"), "true");
+            assert_eq!(
+                TOTAL_LOCK_BOOST.load(&deps.storage).unwrap(),
+                Uint128::zero()
+            );
+            let staker = stakers()
+                .load(&deps.storage, Addr::unchecked("addr2"))
+                .unwrap();
+            assert_eq!(staker.locked_wattpeak, Uint128::zero());
+
+            let res = process(&mut deps, &env);
+            assert_eq!(attribute(&res, "processed"), "0");
+            assert_eq!(attribute(&res, "done"), "true");
+            execute(
+                deps.as_mut(),
+                env,
+                mock_info("addr1", &[]),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::new(2000),
+                    denom: None,
+                },
+            )
+            .unwrap();
+        }
+    }
+
+    mod loyalty_test {
+        use super::*;
+        use crate::{
+            helpers::{calculate_interest_after_epoch, settle_staker, MAX_ACCRUAL_EXPIRIES},
+            state::{Config, EarlyUnstakeFee, LoyaltyTier, LOYALTY_UPDATES, TOTAL_LOYALTY_BOOST},
+        };
+        use cosmwasm_std::{
+            testing::{mock_dependencies, mock_info},
+            Addr, BankMsg, Coin, CosmosMsg, Decimal, Uint128,
+        };
+
+        const DAY: u64 = 86400;
+
+        fn loyalty_config() -> Config {
+            Config {
+                early_unstake_fee: Some(EarlyUnstakeFee {
+                    period: 10 * DAY,
+                    percentage: Decimal::percent(10),
+                }),
+                loyalty_tiers: vec![LoyaltyTier {
+                    duration: 30 * DAY,
+                    multiplier: Decimal::percent(150),
+                }],
+                ..default_config()
+            }
+        }
+
+        #[test]
+        fn validate_config_early_unstake_fee_and_loyalty_tiers() {
+            let deps = mock_dependencies();
+            let mut config = loyalty_config();
+            config.early_unstake_fee = Some(EarlyUnstakeFee {
+                period: 0,
+                percentage: Decimal::percent(10),
+            });
+            assert_eq!(
+                config.validate(deps.as_ref()).unwrap_err(),
+                ContractError::EarlyUnstakeFeePeriodZero {}
+            );
+
+            let mut config = loyalty_config();
+            config.loyalty_tiers[0].multiplier = Decimal::percent(90);
+            assert_eq!(
+                config.validate(deps.as_ref()).unwrap_err(),
+                ContractError::LoyaltyMultiplierTooLow {}
+            );
+        }
+
+        #[test]
+        fn early_unstake_fee_declines_over_the_period() {
+            let (mut deps, mut env) = instantiate_with(loyalty_config());
+
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            // Halfway through the period half of the fee is paid
+            env.block.time = env.block.time.plus_seconds(5 * DAY);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::new(100),
+                    denom: None,
+                },
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages
+                    .iter()
+                    .map(|msg| msg.msg.clone())
+                    .collect::<Vec<_>>(),
+                vec![
+                    CosmosMsg::Bank(BankMsg::Send {
+                        to_address: "staking_fee_address".to_string(),
+                        amount: vec![Coin::new(5u128, "watt")],
+                    }),
+                    CosmosMsg::Bank(BankMsg::Send {
+                        to_address: "addr1".to_string(),
+                        amount: vec![Coin::new(95u128, "watt")],
+                    }),
+                ]
+            );
+
+            // No fee is paid once the period has passed
+            env.block.time = env.block.time.plus_seconds(5 * DAY);
+            let res = execute(
+                deps.as_mut(),
+                env,
+                mock_info("addr1", &[]),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::new(100),
+                    denom: None,
+                },
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "addr1".to_string(),
+                    amount: vec![Coin::new(100u128, "watt")],
+                })
+            );
+        }
+
+        #[test]
+        fn top_up_moves_stake_start_time_to_weighted_average() {
+            let (mut deps, mut env) = instantiate_with(loyalty_config());
+            let start = env.block.time.seconds();
+
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            let staker = stakers()
+                .load(&deps.storage, Addr::unchecked("addr1"))
+                .unwrap();
+            assert_eq!(staker.stake_start_time, start);
+            assert_eq!(staker.loyalty_update_time, start + 30 * DAY);
+
+            env.block.time = env.block.time.plus_seconds(10 * DAY);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(3000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            // A quarter of the stake started ten days ago
+            let staker = stakers()
+                .load(&deps.storage, Addr::unchecked("addr1"))
+                .unwrap();
+            let weighted_start = start + 10 * DAY - 10 * DAY / 4;
+            assert_eq!(staker.stake_start_time, weighted_start);
+            assert_eq!(staker.loyalty_update_time, weighted_start + 30 * DAY);
+            assert!(LOYALTY_UPDATES.has(
+                &deps.storage,
+                (weighted_start + 30 * DAY, Addr::unchecked("addr1"))
+            ));
+            assert!(
+                !LOYALTY_UPDATES.has(&deps.storage, (start + 30 * DAY, Addr::unchecked("addr1")))
+            );
+        }
+
+        #[test]
+        fn restake_moves_stake_start_time_like_a_top_up() {
+            let (mut deps, mut env) = instantiate_with(loyalty_config());
+            let start = env.block.time.seconds();
+
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            env.block.time = env.block.time.plus_seconds(10 * DAY);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(100000u128, "watt")]),
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::Restake {},
+            )
+            .unwrap();
+            let restaked: u64 = attribute(&res, "amount").parse().unwrap();
+            assert!(restaked > 0);
+
+            // The restaked rewards start now, the same as staking them manually would
+            let now = env.block.time.seconds();
+            let staker = stakers()
+                .load(&deps.storage, Addr::unchecked("addr1"))
+                .unwrap();
+            let weighted_start = (start * 1000000 + now * restaked) / (1000000 + restaked);
+            assert!(weighted_start > start);
+            assert_eq!(staker.stake_start_time, weighted_start);
+            assert_eq!(staker.loyalty_update_time, weighted_start + 30 * DAY);
+        }
+
+        #[test]
+        fn loyalty_tier_boosts_interest() {
+            let (mut deps, mut env) = instantiate_with(loyalty_config());
+
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+
+            // The loyalty tier is reached while accruing, without any action of the staker
+            env.block.time = env.block.time.plus_seconds(31 * DAY);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            let staker1 = stakers()
+                .load(&deps.storage, Addr::unchecked("addr1"))
+                .unwrap();
+            assert_eq!(staker1.loyalty_boost, Uint128::new(500));
+            assert_eq!(staker1.loyalty_update_time, 0);
+            assert_eq!(
+                TOTAL_LOYALTY_BOOST.load(&deps.storage).unwrap(),
+                Uint128::new(500)
+            );
+
+            // From now on the loyal stake earns one and a half times the interest of a new stake
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr2", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            let now = env.block.time.seconds();
+            let mut staker1 = stakers()
+                .load(&deps.storage, Addr::unchecked("addr1"))
+                .unwrap();
+            settle_staker(&deps.storage, &mut staker1, now).unwrap();
+            let interest_before = staker1.interest_wattpeak;
+
+            env.block.time = env.block.time.plus_seconds(DAY);
+            calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+            let now = env.block.time.seconds();
+            let mut staker1 = stakers()
+                .load(&deps.storage, Addr::unchecked("addr1"))
+                .unwrap();
+            settle_staker(&deps.storage, &mut staker1, now).unwrap();
+            let mut staker2 = stakers()
+                .load(&deps.storage, Addr::unchecked("addr2"))
+                .unwrap();
+            settle_staker(&deps.storage, &mut staker2, now).unwrap();
+            assert_eq!(
+                staker1.interest_wattpeak - interest_before,
+                staker2.interest_wattpeak * Decimal::percent(150)
+            );
+
+            // Unstaking everything ends the loyalty
+            execute(
+                deps.as_mut(),
+                env,
+                mock_info("addr1", &[]),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::new(1000),
+                    denom: None,
+                },
+            )
+            .unwrap();
+            assert_eq!(
+                TOTAL_LOYALTY_BOOST.load(&deps.storage).unwrap(),
+                Uint128::zero()
+            );
+        }
+
+        #[test]
+        fn loyalty_backlog_is_processed_in_batches() {
+            let (mut deps, mut env) = instantiate_with(loyalty_config());
+
+            // More stakers reach their loyalty tier at once than a single accrual processes
+            let stakers_count = MAX_ACCRUAL_EXPIRIES + 2;
+            for i in 0..stakers_count {
+                execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info(&format!("addr{i}"), &[Coin::new(10u128, "watt")]),
+                    ExecuteMsg::Stake {},
+                )
+                .unwrap();
+            }
+            env.block.time = env.block.time.plus_seconds(31 * DAY);
+
+            // Ordinary messages still go through, each processing part of the backlog
+            let stake = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, env: &Env| {
+                execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info("late", &[Coin::new(1000u128, "watt")]),
+                    ExecuteMsg::Stake {},
+                )
+                .unwrap()
+            };
+            stake(&mut deps, &env);
+            assert_eq!(
+                TOTAL_LOYALTY_BOOST.load(&deps.storage).unwrap(),
+                Uint128::new(5 * MAX_ACCRUAL_EXPIRIES as u128)
+            );
+
+            // Anyone can process part of the backlog, the rest is processed by the next accrual
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("anyone", &[]),
+                ExecuteMsg::ProcessExpiries { limit: Some(1) },
+            )
+            .unwrap();
+            assert_eq!(attribute(&res, "processed"), "1");
+            assert_eq!(attribute(&res, "done"), "false");
+
+            stake(&mut deps, &env);
+            assert_eq!(
+                TOTAL_LOYALTY_BOOST.load(&deps.storage).unwrap(),
+                Uint128::new(5 * stakers_count as u128)
+            );
+            for i in 0..stakers_count {
+                let staker = stakers()
+                    .load(&deps.storage, Addr::unchecked(format!("addr{i}")))
+                    .unwrap();
+                assert_eq!(staker.loyalty_boost, Uint128::new(5));
+            }
+        }
+    }
+
+    mod transfer_stake_test {
+        use super::*;
+        use crate::{
+            helpers::settle_staker,
+            state::{Config, LockTier, STAKER_COUNT},
+        };
+        use cosmwasm_std::{testing::mock_info, Addr, Coin, Decimal, Uint128};
+
+        fn transfer_config() -> Config {
+            Config {
+                lock_tiers: vec![LockTier {
+                    duration: 86400 * 30,
+                    multiplier: Decimal::percent(200),
+                    early_unlock_penalty: Decimal::percent(10),
+                }],
+                ..default_config()
+            }
+        }
+
+        #[test]
+        fn transfer_stake_without_rewards() {
+            let (mut deps, mut env) = instantiate_with(transfer_config());
+            let start = env.block.time.seconds();
+
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(500u128, "watt")]),
+                ExecuteMsg::Lock {
+                    duration: 86400 * 30,
+                },
+            )
+            .unwrap();
+
+            env.block.time = env.block.time.plus_seconds(86400);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::TransferStake {
+                    recipient: "addr1".to_string(),
+                    amount: Uint128::new(100),
+                    include_rewards: false,
+                },
+            );
+            assert_eq!(res.unwrap_err(), ContractError::TransferToSelf {});
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::TransferStake {
+                    recipient: "addr2".to_string(),
+                    amount: Uint128::new(1100),
+                    include_rewards: false,
+                },
+            );
+            assert_eq!(
+                res.unwrap_err(),
+                ContractError::InsufficientUnlockedStake {}
+            );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::TransferStake {
+                    recipient: "addr2".to_string(),
+                    amount: Uint128::new(400),
+                    include_rewards: false,
+                },
+            )
+            .unwrap();
 
-    mod update_config {
+            let staker1 = stakers()
+                .load(&deps.storage, Addr::unchecked("addr1"))
+                .unwrap();
+            let staker2 = stakers()
+                .load(&deps.storage, Addr::unchecked("addr2"))
+                .unwrap();
+            assert_eq!(staker1.wattpeak_staked, Uint128::new(1100));
+            assert_eq!(staker1.locked_wattpeak, Uint128::new(500));
+            assert!(!staker1.interest_wattpeak.is_zero());
+            assert_eq!(staker2.wattpeak_staked, Uint128::new(400));
+            assert_eq!(staker2.stake_start_time, start);
+            assert_eq!(staker2.interest_wattpeak, Decimal::zero());
+            assert_eq!(
+                TOTAL_WATTPEAK_STAKED.load(&deps.storage).unwrap(),
+                Uint128::new(1500)
+            );
+            assert_eq!(STAKER_COUNT.load(&deps.storage).unwrap(), 2);
+        }
+
+        #[test]
+        fn transfer_stake_with_rewards() {
+            let (mut deps, mut env) = instantiate_with(transfer_config());
+
+            for address in ["addr1", "addr2"] {
+                execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info(address, &[Coin::new(1000u128, "watt")]),
+                    ExecuteMsg::Stake {},
+                )
+                .unwrap();
+            }
+            env.block.time = env.block.time.plus_seconds(86400);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+            env.block.time = env.block.time.plus_seconds(86400);
+
+            let now = env.block.time.seconds();
+            let mut expected = stakers()
+                .load(&deps.storage, Addr::unchecked("addr1"))
+                .unwrap();
+            settle_staker(&deps.storage, &mut expected, now).unwrap();
+
+            // The whole stake and rewards move, the empty record of the sender is removed
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::TransferStake {
+                    recipient: "addr3".to_string(),
+                    amount: Uint128::new(1000),
+                    include_rewards: true,
+                },
+            )
+            .unwrap();
+
+            assert!(stakers()
+                .may_load(&deps.storage, Addr::unchecked("addr1"))
+                .unwrap()
+                .is_none());
+            let staker3 = stakers()
+                .load(&deps.storage, Addr::unchecked("addr3"))
+                .unwrap();
+            assert_eq!(staker3.wattpeak_staked, Uint128::new(1000));
+            assert_eq!(staker3.interest_wattpeak, expected.interest_wattpeak);
+            assert_eq!(staker3.claimable_rewards, expected.claimable_rewards);
+            assert_eq!(staker3.claimable_rewards.to_uint_floor(), Uint128::new(499));
+            assert_eq!(STAKER_COUNT.load(&deps.storage).unwrap(), 2);
+            assert_eq!(
+                TOTAL_WATTPEAK_STAKED.load(&deps.storage).unwrap(),
+                Uint128::new(2000)
+            );
+        }
+    }
+
+    mod claim_to_test {
+        use super::*;
+        use crate::{
+            ibc::{MsgTransferResponse, IBC_TRANSFER_REPLY_ID},
+            msg::{IbcLifecycleComplete, SudoMsg},
+            reply,
+            state::IBC_TRANSFERS,
+            sudo,
+        };
         use cosmwasm_std::{
-            testing::{mock_dependencies, mock_env, mock_info},
-            Addr, Decimal,
+            testing::{mock_info, MockApi, MockQuerier, MockStorage},
+            Addr, BankMsg, Binary, Coin, CosmosMsg, OwnedDeps, Reply, SubMsgResponse, SubMsgResult,
+            Uint128,
         };
+        use prost::Message;
 
-        use crate::execute::execute;
-        use crate::msg::ExecuteMsg;
-        use crate::{instantiate, msg::InstantiateMsg, state::Config};
+        fn staker_with_rewards() -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env) {
+            let (mut deps, mut env) = instantiate_with(default_config());
 
-        use super::CONFIG;
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            env.block.time = env.block.time.plus_seconds(86400);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+            (deps, env)
+        }
 
         #[test]
-        fn proper_update_config() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
+        fn claim_to_withdraw_address_or_recipient() {
+            let (mut deps, env) = staker_with_rewards();
 
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::SetWithdrawAddress {
+                    address: Some("cold_wallet".to_string()),
                 },
-            };
+            )
+            .unwrap();
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "cold_wallet".to_string(),
+                    amount: vec![Coin::new(950u128, "watt")],
+                })
+            );
 
-            let info = mock_info("admin", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
-            let msg = ExecuteMsg::UpdateConfig {
-                admin: Some("new_admin".to_string()),
-                rewards_percentage: Some(Decimal::percent(5)),
-                epoch_length: Some(86400),
+            // A recipient passed on the claim takes precedence over the withdraw address
+            let mut env = env;
+            env.block.time = env.block.time.plus_seconds(86400);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+            let res = execute(
+                deps.as_mut(),
+                env,
+                mock_info("addr1", &[]),
+                ExecuteMsg::ClaimReward {
+                    recipient: Some("multisig".to_string()),
+                    ibc: None,
+                },
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "multisig".to_string(),
+                    amount: vec![Coin::new(950u128, "watt")],
+                })
+            );
+        }
+
+        #[test]
+        fn claim_over_ibc_is_refunded_on_timeout() {
+            let (mut deps, env) = staker_with_rewards();
+            let ibc = IbcClaim {
+                channel_id: "channel-0".to_string(),
+                receiver: "osmo1receiver".to_string(),
+                timeout: None,
             };
-            execute(deps.as_mut(), env.clone(), info, msg).unwrap();
-            CONFIG
-                .save(
-                    deps.as_mut().storage,
-                    &Config {
-                        admin: Addr::unchecked("admin"),
-                        rewards_percentage: Decimal::percent(10),
-                        epoch_length: 86400,
-                        wattpeak_denom: "watt".to_string(),
-                        staking_fee_address: Addr::unchecked("staking_fee_address"),
-                        staking_fee_percentage: Decimal::percent(5),
-                    },
-                )
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::ClaimReward {
+                    recipient: Some("multisig".to_string()),
+                    ibc: Some(ibc.clone()),
+                },
+            );
+            assert_eq!(res.unwrap_err(), ContractError::ClaimToRecipientAndIbc {});
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: Some(ibc),
+                },
+            )
+            .unwrap();
+            let transfer = res
+                .messages
+                .iter()
+                .find(|msg| msg.id == IBC_TRANSFER_REPLY_ID)
                 .unwrap();
-            let updated_config = CONFIG.load(deps.as_ref().storage).unwrap();
-            assert_eq!(updated_config.admin, Addr::unchecked("admin"));
+            match &transfer.msg {
+                CosmosMsg::Stargate { type_url, .. } => {
+                    assert_eq!(type_url, "/ibc.applications.transfer.v1.MsgTransfer")
+                }
+                msg => panic!("unexpected message {:?}", msg),
+            }
+
+            // The reply returns the sequence the transfer was sent with
+            reply(
+                deps.as_mut(),
+                env.clone(),
+                Reply {
+                    id: IBC_TRANSFER_REPLY_ID,
+                    result: SubMsgResult::Ok(SubMsgResponse {
+                        events: vec![],
+                        data: Some(Binary(MsgTransferResponse { sequence: 7 }.encode_to_vec())),
+                    }),
+                },
+            )
+            .unwrap();
+            let pending = IBC_TRANSFERS.load(&deps.storage, ("channel-0", 7)).unwrap();
+            assert_eq!(pending.staker, Addr::unchecked("addr1"));
+            assert_eq!(pending.amount, Coin::new(950u128, "watt"));
+
+            let res = sudo(
+                deps.as_mut(),
+                env.clone(),
+                SudoMsg::IbcLifecycleComplete(IbcLifecycleComplete::IbcTimeout {
+                    channel: "channel-0".to_string(),
+                    sequence: 7,
+                }),
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "addr1".to_string(),
+                    amount: vec![Coin::new(950u128, "watt")],
+                })
+            );
+
+            let res = sudo(
+                deps.as_mut(),
+                env,
+                SudoMsg::IbcLifecycleComplete(IbcLifecycleComplete::IbcAck {
+                    channel: "channel-0".to_string(),
+                    sequence: 7,
+                    ack: String::new(),
+                    success: true,
+                }),
+            );
+            assert_eq!(
+                res.unwrap_err(),
+                ContractError::UnknownIbcTransfer {
+                    channel: "channel-0".to_string(),
+                    sequence: 7
+                }
+            );
+            assert_eq!(
+                stakers()
+                    .load(&deps.storage, Addr::unchecked("addr1"))
+                    .unwrap()
+                    .wattpeak_staked,
+                Uint128::new(1000)
+            );
         }
-        #[test]
-        fn unauthorized_update_config() {
+    }
+
+    mod pool_test {
+        use super::*;
+        use crate::{
+            instantiate,
+            msg::{
+                InstantiateMsg, QueryMsg, TotalPowerAtHeightResponse, VotingPowerAtHeightResponse,
+            },
+            query::query,
+            state::{Config, TOTAL_WATTPEAK_STAKED},
+        };
+        use cosmwasm_std::{
+            from_json,
+            testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage},
+            Addr, BankMsg, Binary, Coin, ContractResult, CosmosMsg, Decimal, OwnedDeps,
+            SystemError, SystemResult, Uint128, WasmQuery,
+        };
+
+        use crate::msg::MinterQueryMsg;
+
+        /// Instantiates the staker with a minter that knows project 1, 5000 wattpeak of which
+        /// have been minted
+        fn staker_with_minter() -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env) {
             let mut deps = mock_dependencies();
+            deps.querier.update_wasm(|query| match query {
+                WasmQuery::Smart { contract_addr, msg } if contract_addr == "minter" => {
+                    match from_json(msg).unwrap() {
+                        MinterQueryMsg::Project { id: 1 } => SystemResult::Ok(ContractResult::Ok(
+                            Binary::from(
+                                br#"{"name":"Solar park","description":"","document_deal_link":"","max_wattpeak":10000,"image_link":"","location":"","minted_wattpeak_count":5000}"#
+                                    .as_slice(),
+                            ),
+                        )),
+                        _ => SystemResult::Ok(ContractResult::Err("Project not found".to_string())),
+                    }
+                }
+                WasmQuery::Raw { contract_addr, key }
+                    if contract_addr == "minter" && key.as_slice() == b"token_full_denom" =>
+                {
+                    SystemResult::Ok(ContractResult::Ok(Binary::from(br#""watt""#.as_slice())))
+                }
+                _ => SystemResult::Err(SystemError::Unknown {}),
+            });
             let env = mock_env();
-
             let msg = InstantiateMsg {
                 config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
+                    minter_address: Some(Addr::unchecked("minter")),
+                    ..default_config()
                 },
             };
+            instantiate(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+            (deps, env)
+        }
 
-            let info = mock_info("admin", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
-            let msg = ExecuteMsg::UpdateConfig {
-                admin: Some("new_admin".to_string()),
-                rewards_percentage: Some(Decimal::percent(5)),
-                epoch_length: Some(86400),
+        #[test]
+        fn create_pool_checks_the_minter_project() {
+            let (mut deps, env) = staker_with_minter();
+            let create = |project_id| ExecuteMsg::CreatePool {
+                project_id,
+                rewards_percentage: Decimal::percent(10),
             };
-            let res = execute(deps.as_mut(), env.clone(), mock_info("random", &[]), msg);
-            assert_eq!(res.unwrap_err().to_string(), "Generic error: Unauthorized");
+
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                create(1),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
+
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                create(2),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::ProjectNotFound { project_id: 2 });
+
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                ExecuteMsg::CreatePool {
+                    project_id: 1,
+                    rewards_percentage: Decimal::percent(101),
+                },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::RewardsPercentageTooHigh {});
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                create(1),
+            )
+            .unwrap();
+            assert!(res
+                .attributes
+                .iter()
+                .any(|attr| attr.key == "name" && attr.value == "Solar park"));
+            let pool = POOLS.load(deps.as_ref().storage, 1).unwrap();
+            assert_eq!(pool.name, "Solar park");
+            assert_eq!(pool.rewards_percentage, Decimal::percent(10));
+
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                create(1),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::PoolExists { project_id: 1 });
+
+            let err = execute(
+                deps.as_mut(),
+                env,
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::StakeInPool { project_id: 2 },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::PoolNotFound { project_id: 2 });
         }
-        #[test]
-        fn validate_config_epoch() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
 
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
+        #[test]
+        fn pool_rewards_go_to_the_pool_stakers() {
+            let (mut deps, mut env) = staker_with_minter();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                ExecuteMsg::CreatePool {
+                    project_id: 1,
+                    rewards_percentage: Decimal::percent(10),
                 },
-            };
+            )
+            .unwrap();
+
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::StakeInPool { project_id: 1 },
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr2", &[Coin::new(3000u128, "watt")]),
+                ExecuteMsg::StakeInPool { project_id: 1 },
+            )
+            .unwrap();
+
+            // The pool cannot hold more than was minted for the project
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr3", &[Coin::new(1001u128, "watt")]),
+                ExecuteMsg::StakeInPool { project_id: 1 },
+            )
+            .unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::PoolCapExceeded {
+                    project_id: 1,
+                    minted: 5000
+                }
+            );
+
+            // Wattpeak staked in a pool is apart from the stake in the contract
+            assert_eq!(
+                TOTAL_WATTPEAK_STAKED.load(deps.as_ref().storage).unwrap(),
+                Uint128::zero()
+            );
+            assert!(stakers()
+                .may_load(deps.as_ref().storage, Addr::unchecked("addr1"))
+                .unwrap()
+                .is_none());
 
-            let info = mock_info("admin", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
-            let msg = ExecuteMsg::UpdateConfig {
-                admin: Some("new_admin".to_string()),
-                rewards_percentage: Some(Decimal::percent(5)),
-                epoch_length: Some(0),
-            };
-            let res = execute(deps.as_mut(), env.clone(), info, msg);
+            env.block.time = env.block.time.plus_seconds(86400);
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::DepositPoolRewards { project_id: 1 },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(1000u128, "other")]),
+                ExecuteMsg::DepositPoolRewards { project_id: 1 },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::NoWattpeakDeposited {});
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::DepositPoolRewards { project_id: 1 },
+            )
+            .unwrap();
+
+            // addr1 earned a quarter of the pool's interest, so just under a quarter of its
+            // rewards after rounding. The fraction stays claimable
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::ClaimPoolRewards { project_id: 1 },
+            )
+            .unwrap();
             assert_eq!(
-                res.unwrap_err().to_string(),
-                "Generic error: epoch_length cannot be zero"
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "addr1".to_string(),
+                    amount: vec![Coin::new(237u128, "watt")],
+                })
+            );
+            assert_eq!(
+                res.messages[1].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "staking_fee_address".to_string(),
+                    amount: vec![Coin::new(12u128, "watt")],
+                })
             );
-        }
-        #[test]
-        fn validate_config_rewards_percentage() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
 
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
-                },
-            };
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr3", &[]),
+                ExecuteMsg::ClaimPoolRewards { project_id: 1 },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::StakerNotFound {});
 
-            let info = mock_info("admin", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
-            let msg = ExecuteMsg::UpdateConfig {
-                admin: Some("new_admin".to_string()),
-                rewards_percentage: Some(Decimal::percent(101)),
-                epoch_length: Some(86400),
-            };
-            let res = execute(deps.as_mut(), env.clone(), info, msg);
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::UnstakeFromPool {
+                    project_id: 1,
+                    amount: Uint128::new(1001),
+                },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::InsufficientStake {});
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::UnstakeFromPool {
+                    project_id: 1,
+                    amount: Uint128::new(1000),
+                },
+            )
+            .unwrap();
             assert_eq!(
-                res.unwrap_err().to_string(),
-                "Generic error: rewards_percentage cannot be greater than 100%"
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "addr1".to_string(),
+                    amount: vec![Coin::new(1000u128, "watt")],
+                })
+            );
+            assert_eq!(
+                POOLS.load(deps.as_ref().storage, 1).unwrap().total_staked,
+                Uint128::new(3000)
             );
+            assert!(!POOL_STAKES.has(deps.as_ref().storage, (1, Addr::unchecked("addr1"))));
         }
-    }
-    mod staking_tests {
-        use super::*;
-        use crate::{instantiate, msg::InstantiateMsg, state::Config};
-        use cosmwasm_std::{
-            testing::{mock_dependencies, mock_env, mock_info},
-            Addr, Coin, Decimal, Uint128,
-        };
 
         #[test]
-        fn stake_wattpeak() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
-
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
+        fn pool_stakes_are_left_out_of_voting_and_hooks() {
+            let (mut deps, env) = staker_with_minter();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                ExecuteMsg::CreatePool {
+                    project_id: 1,
+                    rewards_percentage: Decimal::percent(10),
                 },
-            };
-
-            let info = mock_info("creator", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
-
-            let staker_info = mock_info("staker", &[Coin::new(100u128, "watt")]);
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                ExecuteMsg::AddHook {
+                    address: "hook".to_string(),
+                    fail_on_error: true,
+                },
+            )
+            .unwrap();
 
             let res = execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info.clone(),
-                ExecuteMsg::Stake {},
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::StakeInPool { project_id: 1 },
             )
             .unwrap();
+            assert!(res.messages.is_empty());
+
+            let height = Some(env.block.height + 1);
+            let res: VotingPowerAtHeightResponse = from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::VotingPowerAtHeight {
+                        address: "addr1".to_string(),
+                        height,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            assert_eq!(res.power, Uint128::zero());
+            let res: TotalPowerAtHeightResponse = from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::TotalPowerAtHeight { height },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            assert_eq!(res.power, Uint128::zero());
 
-            assert_eq!(res.messages.len(), 0);
-
-            let staker = STAKERS
-                .load(deps.as_ref().storage, staker_info.sender)
-                .unwrap();
-            assert_eq!(staker.wattpeak_staked, Uint128::from(100u128));
-
-            let total_wattpeak: Uint128 =
-                TOTAL_WATTPEAK_STAKED.load(deps.as_ref().storage).unwrap();
-            assert_eq!(total_wattpeak, Uint128::from(100u128));
+            // Only the unstaked wattpeak is sent back, no hook is called
+            let res = execute(
+                deps.as_mut(),
+                env,
+                mock_info("addr1", &[]),
+                ExecuteMsg::UnstakeFromPool {
+                    project_id: 1,
+                    amount: Uint128::new(1000),
+                },
+            )
+            .unwrap();
+            assert!(res
+                .messages
+                .iter()
+                .all(|msg| matches!(msg.msg, CosmosMsg::Bank(_))));
         }
 
         #[test]
-        fn stake_wattpeak_existing_staker() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
-
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
+        fn stake_in_pool_takes_a_single_denom() {
+            let (mut deps, env) = staker_with_minter();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                ExecuteMsg::CreatePool {
+                    project_id: 1,
                     rewards_percentage: Decimal::percent(10),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
                 },
-            };
+            )
+            .unwrap();
 
-            let info = mock_info("creator", &[Coin::new(100u128, "watt")]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+            // A coin sent next to the wattpeak would not be credited
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(
+                    "addr1",
+                    &[Coin::new(1000u128, "watt"), Coin::new(1000u128, "uatom")],
+                ),
+                ExecuteMsg::StakeInPool { project_id: 1 },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::MultipleDenomsStaked {});
+            assert!(!POOL_STAKES.has(deps.as_ref().storage, (1, Addr::unchecked("addr1"))));
 
-            let staker_info = mock_info("staker", &[Coin::new(100u128, "watt")]);
+            let err = execute(
+                deps.as_mut(),
+                env,
+                mock_info("addr1", &[Coin::new(1000u128, "uatom")]),
+                ExecuteMsg::StakeInPool { project_id: 1 },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::NoWattpeakStaked {});
+        }
+    }
 
-            let _res = execute(
+    mod reserve_test {
+        use super::*;
+        use crate::{
+            helpers::SECONDS_PER_YEAR,
+            state::{Config, TOTAL_INTEREST_WATTPEAK},
+        };
+        use cosmwasm_std::{
+            testing::{mock_info, MockApi, MockQuerier, MockStorage},
+            Coin, CosmosMsg, Decimal, OwnedDeps, Uint128,
+        };
+
+        fn staker_with_reserve(
+            cap_interest_to_reserve: bool,
+            reserve: u128,
+        ) -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env) {
+            let (mut deps, env) = instantiate_with(Config {
+                cap_interest_to_reserve,
+                ..default_config()
+            });
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info.clone(),
-                ExecuteMsg::Stake {},
+                mock_info("admin", &[Coin::new(reserve, "watt")]),
+                ExecuteMsg::FundReserve {},
             )
             .unwrap();
-
-            let staker_info2 = mock_info("staker", &[Coin::new(100u128, "watt")]);
-
-            let res = execute(
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info2,
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
                 ExecuteMsg::Stake {},
             )
             .unwrap();
+            (deps, env)
+        }
 
-            assert_eq!(res.messages.len(), 0);
+        #[test]
+        fn fund_reserve_takes_wattpeak_from_the_admin() {
+            let (mut deps, env) = staker_with_reserve(false, 50);
 
-            let staker = STAKERS
-                .load(deps.as_ref().storage, staker_info.sender)
-                .unwrap();
-            assert_eq!(staker.wattpeak_staked, Uint128::from(200u128));
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(50u128, "watt")]),
+                ExecuteMsg::FundReserve {},
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
 
-            let total_wattpeak: Uint128 =
-                TOTAL_WATTPEAK_STAKED.load(deps.as_ref().storage).unwrap();
-            assert_eq!(total_wattpeak, Uint128::from(200u128));
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(50u128, "other")]),
+                ExecuteMsg::FundReserve {},
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::NoWattpeakDeposited {});
+
+            let res = execute(
+                deps.as_mut(),
+                env,
+                mock_info("admin", &[Coin::new(25u128, "watt")]),
+                ExecuteMsg::FundReserve {},
+            )
+            .unwrap();
+            assert_eq!(attribute(&res, "reserve"), "75");
+            assert_eq!(
+                REWARD_RESERVE.load(deps.as_ref().storage).unwrap(),
+                Uint128::new(75)
+            );
         }
+
         #[test]
-        fn stake_zero_amount() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
+        fn epoch_pays_promised_interest_from_reserve() {
+            let (mut deps, mut env) = staker_with_reserve(false, 150);
 
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(10),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
-                },
-            };
+            // Close to 100 interest wattpeak were promised, the reserve covers them
+            env.block.time = env.block.time.plus_seconds(SECONDS_PER_YEAR + 86400);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("anyone", &[]),
+                ExecuteMsg::NewEpoch {},
+            )
+            .unwrap();
+            assert_eq!(attribute(&res, "paid_from_reserve"), "100");
+            assert_eq!(
+                REWARD_RESERVE.load(deps.as_ref().storage).unwrap(),
+                Uint128::new(50)
+            );
 
-            let info = mock_info("creator", &[Coin::new(100u128, "watt")]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+            let res = execute(
+                deps.as_mut(),
+                env,
+                mock_info("addr1", &[]),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "addr1".to_string(),
+                    amount: vec![Coin::new(95u128, "watt")],
+                })
+            );
+        }
 
-            let staker_info = mock_info("staker", &[Coin::new(0u128, "watt")]);
+        #[test]
+        fn underfunded_reserve_pays_what_it_holds() {
+            let (mut deps, mut env) = staker_with_reserve(false, 50);
 
+            // Close to 100 interest wattpeak were promised, the reserve pays the 50 it holds
+            env.block.time = env.block.time.plus_seconds(SECONDS_PER_YEAR);
             let res = execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info.clone(),
-                ExecuteMsg::Stake {},
+                mock_info("anyone", &[]),
+                ExecuteMsg::NewEpoch {},
+            )
+            .unwrap();
+            assert_eq!(attribute(&res, "paid_from_reserve"), "50");
+            assert_eq!(
+                REWARD_RESERVE.load(deps.as_ref().storage).unwrap(),
+                Uint128::zero()
             );
 
+            let res = execute(
+                deps.as_mut(),
+                env,
+                mock_info("addr1", &[]),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            )
+            .unwrap();
             assert_eq!(
-                res.err().unwrap().to_string(),
-                "Generic error: Stake amount can't be zero"
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "addr1".to_string(),
+                    amount: vec![Coin::new(47u128, "watt")],
+                })
             );
         }
-        #[test]
-        fn stake_incorrect_denom() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
 
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(10),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
-                },
-            };
+        #[test]
+        fn capped_interest_stops_at_the_reserve() {
+            let (mut deps, mut env) = staker_with_reserve(true, 50);
 
-            let info = mock_info("creator", &[Coin::new(100u128, "watt")]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+            // A year at 10% would promise 100, the reserve only funds 50
+            env.block.time = env.block.time.plus_seconds(SECONDS_PER_YEAR);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("anyone", &[]),
+                ExecuteMsg::NewEpoch {},
+            )
+            .unwrap();
+            assert_eq!(attribute(&res, "paid_from_reserve"), "50");
+            assert_eq!(
+                REWARD_RESERVE.load(deps.as_ref().storage).unwrap(),
+                Uint128::zero()
+            );
 
-            let staker_info = mock_info("staker", &[Coin::new(100u128, "random")]);
+            // Nothing accrues while the reserve is empty
+            env.block.time = env.block.time.plus_seconds(86400);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("anyone", &[]),
+                ExecuteMsg::NewEpoch {},
+            )
+            .unwrap();
+            assert!(TOTAL_INTEREST_WATTPEAK
+                .load(deps.as_ref().storage)
+                .unwrap()
+                .is_zero());
 
-            let res = execute(
+            // Funding the reserve again lets interest accrue at the full rate
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info.clone(),
-                ExecuteMsg::Stake {},
-            );
-
+                mock_info("admin", &[Coin::new(100u128, "watt")]),
+                ExecuteMsg::FundReserve {},
+            )
+            .unwrap();
+            env.block.time = env.block.time.plus_seconds(SECONDS_PER_YEAR / 2);
+            accrue_interest(deps.as_mut().storage, env.block.time.seconds()).unwrap();
             assert_eq!(
-                res.err().unwrap().to_string(),
-                "Generic error: Must stake WattPeak tokens to the contract"
+                TOTAL_INTEREST_WATTPEAK.load(deps.as_ref().storage).unwrap(),
+                Decimal::percent(5000)
             );
         }
     }
 
-    mod unstake_test {
+    mod rate_schedule_test {
         use super::*;
-        use crate::{instantiate, msg::InstantiateMsg, state::Config};
+        use crate::{
+            helpers::SECONDS_PER_YEAR,
+            state::{EPOCHS, TOTAL_INTEREST_WATTPEAK},
+        };
         use cosmwasm_std::{
-            testing::{mock_dependencies, mock_env, mock_info},
-            Addr, BankMsg, Coin, CosmosMsg, Decimal, Uint128,
+            testing::{mock_info, MockApi, MockQuerier, MockStorage},
+            Coin, Decimal, OwnedDeps,
         };
 
+        fn staker() -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env) {
+            let (mut deps, env) = instantiate_with(default_config());
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            (deps, env)
+        }
+
         #[test]
-        fn unstake_wattpeak() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
-                },
+        fn schedule_and_cancel_rate_changes() {
+            let (mut deps, env) = staker();
+            let now = env.block.time.seconds();
+            let schedule = |start| ExecuteMsg::ScheduleRateChange {
+                rewards_percentage: Decimal::percent(20),
+                start,
             };
 
-            let info = mock_info("creator", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                schedule(RateChangeStart::Time(now + 100)),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
 
-            let staker_info = mock_info("staker", &[Coin::new(100u128, "watt")]);
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                ExecuteMsg::ScheduleRateChange {
+                    rewards_percentage: Decimal::percent(101),
+                    start: RateChangeStart::Time(now + 100),
+                },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::RewardsPercentageTooHigh {});
 
-            let _res = execute(
+            let err = execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info.clone(),
-                ExecuteMsg::Stake {},
+                mock_info("admin", &[]),
+                schedule(RateChangeStart::Time(now)),
             )
-            .unwrap();
-            let amount = Uint128::from(50u128);
+            .unwrap_err();
+            assert_eq!(err, ContractError::RateChangeNotInFuture {});
+
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                schedule(RateChangeStart::Epoch(1)),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::EpochAlreadyStarted { epoch: 1 });
+
+            // Epoch 3 starts two epochs after the epoch in progress
             let res = execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info.clone(),
-                ExecuteMsg::Unstake { amount },
+                mock_info("admin", &[]),
+                schedule(RateChangeStart::Epoch(3)),
             )
             .unwrap();
-
-            assert_eq!(res.messages.len(), 1);
+            let time = now + 2 * 86400;
+            assert!(res
+                .attributes
+                .iter()
+                .any(|attr| attr.key == "time" && attr.value == time.to_string()));
             assert_eq!(
-                res.messages[0].msg,
-                CosmosMsg::Bank(BankMsg::Send {
-                    to_address: staker_info.sender.to_string(),
-                    amount: vec![Coin {
-                        denom: "watt".to_string(),
-                        amount,
-                    }],
-                })
+                RATE_SCHEDULE.load(deps.as_ref().storage, time).unwrap(),
+                Decimal::percent(20)
             );
 
-            let staker = STAKERS
-                .load(deps.as_ref().storage, staker_info.sender)
-                .unwrap();
-            assert_eq!(staker.wattpeak_staked, Uint128::from(50u128));
-
-            let total_wattpeak: Uint128 =
-                TOTAL_WATTPEAK_STAKED.load(deps.as_ref().storage).unwrap();
-            assert_eq!(total_wattpeak, Uint128::from(50u128));
-        }
-        #[test]
-        fn unstake_insufficient_stake() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
-
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
-                },
-            };
-
-            let info = mock_info("creator", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
-
-            let staker_info = mock_info("staker", &[Coin::new(100u128, "watt")]);
-
-            let _res = execute(
+            let err = execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info.clone(),
-                ExecuteMsg::Stake {},
+                mock_info("admin", &[]),
+                ExecuteMsg::CancelRateChange { time: now },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::RateChangeTookEffect { time: now });
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                ExecuteMsg::CancelRateChange { time: time + 1 },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::RateChangeNotFound { time: time + 1 });
+            execute(
+                deps.as_mut(),
+                env,
+                mock_info("admin", &[]),
+                ExecuteMsg::CancelRateChange { time },
             )
             .unwrap();
+            assert!(!RATE_SCHEDULE.has(deps.as_ref().storage, time));
+        }
 
-            let res = execute(
+        #[test]
+        fn interest_follows_the_rate_schedule() {
+            let (mut deps, mut env) = staker();
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info.clone(),
-                ExecuteMsg::Unstake {
-                    amount: Uint128::from(200u128),
+                mock_info("admin", &[]),
+                ExecuteMsg::ScheduleRateChange {
+                    rewards_percentage: Decimal::percent(20),
+                    start: RateChangeStart::Time(env.block.time.seconds() + SECONDS_PER_YEAR / 2),
                 },
+            )
+            .unwrap();
+
+            // Half a year at 10% and half a year at 20%
+            env.block.time = env.block.time.plus_seconds(SECONDS_PER_YEAR);
+            accrue_interest(deps.as_mut().storage, env.block.time.seconds()).unwrap();
+            assert_eq!(
+                TOTAL_INTEREST_WATTPEAK.load(deps.as_ref().storage).unwrap(),
+                Decimal::percent(15000)
+            );
+            assert_eq!(
+                CONFIG
+                    .load(deps.as_ref().storage)
+                    .unwrap()
+                    .rewards_percentage,
+                Decimal::percent(20)
             );
 
+            // The epoch that ended records the rate in effect at its end
+            execute(
+                deps.as_mut(),
+                env,
+                mock_info("anyone", &[]),
+                ExecuteMsg::NewEpoch {},
+            )
+            .unwrap();
             assert_eq!(
-                res.err().unwrap().to_string(),
-                "Generic error: Insufficient staked wattpeak"
+                EPOCHS
+                    .load(deps.as_ref().storage, 1)
+                    .unwrap()
+                    .rewards_percentage,
+                Decimal::percent(20)
             );
         }
-        #[test]
-        fn unstake_staker_doesnt_exist() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
-
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
-                },
-            };
-
-            let info = mock_info("creator", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+    }
 
-            let staker_info = mock_info("staker", &[]);
-            let amount = Uint128::from(100u128);
+    mod reward_stream_test {
+        use super::*;
+        use crate::state::Config;
+        use cosmwasm_std::{
+            testing::{mock_info, MockApi, MockQuerier, MockStorage},
+            Coin, CosmosMsg, OwnedDeps, Uint128,
+        };
 
-            let res = execute(
-                deps.as_mut(),
-                env.clone(),
-                staker_info.clone(),
-                ExecuteMsg::Unstake { amount },
-            );
+        const DAY: u64 = 86400;
 
-            assert_eq!(
-                res.err().unwrap().to_string(),
-                "Generic error: Staker does not exist"
-            );
+        fn staker() -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env) {
+            instantiate_with(Config {
+                reward_denoms: vec!["usdc".to_string()],
+                ..default_config()
+            })
         }
+
         #[test]
-        fn unstake_amount_is_zero() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
+        fn deposit_reward_stream_validation() {
+            let (mut deps, env) = staker();
+            let now = env.block.time.seconds();
+            let deposit = |start, end| ExecuteMsg::DepositRewardStream { start, end };
 
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
-                },
-            };
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                deposit(now, now + DAY),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
 
-            let info = mock_info("creator", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(1000u128, "watt")]),
+                deposit(now - 1, now + DAY),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::StreamStartInPast {});
 
-            let staker_info = mock_info("staker", &[Coin::new(0u128, "watt")]);
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(1000u128, "watt")]),
+                deposit(now + DAY, now + DAY),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::StreamEndBeforeStart {});
 
-            let res = execute(
+            let err = execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info.clone(),
-                ExecuteMsg::Unstake {
-                    amount: Uint128::zero(),
-                },
+                mock_info("admin", &[Coin::new(1000u128, "other")]),
+                deposit(now, now + DAY),
+            )
+            .unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::DenomNotWhitelisted {
+                    denom: "other".to_string()
+                }
             );
 
+            let res = execute(
+                deps.as_mut(),
+                env,
+                mock_info(
+                    "admin",
+                    &[Coin::new(1000u128, "watt"), Coin::new(500u128, "usdc")],
+                ),
+                deposit(now, now + DAY),
+            )
+            .unwrap();
+            assert!(res
+                .attributes
+                .iter()
+                .any(|attr| attr.key == "stream_ids" && attr.value == "1,2"));
             assert_eq!(
-                res.err().unwrap().to_string(),
-                "Generic error: Unstake amount can't be zero"
+                REWARD_STREAMS.load(deps.as_ref().storage, 2).unwrap(),
+                RewardStream {
+                    denom: "usdc".to_string(),
+                    amount: Uint128::new(500),
+                    released: Uint128::zero(),
+                    start: now,
+                    end: now + DAY,
+                }
             );
         }
-        #[test]
-        fn remove_staker() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
-                },
-            };
-
-            let info = mock_info("creator", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
-
-            let staker_info = mock_info("staker", &[Coin::new(100u128, "watt")]);
 
-            let _res = execute(
+        #[test]
+        fn overlapping_streams_release_linearly_at_every_epoch() {
+            let (mut deps, mut env) = staker();
+            let now = env.block.time.seconds();
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
                 ExecuteMsg::Stake {},
             )
             .unwrap();
-            let amount = Uint128::from(100u128);
-            let _res = execute(
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info.clone(),
-                ExecuteMsg::Unstake { amount },
+                mock_info(
+                    "admin",
+                    &[Coin::new(1000u128, "watt"), Coin::new(500u128, "usdc")],
+                ),
+                ExecuteMsg::DepositRewardStream {
+                    start: now,
+                    end: now + 10 * DAY,
+                },
             )
             .unwrap();
-
-            let staker = STAKERS
-                .may_load(deps.as_ref().storage, staker_info.sender.clone())
-                .unwrap();
-            assert_eq!(staker, None);
-        }
-    }
-    mod deposit_rewards_tests {
-        use super::*;
-        use crate::{instantiate, msg::InstantiateMsg, state::Config};
-        use cosmwasm_std::{
-            testing::{mock_dependencies, mock_env, mock_info},
-            Addr, Coin, Decimal, Uint128,
-        };
-
-        #[test]
-        fn deposit_rewards() {}
-        #[test]
-        fn deposit_rewards_unauthorized() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
-
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(200u128, "watt")]),
+                ExecuteMsg::DepositRewardStream {
+                    start: now + DAY,
+                    end: now + 3 * DAY,
                 },
-            };
+            )
+            .unwrap();
 
-            let info = mock_info("creator", &[Coin::new(100u128, "watt")]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+            // A tenth of the first streams is released after a day, the second has not started
+            env.block.time = env.block.time.plus_seconds(DAY);
+            assert_eq!(
+                attribute(&new_epoch(&mut deps, &env), "streamed"),
+                "100watt,50usdc"
+            );
+            assert_eq!(
+                REWARD_STREAMS
+                    .load(deps.as_ref().storage, 1)
+                    .unwrap()
+                    .released,
+                Uint128::new(100)
+            );
 
-            let deposit_amount = Uint128::from(574u128);
-            let funds = Coin {
-                denom: "watt".to_string(),
-                amount: deposit_amount,
-            };
-            let info = mock_info("random", &[funds]);
+            // The second stream ended and is released in full
+            env.block.time = env.block.time.plus_seconds(4 * DAY);
+            assert_eq!(
+                attribute(&new_epoch(&mut deps, &env), "streamed"),
+                "600watt,200usdc"
+            );
+            assert!(!REWARD_STREAMS.has(deps.as_ref().storage, 3));
 
             let res = execute(
                 deps.as_mut(),
                 env.clone(),
-                info.clone(),
-                ExecuteMsg::DepositRewards {},
+                mock_info("addr1", &[]),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "addr1".to_string(),
+                    amount: vec![Coin::new(665u128, "watt"), Coin::new(237u128, "usdc")],
+                })
             );
 
+            // Streams released in full are removed
+            env.block.time = env.block.time.plus_seconds(5 * DAY);
             assert_eq!(
-                res.err().unwrap().to_string(),
-                "Generic error: Unauthorized"
+                attribute(&new_epoch(&mut deps, &env), "streamed"),
+                "500watt,250usdc"
             );
-        }
-
-        #[test]
-        fn deposit_rewards_zero_amount() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
-
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
-                },
-            };
-
-            let info = mock_info("admin", &[Coin::new(0u128, "watt")]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+            assert!(REWARD_STREAMS.is_empty(deps.as_ref().storage));
+        }
 
-            let res = execute(
+        #[test]
+        fn streams_wait_while_no_interest_accrues() {
+            let (mut deps, mut env) = staker();
+            let now = env.block.time.seconds();
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                info.clone(),
-                ExecuteMsg::DepositRewards {},
-            );
+                mock_info("admin", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::DepositRewardStream {
+                    start: now,
+                    end: now + 10 * DAY,
+                },
+            )
+            .unwrap();
 
+            env.block.time = env.block.time.plus_seconds(DAY);
+            assert_eq!(attribute(&new_epoch(&mut deps, &env), "streamed"), "");
             assert_eq!(
-                res.err().unwrap().to_string(),
-                "Generic error: Deposit amount can't be zero"
+                REWARD_STREAMS
+                    .load(deps.as_ref().storage, 1)
+                    .unwrap()
+                    .released,
+                Uint128::zero()
             );
         }
     }
 
-    mod claim_rewards_test {
+    mod reward_expiry_test {
         use super::*;
-        use crate::{
-            helpers::calculate_interest_after_epoch, instantiate, msg::InstantiateMsg,
-            state::Config,
-        };
+        use crate::state::{Config, RewardExpiry};
         use cosmwasm_std::{
-            testing::{mock_dependencies, mock_env, mock_info},
-            Addr, BankMsg, Coin, CosmosMsg, Decimal, Uint128,
+            testing::{mock_info, MockApi, MockQuerier, MockStorage},
+            Addr, Coin, CosmosMsg, OwnedDeps, Uint128,
         };
 
-        #[test]
-        fn claim_rewards() {
-            let mut deps = mock_dependencies();
-            let mut env = mock_env();
+        const DAY: u64 = 86400;
 
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
-                },
-            };
+        fn staker(
+            reward_expiry: Option<RewardExpiry>,
+        ) -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env) {
+            instantiate_with(Config {
+                reward_expiry,
+                ..default_config()
+            })
+        }
 
-            let info = mock_info("admin", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        fn two_epoch_expiry() -> Option<RewardExpiry> {
+            Some(RewardExpiry {
+                epochs: 2,
+                treasury: Addr::unchecked("treasury"),
+            })
+        }
 
-            let staker_info1 = mock_info("addr1", &[Coin::new(100u128, "watt")]);
-            let staker_info2 = mock_info("addr2", &[Coin::new(200u128, "watt")]);
-            let staker_info3 = mock_info("addr3", &[Coin::new(300u128, "watt")]);
-            let deposit_amount = Uint128::from(574u128);
+        /// Starts the epoch a day later
+        fn next_epoch(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, env: &mut Env) {
+            env.block.time = env.block.time.plus_seconds(DAY);
+            new_epoch(deps, env);
+        }
 
+        fn sweep(
+            deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+            env: &Env,
+            limit: Option<u32>,
+        ) -> Response<TokenFactoryMsg> {
             execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info1.clone(),
-                ExecuteMsg::Stake {},
+                mock_info("anyone", &[]),
+                ExecuteMsg::SweepExpired { limit },
             )
-            .unwrap();
+            .unwrap()
+        }
+
+        #[test]
+        fn sweep_requires_reward_expiry() {
+            let (mut deps, env) = staker(None);
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("anyone", &[]),
+                ExecuteMsg::SweepExpired { limit: None },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::RewardsDoNotExpire {});
+
+            let update = |epochs| ExecuteMsg::UpdateConfig {
+                rewards_percentage: None,
+                epoch_length: None,
+                unbonding_period: None,
+                reward_denoms: None,
+                lock_tiers: None,
+                early_unstake_fee: None,
+                loyalty_tiers: None,
+                minter_address: None,
+                cap_interest_to_reserve: None,
+                reward_expiry: Some(Some(RewardExpiry {
+                    epochs,
+                    treasury: Addr::unchecked("treasury"),
+                })),
+                guardian: None,
+                wattpeak_denom: None,
+                staking_fee_percentage: None,
+                staking_fee_address: None,
+                bridged_denoms: None,
+            };
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                update(0),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::RewardExpiryEpochsZero {});
+            execute(deps.as_mut(), env, mock_info("admin", &[]), update(2)).unwrap();
+        }
+
+        #[test]
+        fn unclaimed_rewards_expire_to_the_treasury() {
+            let (mut deps, mut env) = staker(two_epoch_expiry());
+            for address in ["addr1", "addr2"] {
+                execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info(address, &[Coin::new(1000u128, "watt")]),
+                    ExecuteMsg::Stake {},
+                )
+                .unwrap();
+            }
+            next_epoch(&mut deps, &mut env);
             execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info2.clone(),
-                ExecuteMsg::Stake {},
+                mock_info("admin", &[Coin::new(100u128, "watt")]),
+                ExecuteMsg::DepositRewards {},
             )
             .unwrap();
+            // addr2 leaves with its rewards unclaimed
             execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info3.clone(),
-                ExecuteMsg::Stake {},
+                mock_info("addr2", &[]),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::new(1000),
+                    denom: None,
+                },
             )
             .unwrap();
 
-            calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
+            // The rewards were settled in epoch 2 and can be claimed through epoch 4
+            let res = sweep(&mut deps, &env, None);
+            assert_eq!(attribute(&res, "stakers"), "2");
+            assert_eq!(attribute(&res, "swept"), "");
+            next_epoch(&mut deps, &mut env);
+            next_epoch(&mut deps, &mut env);
+            let res = sweep(&mut deps, &env, None);
+            assert_eq!(attribute(&res, "swept"), "");
+            assert!(res.messages.is_empty());
+
+            next_epoch(&mut deps, &mut env);
+            let res = sweep(&mut deps, &env, None);
+            assert_eq!(attribute(&res, "swept"), "99watt");
+            assert_eq!(attribute(&res, "removed"), "1");
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: vec![Coin::new(99u128, "watt")],
+                })
+            );
 
-            env = mock_env();
-            let funds = Coin {
-                denom: "watt".to_string(),
-                amount: deposit_amount,
-            };
+            // The staker that left is removed, the other one keeps its stake
+            assert!(!stakers().has(deps.as_ref().storage, Addr::unchecked("addr2")));
+            let staker = stakers()
+                .load(deps.as_ref().storage, Addr::unchecked("addr1"))
+                .unwrap();
+            assert_eq!(staker.wattpeak_staked, Uint128::new(1000));
+            assert!(staker.claimable_rewards.is_zero());
+            assert!(staker.reward_buckets.is_empty());
+        }
 
-            let info = mock_info("admin", &[funds.clone()]);
-            let res = execute(
+        #[test]
+        fn sweeps_continue_after_the_last_batch_and_transfers_keep_expiry() {
+            let (mut deps, mut env) = staker(two_epoch_expiry());
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                info.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            next_epoch(&mut deps, &mut env);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(100u128, "watt")]),
                 ExecuteMsg::DepositRewards {},
             )
             .unwrap();
+            sweep(&mut deps, &env, None);
 
-            assert_eq!(res.messages.len(), 0);
-
-            let claimer = mock_info("addr1", &[]);
-            let res = execute(
+            // The rewards moved in a later epoch still expire with the epoch they were settled in
+            next_epoch(&mut deps, &mut env);
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                claimer.clone(),
-                ExecuteMsg::ClaimReward {},
+                mock_info("addr1", &[]),
+                ExecuteMsg::TransferStake {
+                    recipient: "addr2".to_string(),
+                    amount: Uint128::new(500),
+                    include_rewards: true,
+                },
             )
             .unwrap();
-            println!("{:?}", res);
-            assert_eq!(res.messages.len(), 2);
-            assert_eq!(
-                res.messages[0].msg,
-                CosmosMsg::Bank(BankMsg::Send {
-                    to_address: staker_info1.sender.to_string(),
-                    amount: vec![Coin {
-                        denom: "watt".to_string(),
-                        amount: Uint128::from(90u128),
-                    }],
-                })
-            );
+            let recipient = stakers()
+                .load(deps.as_ref().storage, Addr::unchecked("addr2"))
+                .unwrap();
+            assert_eq!(recipient.reward_buckets.len(), 1);
+            assert_eq!(recipient.reward_buckets[0].epoch, 2);
+
+            next_epoch(&mut deps, &mut env);
+            next_epoch(&mut deps, &mut env);
+            let res = sweep(&mut deps, &env, Some(1));
+            assert_eq!(attribute(&res, "stakers"), "1");
+            assert_eq!(attribute(&res, "swept"), "");
             assert_eq!(
-                res.messages[1].msg,
-                CosmosMsg::Bank(BankMsg::Send {
-                    to_address: "staking_fee_address".to_string(),
-                    amount: vec![Coin {
-                        denom: "watt".to_string(),
-                        amount: Uint128::from(4u128),
-                    }],
-                })
+                SWEEP_CURSOR.load(deps.as_ref().storage).unwrap(),
+                Addr::unchecked("addr1")
             );
+            let res = sweep(&mut deps, &env, Some(1));
+            assert_eq!(attribute(&res, "swept"), "99watt");
+            let res = sweep(&mut deps, &env, Some(1));
+            assert_eq!(attribute(&res, "stakers"), "0");
+            assert!(SWEEP_CURSOR
+                .may_load(deps.as_ref().storage)
+                .unwrap()
+                .is_none());
+        }
+    }
 
-            let staker = STAKERS
-                .load(deps.as_ref().storage, staker_info1.sender)
-                .unwrap();
-            assert_eq!(staker.claimable_rewards, Decimal::zero());
+    mod pause_test {
+        use super::*;
+        use crate::{
+            helpers::interest_index_at,
+            state::{Config, LockTier},
+        };
+        use cosmwasm_std::{
+            testing::{mock_info, MockApi, MockQuerier, MockStorage},
+            Addr, Coin, CosmosMsg, Decimal, OwnedDeps, Uint128,
+        };
+
+        const DAY: u64 = 86400;
+
+        fn staker() -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env) {
+            instantiate_with(Config {
+                unbonding_period: 7 * DAY,
+                lock_tiers: vec![LockTier {
+                    duration: 90 * DAY,
+                    multiplier: Decimal::percent(150),
+                    early_unlock_penalty: Decimal::percent(10),
+                }],
+                guardian: Some(Addr::unchecked("guardian")),
+                ..default_config()
+            })
         }
-        #[test]
-        fn claim_rewards_multiple_epochs() {
-            let mut deps = mock_dependencies();
-            let mut env = mock_env();
 
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
+        fn set_pause(
+            deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+            env: &Env,
+            sender: &str,
+            paused: bool,
+        ) -> Result<Response<TokenFactoryMsg>, ContractError> {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(sender, &[]),
+                ExecuteMsg::SetPause {
+                    staking: Some(paused),
+                    claiming: Some(paused),
+                    epochs: Some(paused),
                 },
-            };
+            )
+        }
 
-            let info = mock_info("admin", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        #[test]
+        fn paused_messages_return_typed_errors() {
+            let (mut deps, mut env) = staker();
+            let err = set_pause(&mut deps, &env, "addr1", true).unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
+            set_pause(&mut deps, &env, "guardian", true).unwrap();
 
-            let staker_info1 = mock_info("addr1", &[Coin::new(100u128, "watt")]);
-            let staker_info2 = mock_info("addr2", &[Coin::new(200u128, "watt")]);
-            let staker_info3 = mock_info("addr3", &[Coin::new(300u128, "watt")]);
-            let deposit_amount = Uint128::from(574u128);
+            let stake = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::Stake {},
+            );
+            assert_eq!(stake.unwrap_err(), ContractError::StakingPaused {});
+            let claim = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::ClaimReward {
+                    recipient: None,
+                    ibc: None,
+                },
+            );
+            assert_eq!(claim.unwrap_err(), ContractError::ClaimingPaused {});
+            env.block.time = env.block.time.plus_seconds(DAY);
+            let new_epoch = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("anyone", &[]),
+                ExecuteMsg::NewEpoch {},
+            );
+            assert_eq!(new_epoch.unwrap_err(), ContractError::EpochsPaused {});
+            let withdraw = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::EmergencyWithdraw { project_id: None },
+            );
+            assert_eq!(withdraw.unwrap_err(), ContractError::NotInEmergencyMode {});
 
+            // The admin unpauses
+            set_pause(&mut deps, &env, "admin", false).unwrap();
             execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info1.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
                 ExecuteMsg::Stake {},
             )
             .unwrap();
             execute(
                 deps.as_mut(),
-                env.clone(),
-                staker_info2.clone(),
-                ExecuteMsg::Stake {},
+                env,
+                mock_info("anyone", &[]),
+                ExecuteMsg::NewEpoch {},
             )
             .unwrap();
+        }
+
+        #[test]
+        fn emergency_mode_freezes_rewards_and_returns_principal() {
+            let (mut deps, mut env) = staker();
             execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info3.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
                 ExecuteMsg::Stake {},
             )
             .unwrap();
-
-            calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
-            calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
-            calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
-            calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
-            calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
-
-            env = mock_env();
-            let funds = Coin {
-                denom: "watt".to_string(),
-                amount: deposit_amount,
-            };
-
-            let info = mock_info("admin", &[funds.clone()]);
-            let res = execute(
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                info.clone(),
-                ExecuteMsg::DepositRewards {},
+                mock_info("addr1", &[Coin::new(500u128, "watt")]),
+                ExecuteMsg::Lock { duration: 90 * DAY },
             )
             .unwrap();
-
-            assert_eq!(res.messages.len(), 0);
-
-            let claimer = mock_info("addr1", &[]);
-            let res = execute(
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                claimer.clone(),
-                ExecuteMsg::ClaimReward {},
+                mock_info("addr1", &[]),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::new(200),
+                    denom: None,
+                },
             )
             .unwrap();
 
-            let claimer2 = mock_info("addr2", &[]);
-            let res2 = execute(
+            env.block.time = env.block.time.plus_seconds(DAY);
+            let err = execute(
                 deps.as_mut(),
                 env.clone(),
-                claimer2.clone(),
-                ExecuteMsg::ClaimReward {},
+                mock_info("addr1", &[]),
+                ExecuteMsg::EnableEmergencyMode {},
             )
-            .unwrap();
-
-            let claimer3 = mock_info("addr3", &[]);
-            let res3 = execute(
+            .unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                claimer3.clone(),
-                ExecuteMsg::ClaimReward {},
+                mock_info("guardian", &[]),
+                ExecuteMsg::EnableEmergencyMode {},
             )
             .unwrap();
+            let frozen_index =
+                interest_index_at(deps.as_ref().storage, env.block.time.seconds()).unwrap();
 
-            assert_eq!(res.messages.len(), 2);
-            assert_eq!(
-                res.messages[0].msg,
-                CosmosMsg::Bank(BankMsg::Send {
-                    to_address: staker_info1.sender.to_string(),
-                    amount: vec![Coin {
-                        denom: "watt".to_string(),
-                        amount: Uint128::from(90u128),
-                    }],
-                })
-            );
+            env.block.time = env.block.time.plus_seconds(DAY);
             assert_eq!(
-                res.messages[1].msg,
-                CosmosMsg::Bank(BankMsg::Send {
-                    to_address: "staking_fee_address".to_string(),
-                    amount: vec![Coin {
-                        denom: "watt".to_string(),
-                        amount: Uint128::from(4u128),
-                    }],
-                })
+                interest_index_at(deps.as_ref().storage, env.block.time.seconds()).unwrap(),
+                frozen_index
             );
+            for msg in [
+                ExecuteMsg::Stake {},
+                ExecuteMsg::NewEpoch {},
+                ExecuteMsg::Restake {},
+                ExecuteMsg::Unstake {
+                    amount: Uint128::new(100),
+                    denom: None,
+                },
+            ] {
+                let err = execute(deps.as_mut(), env.clone(), mock_info("addr1", &[]), msg);
+                assert_eq!(err.unwrap_err(), ContractError::EmergencyMode {});
+            }
 
-            assert_eq!(res2.messages.len(), 2);
-            assert_eq!(
-                res2.messages[0].msg,
-                CosmosMsg::Bank(BankMsg::Send {
-                    to_address: staker_info2.sender.to_string(),
-                    amount: vec![Coin {
-                        denom: "watt".to_string(),
-                        amount: Uint128::from(181u128),
-                    }],
-                })
-            );
+            // The stake, the locked stake and the unbonding entry are returned in full
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[]),
+                ExecuteMsg::EmergencyWithdraw { project_id: None },
+            )
+            .unwrap();
             assert_eq!(
-                res2.messages[1].msg,
+                res.messages[0].msg,
                 CosmosMsg::Bank(BankMsg::Send {
-                    to_address: "staking_fee_address".to_string(),
-                    amount: vec![Coin {
-                        denom: "watt".to_string(),
-                        amount: Uint128::from(9u128),
-                    }],
+                    to_address: "addr1".to_string(),
+                    amount: vec![Coin::new(1500u128, "watt")],
                 })
             );
+            let staker = stakers()
+                .load(deps.as_ref().storage, Addr::unchecked("addr1"))
+                .unwrap();
+            assert!(staker.wattpeak_staked.is_zero());
+            assert!(staker.locked_wattpeak.is_zero());
+            assert!(LOCKS.is_empty(deps.as_ref().storage));
+            assert!(UNBONDINGS.is_empty(deps.as_ref().storage));
+            assert!(TOTAL_WATTPEAK_STAKED
+                .load(deps.as_ref().storage)
+                .unwrap()
+                .is_zero());
+
+            let err = execute(
+                deps.as_mut(),
+                env,
+                mock_info("addr1", &[]),
+                ExecuteMsg::EmergencyWithdraw { project_id: None },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::NothingToWithdraw {});
+        }
 
-            assert_eq!(res3.messages.len(), 2);
-            assert_eq!(
-                res3.messages[0].msg,
-                CosmosMsg::Bank(BankMsg::Send {
-                    to_address: staker_info3.sender.to_string(),
-                    amount: vec![Coin {
-                        denom: "watt".to_string(),
-                        amount: Uint128::from(272u128),
-                    }],
-                })
-            );
+        #[test]
+        fn liquid_unstake_in_emergency_returns_principal_only() {
+            let (mut deps, mut env) = staker();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                ExecuteMsg::CreateLiquidDenom {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::LiquidStake {},
+            )
+            .unwrap();
 
-            assert_eq!(
-                res3.messages[1].msg,
-                CosmosMsg::Bank(BankMsg::Send {
-                    to_address: "staking_fee_address".to_string(),
-                    amount: vec![Coin {
-                        denom: "watt".to_string(),
-                        amount: Uint128::from(14u128),
-                    }],
-                })
-            );
+            // The pool earns the whole deposit, which stays unclaimed
+            env.block.time = env.block.time.plus_seconds(DAY);
+            new_epoch(&mut deps, &env);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(1000u128, "watt")]),
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("guardian", &[]),
+                ExecuteMsg::EnableEmergencyMode {},
+            )
+            .unwrap();
 
-            let staker = STAKERS
-                .load(deps.as_ref().storage, staker_info1.sender)
+            // The rewards are not restaked, stWP is redeemed for the principal without a fee
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(
+                    "addr1",
+                    &[Coin::new(1000u128, "factory/cosmos2contract/ustwattpeak")],
+                ),
+                ExecuteMsg::LiquidUnstake {},
+            )
+            .unwrap();
+            assert_eq!(attribute(&res, "amount"), "1000");
+            assert_eq!(res.messages.len(), 1);
+            let entry = UNBONDINGS
+                .load(deps.as_ref().storage, (Addr::unchecked("addr1"), 1))
                 .unwrap();
-            assert_eq!(staker.claimable_rewards, Decimal::zero());
+            assert_eq!(entry.amount, Uint128::new(1000));
+            let pool = stakers()
+                .load(deps.as_ref().storage, env.contract.address)
+                .unwrap();
+            assert!(pool.wattpeak_staked.is_zero());
+            assert!(!pool.claimable_rewards.is_zero());
         }
+    }
 
-        #[test]
-        fn claim_when_staker_doesnt_exist() {
-            let mut deps = mock_dependencies();
-            let env = mock_env();
+    mod admin_test {
+        use super::*;
+        use crate::state::MAX_STAKING_FEE_PERCENTAGE;
+        use cosmwasm_std::{
+            attr,
+            testing::{mock_info, MockApi, MockQuerier, MockStorage},
+            Addr, Binary, Coin, ContractResult, Decimal, OwnedDeps, SystemError, SystemResult,
+            Uint128, WasmQuery,
+        };
 
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
+        fn staker() -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env) {
+            instantiate_with(default_config())
+        }
+
+        fn update_fee_and_denom(
+            deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+            env: &Env,
+            wattpeak_denom: Option<&str>,
+            staking_fee_percentage: Option<Decimal>,
+            staking_fee_address: Option<&str>,
+        ) -> Result<Response<TokenFactoryMsg>, ContractError> {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                ExecuteMsg::UpdateConfig {
+                    rewards_percentage: None,
+                    epoch_length: None,
+                    unbonding_period: None,
+                    reward_denoms: None,
+                    lock_tiers: None,
+                    early_unstake_fee: None,
+                    loyalty_tiers: None,
+                    minter_address: None,
+                    cap_interest_to_reserve: None,
+                    reward_expiry: None,
+                    guardian: None,
+                    wattpeak_denom: wattpeak_denom.map(str::to_string),
+                    staking_fee_percentage,
+                    staking_fee_address: staking_fee_address.map(str::to_string),
+                    bridged_denoms: None,
                 },
-            };
+            )
+        }
 
-            let info = mock_info("creator", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+        #[test]
+        fn update_fee_and_denom_with_validation() {
+            let (mut deps, env) = staker();
 
-            let claimer = mock_info("addr1", &[]);
-            let res = execute(
+            let err = update_fee_and_denom(&mut deps, &env, None, Some(Decimal::percent(11)), None)
+                .unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::StakingFeeTooHigh {
+                    max: MAX_STAKING_FEE_PERCENTAGE
+                }
+            );
+            let err = update_fee_and_denom(&mut deps, &env, None, None, Some("x")).unwrap_err();
+            assert!(matches!(err, ContractError::Std(_)));
+            let err = update_fee_and_denom(&mut deps, &env, Some(""), None, None).unwrap_err();
+            assert_eq!(err, ContractError::WattpeakDenomEmpty {});
+
+            // Only the changed fields are listed in the event
+            let res = update_fee_and_denom(
+                &mut deps,
+                &env,
+                Some("uwatt"),
+                Some(Decimal::percent(10)),
+                Some("treasury"),
+            )
+            .unwrap();
+            assert_eq!(res.events.len(), 1);
+            assert_eq!(res.events[0].ty, "config_change");
+            assert_eq!(
+                res.events[0].attributes,
+                vec![
+                    attr("old_wattpeak_denom", "\"watt\""),
+                    attr("new_wattpeak_denom", "\"uwatt\""),
+                    attr("old_staking_fee_percentage", "\"0.05\""),
+                    attr("new_staking_fee_percentage", "\"0.1\""),
+                    attr("old_staking_fee_address", "\"staking_fee_address\""),
+                    attr("new_staking_fee_address", "\"treasury\""),
+                ]
+            );
+            let config = CONFIG.load(deps.as_ref().storage).unwrap();
+            assert_eq!(config.wattpeak_denom, "uwatt");
+            assert_eq!(config.staking_fee_percentage, Decimal::percent(10));
+            assert_eq!(config.staking_fee_address, Addr::unchecked("treasury"));
+
+            // The denom is locked in once wattpeak is staked
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                claimer.clone(),
-                ExecuteMsg::ClaimReward {},
-            );
+                mock_info("alice", &[Coin::new(1000, "uwatt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+            let err = update_fee_and_denom(&mut deps, &env, Some("watt"), None, None).unwrap_err();
+            assert_eq!(err, ContractError::WattpeakDenomInUse {});
+            let res = update_fee_and_denom(&mut deps, &env, Some("uwatt"), None, None).unwrap();
+            assert!(res.events[0].attributes.is_empty());
+        }
+
+        #[test]
+        fn wattpeak_denom_is_locked_while_the_contract_holds_it() {
+            // A staker with one of the ways the contract can hold wattpeak without anything staked
+            let holding = |hold: fn(&mut OwnedDeps<MockStorage, MockApi, MockQuerier>, &Env)| {
+                let (mut deps, env) = instantiate_with(Config {
+                    unbonding_period: 100,
+                    ..default_config()
+                });
+                deps.querier.update_wasm(|query| match query {
+                    WasmQuery::Raw { contract_addr, key }
+                        if contract_addr == "minter" && key.as_slice() == b"token_full_denom" =>
+                    {
+                        SystemResult::Ok(ContractResult::Ok(Binary::from(br#""uwatt""#.as_slice())))
+                    }
+                    _ => SystemResult::Err(SystemError::Unknown {}),
+                });
+                hold(&mut deps, &env);
+                (deps, env)
+            };
+            let link_minter = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                               env: &Env| {
+                execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info("admin", &[]),
+                    ExecuteMsg::UpdateConfig {
+                        rewards_percentage: None,
+                        epoch_length: None,
+                        unbonding_period: None,
+                        reward_denoms: None,
+                        lock_tiers: None,
+                        early_unstake_fee: None,
+                        loyalty_tiers: None,
+                        minter_address: Some(Some("minter".to_string())),
+                        cap_interest_to_reserve: None,
+                        reward_expiry: None,
+                        guardian: None,
+                        wattpeak_denom: None,
+                        staking_fee_percentage: None,
+                        staking_fee_address: None,
+                        bridged_denoms: None,
+                    },
+                )
+            };
 
+            let reserve: fn(&mut OwnedDeps<MockStorage, MockApi, MockQuerier>, &Env) =
+                |deps, env| {
+                    execute(
+                        deps.as_mut(),
+                        env.clone(),
+                        mock_info("admin", &[Coin::new(100, "watt")]),
+                        ExecuteMsg::FundReserve {},
+                    )
+                    .unwrap();
+                };
+            let unbonding: fn(&mut OwnedDeps<MockStorage, MockApi, MockQuerier>, &Env) =
+                |deps, env| {
+                    execute(
+                        deps.as_mut(),
+                        env.clone(),
+                        mock_info("alice", &[Coin::new(100, "watt")]),
+                        ExecuteMsg::Stake {},
+                    )
+                    .unwrap();
+                    execute(
+                        deps.as_mut(),
+                        env.clone(),
+                        mock_info("alice", &[]),
+                        ExecuteMsg::Unstake {
+                            amount: Uint128::new(100),
+                            denom: None,
+                        },
+                    )
+                    .unwrap();
+                    assert!(TOTAL_WATTPEAK_STAKED
+                        .load(deps.as_ref().storage)
+                        .unwrap()
+                        .is_zero());
+                };
+            let stream: fn(&mut OwnedDeps<MockStorage, MockApi, MockQuerier>, &Env) =
+                |deps, env| {
+                    let start = env.block.time.seconds() + 100;
+                    execute(
+                        deps.as_mut(),
+                        env.clone(),
+                        mock_info("admin", &[Coin::new(100, "watt")]),
+                        ExecuteMsg::DepositRewardStream {
+                            start,
+                            end: start + 100,
+                        },
+                    )
+                    .unwrap();
+                };
+            for hold in [reserve, unbonding, stream] {
+                let (mut deps, env) = holding(hold);
+                let err =
+                    update_fee_and_denom(&mut deps, &env, Some("uwatt"), None, None).unwrap_err();
+                assert_eq!(err, ContractError::WattpeakDenomInUse {});
+                let err = link_minter(&mut deps, &env).unwrap_err();
+                assert_eq!(err, ContractError::WattpeakDenomInUse {});
+            }
+
+            // With nothing held the minter can switch the denom
+            let (mut deps, env) = holding(|_, _| {});
+            link_minter(&mut deps, &env).unwrap();
             assert_eq!(
-                res.err().unwrap().to_string(),
-                "wattpeak_staker::state::Staker not found"
+                CONFIG.load(deps.as_ref().storage).unwrap().wattpeak_denom,
+                "uwatt"
             );
         }
-        #[test]
-        fn try_to_claim_twice_in_a_row() {
-            let mut deps = mock_dependencies();
-            let mut env = mock_env();
 
-            let msg = InstantiateMsg {
-                config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 86400,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
-                },
+        #[test]
+        fn admin_is_handed_over_in_two_steps() {
+            let (mut deps, env) = staker();
+            let propose = |admin: &str| ExecuteMsg::ProposeAdmin {
+                admin: admin.to_string(),
             };
 
-            let info = mock_info("admin", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
-
-            let staker_info1 = mock_info("addr1", &[Coin::new(100u128, "watt")]);
-            let staker_info2 = mock_info("addr2", &[Coin::new(200u128, "watt")]);
-            let staker_info3 = mock_info("addr3", &[Coin::new(300u128, "watt")]);
-            let deposit_amount = Uint128::from(574u128);
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("alice", &[]),
+                propose("alice"),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                ExecuteMsg::AcceptAdmin {},
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::NoPendingAdmin {});
 
+            // A cancelled proposal can no longer be accepted
             execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info1.clone(),
-                ExecuteMsg::Stake {},
+                mock_info("admin", &[]),
+                propose("new_admin"),
             )
             .unwrap();
             execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info2.clone(),
-                ExecuteMsg::Stake {},
+                mock_info("admin", &[]),
+                ExecuteMsg::CancelAdminProposal {},
             )
             .unwrap();
-            execute(
+            let err = execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info3.clone(),
-                ExecuteMsg::Stake {},
+                mock_info("new_admin", &[]),
+                ExecuteMsg::AcceptAdmin {},
             )
-            .unwrap();
-
-            calculate_interest_after_epoch(deps.as_mut(), info).unwrap();
-
-            env = mock_env();
-            let funds = Coin {
-                denom: "watt".to_string(),
-                amount: deposit_amount,
-            };
+            .unwrap_err();
+            assert_eq!(err, ContractError::NoPendingAdmin {});
 
-            let info = mock_info("admin", &[funds.clone()]);
-            let res = execute(
+            // The admin only changes once the proposed address accepts
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                info.clone(),
-                ExecuteMsg::DepositRewards {},
+                mock_info("admin", &[]),
+                propose("new_admin"),
             )
             .unwrap();
-
-            assert_eq!(res.messages.len(), 0);
-
-            let claimer = mock_info("addr1", &[]);
+            assert_eq!(
+                CONFIG.load(deps.as_ref().storage).unwrap().admin,
+                Addr::unchecked("admin")
+            );
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("alice", &[]),
+                ExecuteMsg::AcceptAdmin {},
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
             let res = execute(
                 deps.as_mut(),
                 env.clone(),
-                claimer.clone(),
-                ExecuteMsg::ClaimReward {},
+                mock_info("new_admin", &[]),
+                ExecuteMsg::AcceptAdmin {},
             )
             .unwrap();
-
-            assert_eq!(res.messages.len(), 2);
             assert_eq!(
-                res.messages[0].msg,
-                CosmosMsg::Bank(BankMsg::Send {
-                    to_address: staker_info1.sender.to_string(),
-                    amount: vec![Coin {
-                        denom: "watt".to_string(),
-                        amount: Uint128::from(90u128),
-                    }],
-                })
+                res.events[0].attributes,
+                vec![
+                    attr("old_admin", "\"admin\""),
+                    attr("new_admin", "\"new_admin\""),
+                ]
             );
-
             assert_eq!(
-                res.messages[1].msg,
-                CosmosMsg::Bank(BankMsg::Send {
-                    to_address: "staking_fee_address".to_string(),
-                    amount: vec![Coin {
-                        denom: "watt".to_string(),
-                        amount: Uint128::from(4u128),
-                    }],
-                })
+                CONFIG.load(deps.as_ref().storage).unwrap().admin,
+                Addr::unchecked("new_admin")
             );
+            assert_eq!(PENDING_ADMIN.may_load(deps.as_ref().storage).unwrap(), None);
 
-            let staker = STAKERS
-                .load(deps.as_ref().storage, staker_info1.sender)
-                .unwrap();
-            assert_eq!(staker.claimable_rewards, Decimal::zero());
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                propose("admin"),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
+        }
+    }
 
-            let res = execute(
+    mod bridged_test {
+        use super::*;
+        use crate::{instantiate, msg::InstantiateMsg, state::Config};
+        use cosmwasm_std::{
+            testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage},
+            Addr, Binary, Coin, ContractResult, CosmosMsg, Decimal, OwnedDeps, SystemResult,
+            Uint128, WasmQuery,
+        };
+
+        const DAY: u64 = 86400;
+        const BRIDGED: &str = "ibc/WATTPEAK";
+
+        fn config() -> Config {
+            Config {
+                unbonding_period: 7 * DAY,
+                lock_tiers: vec![LockTier {
+                    duration: 90 * DAY,
+                    multiplier: Decimal::percent(150),
+                    early_unlock_penalty: Decimal::percent(10),
+                }],
+                bridged_denoms: vec![BRIDGED.to_string()],
+                ..default_config()
+            }
+        }
+
+        fn staker() -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env) {
+            instantiate_with(config())
+        }
+
+        fn unstake(
+            deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+            env: &Env,
+            amount: u128,
+            denom: Option<&str>,
+        ) -> Result<Response<TokenFactoryMsg>, ContractError> {
+            execute(
                 deps.as_mut(),
                 env.clone(),
-                claimer.clone(),
-                ExecuteMsg::ClaimReward {},
-            );
-            assert_eq!(
-                res.err().unwrap().to_string(),
-                "Generic error: No rewards to claim"
-            );
+                mock_info("alice", &[]),
+                ExecuteMsg::Unstake {
+                    amount: Uint128::new(amount),
+                    denom: denom.map(str::to_string),
+                },
+            )
         }
+
         #[test]
-        fn one_staker_three_epochs_deposit_and_claim() {
+        fn wattpeak_denom_is_read_from_the_minter() {
             let mut deps = mock_dependencies();
-            let mut env = mock_env();
-
+            deps.querier.update_wasm(|query| match query {
+                WasmQuery::Raw { contract_addr, key }
+                    if contract_addr == "minter" && key.as_slice() == b"token_full_denom" =>
+                {
+                    SystemResult::Ok(ContractResult::Ok(Binary::from(
+                        br#""factory/minter/uwattpeakt""#.as_slice(),
+                    )))
+                }
+                _ => SystemResult::Ok(ContractResult::Ok(Binary::default())),
+            });
+            let env = mock_env();
             let msg = InstantiateMsg {
                 config: Config {
-                    admin: Addr::unchecked("admin"),
-                    rewards_percentage: Decimal::percent(5),
-                    epoch_length: 1,
-                    wattpeak_denom: "watt".to_string(),
-                    staking_fee_address: Addr::unchecked("staking_fee_address"),
-                    staking_fee_percentage: Decimal::percent(5),
+                    minter_address: Some(Addr::unchecked("minter")),
+                    ..config()
                 },
             };
+            instantiate(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+            assert_eq!(
+                CONFIG.load(deps.as_ref().storage).unwrap().wattpeak_denom,
+                "factory/minter/uwattpeakt"
+            );
 
-            let info = mock_info("admin", &[]);
-            let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+            let update = |minter_address: Option<&str>, wattpeak_denom: Option<&str>| {
+                ExecuteMsg::UpdateConfig {
+                    rewards_percentage: None,
+                    epoch_length: None,
+                    unbonding_period: None,
+                    reward_denoms: None,
+                    lock_tiers: None,
+                    early_unstake_fee: None,
+                    loyalty_tiers: None,
+                    minter_address: minter_address.map(|address| Some(address.to_string())),
+                    cap_interest_to_reserve: None,
+                    reward_expiry: None,
+                    guardian: None,
+                    wattpeak_denom: wattpeak_denom.map(str::to_string),
+                    staking_fee_percentage: None,
+                    staking_fee_address: None,
+                    bridged_denoms: None,
+                }
+            };
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                update(None, Some("watt")),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::WattpeakDenomSetByMinter {});
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                update(Some("other_minter"), None),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::MinterDenomNotFound {});
+        }
 
-            let staker_info1 = mock_info("addr1", &[Coin::new(100u128, "watt")]);
-            let deposit_amount = Uint128::from(5000000u128);
+        #[test]
+        fn validate_config_bridged_denoms() {
+            let deps = mock_dependencies();
+            let mut config = config();
+            config.bridged_denoms.push("watt".to_string());
+            assert_eq!(
+                config.validate(deps.as_ref()).unwrap_err(),
+                ContractError::InvalidBridgedDenom {
+                    denom: "watt".to_string()
+                }
+            );
 
-            execute(
+            let mut config = self::config();
+            config.bridged_denoms.push(BRIDGED.to_string());
+            assert_eq!(
+                config.validate(deps.as_ref()).unwrap_err(),
+                ContractError::DuplicateBridgedDenom {
+                    denom: BRIDGED.to_string()
+                }
+            );
+        }
+
+        #[test]
+        fn bridged_stake_is_unstaked_in_its_denom() {
+            let (mut deps, mut env) = staker();
+            for denom in ["watt", BRIDGED] {
+                execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info("alice", &[Coin::new(1000, denom)]),
+                    ExecuteMsg::Stake {},
+                )
+                .unwrap();
+            }
+            let staker = stakers()
+                .load(deps.as_ref().storage, Addr::unchecked("alice"))
+                .unwrap();
+            assert_eq!(staker.wattpeak_staked, Uint128::new(2000));
+
+            // Locks only hold wattpeak_denom
+            let err = execute(
                 deps.as_mut(),
                 env.clone(),
-                staker_info1.clone(),
-                ExecuteMsg::Stake {},
+                mock_info("alice", &[Coin::new(1000, BRIDGED)]),
+                ExecuteMsg::Lock { duration: 90 * DAY },
             )
-            .unwrap();
+            .unwrap_err();
+            assert_eq!(err, ContractError::NoWattpeakStaked {});
 
-            calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
-            calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
-            calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
-            calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
-            calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
-
-            env = mock_env();
-            let funds = Coin {
-                denom: "watt".to_string(),
-                amount: deposit_amount,
-            };
+            // Each denom can only be unstaked up to the stake in it
+            let err = unstake(&mut deps, &env, 1500, None).unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::InsufficientDenomStake {
+                    denom: "watt".to_string()
+                }
+            );
+            let err = unstake(&mut deps, &env, 1500, Some(BRIDGED)).unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::InsufficientDenomStake {
+                    denom: BRIDGED.to_string()
+                }
+            );
 
-            let info = mock_info("admin", &[funds.clone()]);
-            let res = execute(
+            // Only the stake in wattpeak_denom can be transferred
+            let err = execute(
                 deps.as_mut(),
                 env.clone(),
-                info.clone(),
-                ExecuteMsg::DepositRewards {},
+                mock_info("alice", &[]),
+                ExecuteMsg::TransferStake {
+                    recipient: "bob".to_string(),
+                    amount: Uint128::new(1500),
+                    include_rewards: false,
+                },
             )
-            .unwrap();
+            .unwrap_err();
+            assert_eq!(err, ContractError::InsufficientUnlockedStake {});
 
-            assert_eq!(res.messages.len(), 0);
+            unstake(&mut deps, &env, 400, Some(BRIDGED)).unwrap();
+            unstake(&mut deps, &env, 100, None).unwrap();
+            assert_eq!(
+                BRIDGED_STAKES
+                    .load(deps.as_ref().storage, (Addr::unchecked("alice"), BRIDGED))
+                    .unwrap(),
+                Uint128::new(600)
+            );
 
-            let claimer = mock_info("addr1", &[]);
+            // The unbonded entries are paid in the denom they were unstaked in
+            env.block.time = env.block.time.plus_seconds(7 * DAY);
             let res = execute(
                 deps.as_mut(),
                 env.clone(),
-                claimer.clone(),
-                ExecuteMsg::ClaimReward {},
+                mock_info("alice", &[]),
+                ExecuteMsg::WithdrawUnbonded {},
             )
             .unwrap();
-
-            assert_eq!(res.messages.len(), 2);
             assert_eq!(
                 res.messages[0].msg,
                 CosmosMsg::Bank(BankMsg::Send {
-                    to_address: staker_info1.sender.to_string(),
-                    amount: vec![Coin {
-                        denom: "watt".to_string(),
-                        amount: Uint128::from(4750000u128),
-                    }],
+                    to_address: "alice".to_string(),
+                    amount: vec![Coin::new(400, BRIDGED), Coin::new(100, "watt")],
                 })
             );
+
+            // Emergency withdraw pays every denom back
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                ExecuteMsg::EnableEmergencyMode {},
+            )
+            .unwrap();
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("alice", &[]),
+                ExecuteMsg::EmergencyWithdraw { project_id: None },
+            )
+            .unwrap();
             assert_eq!(
-                res.messages[1].msg,
+                res.messages[0].msg,
                 CosmosMsg::Bank(BankMsg::Send {
-                    to_address: "staking_fee_address".to_string(),
-                    amount: vec![Coin {
-                        denom: "watt".to_string(),
-                        amount: Uint128::from(250000u128),
-                    }],
+                    to_address: "alice".to_string(),
+                    amount: vec![Coin::new(600, BRIDGED), Coin::new(900, "watt")],
                 })
             );
+            assert!(BRIDGED_STAKES
+                .may_load(deps.as_ref().storage, (Addr::unchecked("alice"), BRIDGED))
+                .unwrap()
+                .is_none());
+        }
+
+        #[test]
+        fn stake_takes_a_single_denom() {
+            let (mut deps, env) = staker();
+
+            // Coins sent next to the staked one would not be credited
+            for funds in [
+                vec![Coin::new(1000, "watt"), Coin::new(1000, BRIDGED)],
+                vec![Coin::new(1000, BRIDGED), Coin::new(1000, "ibc/OTHER")],
+                vec![Coin::new(1000, "watt"), Coin::new(1000, "uatom")],
+            ] {
+                let err = execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info("alice", &funds),
+                    ExecuteMsg::Stake {},
+                )
+                .unwrap_err();
+                assert_eq!(err, ContractError::MultipleDenomsStaked {});
+            }
+            assert!(stakers()
+                .may_load(deps.as_ref().storage, Addr::unchecked("alice"))
+                .unwrap()
+                .is_none());
+
+            let err = execute(
+                deps.as_mut(),
+                env,
+                mock_info("alice", &[Coin::new(1000, "uatom")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::NoWattpeakStaked {});
         }
     }
 }
@@ -1,92 +1,1279 @@
-use std::str::FromStr;
+use crate::error::ContractError;
+use crate::msg::StakeChangedHookMsg;
+use crate::state::{
+    stakers, DenomReward, DenomRewardIndex, DepositRecord, EpochRecord, LockPosition, RewardBucket,
+    RewardRound, Staker, BRIDGED_STAKES, CONFIG, DEPOSITS, DEPOSIT_COUNT, EMERGENCY_TIME, EPOCHS,
+    EPOCH_COUNT, HOOKS, INTEREST_INDEX, LAST_ACCRUAL_TIME, LAST_EPOCH_TIME, LEGACY_STAKERS,
+    LIQUID_DECIMALS, LIQUID_DESCRIPTION, LIQUID_NAME, LIQUID_SUBDENOM, LIQUID_SYMBOL, LOCKS,
+    LOCK_EXPIRIES, LOYALTY_UPDATES, MINTER_FULL_DENOM_KEY, POOL_STAKES, RATE_SCHEDULE, REWARD_DUST,
+    REWARD_RESERVE, REWARD_ROUND, REWARD_ROUNDS, REWARD_STREAMS, STAKED_BALANCES, STAKER_COUNT,
+    TOTAL_BRIDGED_STAKED, TOTAL_INTEREST_WATTPEAK, TOTAL_LOCK_BOOST, TOTAL_LOYALTY_BOOST,
+    TOTAL_WATTPEAK_STAKED, UNBONDINGS,
+};
+use cosmwasm_std::{
+    from_json, to_json_binary, Addr, Coin, Decimal, Deps, DepsMut, Empty, Env, Order, Response,
+    StdResult, Storage, SubMsg, Uint128, WasmMsg,
+};
+use cw_storage_plus::Bound;
+use token_bindings::{DenomUnit, Metadata, TokenFactoryMsg};
 
-use crate::state::{CONFIG, EPOCH_COUNT, PERCENTAGE_OF_YEAR, STAKERS, TOTAL_INTEREST_WATTPEAK};
-use cosmwasm_std::{Decimal, DepsMut, Env, MessageInfo, Order, Response, StdError, StdResult, Uint128};
+pub const SECONDS_PER_YEAR: u64 = 31556926;
 
-pub fn set_yearly_percentage(deps: DepsMut, epoch_length: u64) -> StdResult<()> {
-    let time_staked = Decimal::from_ratio(epoch_length, 1u64);
-    let one_year = Decimal::from_ratio(31556926u64, 1u64);
-    let percentage_of_year = time_staked.checked_div(one_year).unwrap();
-    PERCENTAGE_OF_YEAR.save(deps.storage, &percentage_of_year)?;
-    Ok(())
+/// Reply id of the hooks whose errors are ignored
+pub const STAKE_CHANGED_HOOK_REPLY_ID: u64 = 1;
+
+/// Converts a token amount into a Decimal without losing precision
+pub fn to_decimal(amount: Uint128) -> Result<Decimal, ContractError> {
+    Ok(Decimal::from_atomics(amount, 0)?)
 }
 
-pub fn calculate_interest_after_epoch(deps: DepsMut, info: MessageInfo) -> StdResult<Response> {
-    if info.sender != CONFIG.load(deps.storage)?.admin {
-        return Err(StdError::generic_err("Unauthorized"));
+/// Interest earned by every staked wattpeak between the last accrual and `now`, every part of
+/// that time at the rate that was in effect then
+fn pending_interest_per_wattpeak(
+    storage: &dyn Storage,
+    now: u64,
+) -> Result<Decimal, ContractError> {
+    let now = accrual_time(storage, now)?;
+    let last_accrual_time = LAST_ACCRUAL_TIME.load(storage)?;
+    if now <= last_accrual_time {
+        return Ok(Decimal::zero());
     }
+    let config = CONFIG.load(storage)?;
+    let mut interest_per_wattpeak = Decimal::zero();
+    let mut rate = config.rewards_percentage;
+    let mut from = last_accrual_time;
+    for change in rate_changes(storage, last_accrual_time, now, Order::Ascending) {
+        let (time, next_rate) = change?;
+        let percentage_of_year = Decimal::from_ratio(time - from, SECONDS_PER_YEAR);
+        interest_per_wattpeak =
+            interest_per_wattpeak.checked_add(rate.checked_mul(percentage_of_year)?)?;
+        rate = next_rate;
+        from = time;
+    }
+    let percentage_of_year = Decimal::from_ratio(now - from, SECONDS_PER_YEAR);
+    interest_per_wattpeak =
+        interest_per_wattpeak.checked_add(rate.checked_mul(percentage_of_year)?)?;
 
-    let mut total_interest_wattpeak = TOTAL_INTEREST_WATTPEAK.may_load(deps.storage)?.unwrap_or_else(Decimal::zero);
-  
-    let stakers = STAKERS
-        .range(deps.storage, None, None, Order::Ascending)
-        .collect::<StdResult<Vec<_>>>()?;
-    
-    // Load or initialize total wattpeak interest earned during period
-    let mut total_wattpeak_interest_earned_during_period = Decimal::zero();
-    
-    // Load percentage of year
-    let percentage_of_year = PERCENTAGE_OF_YEAR.load(deps.storage)?;
-    
-    // Load interest rate from config
-    let interest_rate = CONFIG.load(deps.storage)?.rewards_percentage;
-
-    for (key, mut staker) in stakers {
-        let wattpeak_staked = Decimal::from_ratio(staker.wattpeak_staked, 1u64);
-        let wattpeak_interest_per_year = wattpeak_staked.checked_mul(interest_rate).unwrap();
-        let wattpeak_interest_earned = wattpeak_interest_per_year.checked_mul(percentage_of_year).unwrap();
-
-        total_wattpeak_interest_earned_during_period = total_wattpeak_interest_earned_during_period
-            .checked_add(wattpeak_interest_earned).unwrap();
-        
-        staker.interest_wattpeak = staker
-            .interest_wattpeak
-            .checked_add(wattpeak_interest_earned).unwrap();
-        
-        // Save the updated staker information
-        STAKERS.save(deps.storage, key, &staker)?;
-    }
-    
-    // Update total interest wattpeak
-    total_interest_wattpeak = total_interest_wattpeak
-        .checked_add(total_wattpeak_interest_earned_during_period).unwrap();
-    
-    // Save updated total interest wattpeak
-    TOTAL_INTEREST_WATTPEAK.save(deps.storage, &total_interest_wattpeak)?;
-    
-    // Increment epoch count
-    EPOCH_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
-
-    Ok(Response::default())
+    // In capped mode interest only accrues as far as the reserve can pay it
+    let total_weight = total_weight(storage)?;
+    if !config.cap_interest_to_reserve || total_weight.is_zero() {
+        return Ok(interest_per_wattpeak);
+    }
+    let headroom = to_decimal(REWARD_RESERVE.may_load(storage)?.unwrap_or_default())?
+        .saturating_sub(TOTAL_INTEREST_WATTPEAK.load(storage)?);
+    let funded_per_wattpeak = headroom.checked_div(total_weight)?;
+    Ok(interest_per_wattpeak.min(funded_per_wattpeak))
+}
+
+/// Time up to which interest accrues at `now`, the time emergency mode was entered at once it was
+pub fn accrual_time(storage: &dyn Storage, now: u64) -> StdResult<u64> {
+    Ok(match EMERGENCY_TIME.may_load(storage)? {
+        Some(emergency_time) => now.min(emergency_time),
+        None => now,
+    })
+}
+
+/// Returns the rate changes that take effect after `after` and until `until`
+fn rate_changes<'a>(
+    storage: &'a dyn Storage,
+    after: u64,
+    until: u64,
+    order: Order,
+) -> impl Iterator<Item = StdResult<(u64, Decimal)>> + 'a {
+    RATE_SCHEDULE.range(
+        storage,
+        Some(Bound::exclusive(after)),
+        Some(Bound::inclusive(until)),
+        order,
+    )
+}
+
+/// Total weight interest is earned on, the total staked plus the boost of every lock and of the
+/// loyalty of every staker
+pub fn total_weight(storage: &dyn Storage) -> Result<Decimal, ContractError> {
+    let total_lock_boost = TOTAL_LOCK_BOOST.may_load(storage)?.unwrap_or_default();
+    let total_loyalty_boost = TOTAL_LOYALTY_BOOST.may_load(storage)?.unwrap_or_default();
+    to_decimal(
+        TOTAL_WATTPEAK_STAKED
+            .load(storage)?
+            .checked_add(total_lock_boost)?
+            .checked_add(total_loyalty_boost)?,
+    )
+}
+
+/// Maximum number of expired locks and loyalty updates the accrual of a message processes, the
+/// rest waits for the next accrual or ProcessExpiries
+pub const MAX_ACCRUAL_EXPIRIES: u32 = 30;
+
+/// Accrues interest up to `now` for everything currently staked. Must run before the total
+/// staked or the interest rate changes, so every wattpeak only earns for the time it was staked.
+/// Processes at most MAX_ACCRUAL_EXPIRIES of the locks that expired and the stakers that reached
+/// a loyalty tier until `now`. Interest still accrues up to `now` when some are left, so the
+/// total interest always matches what the stakers settle, the locks left keep their boost and
+/// the stakers left wait for their tier until they are processed
+pub fn accrue_interest(storage: &mut dyn Storage, now: u64) -> Result<(), ContractError> {
+    let now = accrual_time(storage, now)?;
+    process_expiries(storage, now, MAX_ACCRUAL_EXPIRIES)?;
+    accrue_interest_until(storage, now)
+}
+
+/// Processes the locks that expired and the stakers that reached a loyalty tier until `now` in
+/// order of time, interest is accrued up to each so every boost earns exactly until it changes.
+/// Processes at most `limit` of them, returns the number processed and whether every one due
+/// until `now` was processed
+pub fn process_expiries(
+    storage: &mut dyn Storage,
+    now: u64,
+    limit: u32,
+) -> Result<(u32, bool), ContractError> {
+    let mut processed = 0;
+    loop {
+        let expired_lock = next_expired_lock(storage, now)?;
+        let loyalty_update = next_loyalty_update(storage, now)?;
+        match (expired_lock, loyalty_update) {
+            (Some(((unlock_time, id), address)), loyalty_update)
+                if loyalty_update
+                    .as_ref()
+                    .is_none_or(|(time, _)| unlock_time <= *time) =>
+            {
+                if processed == limit {
+                    return Ok((processed, false));
+                }
+                accrue_interest_until(storage, unlock_time)?;
+                release_lock(storage, &address, id, unlock_time)?;
+                processed += 1;
+            }
+            (_, Some(_)) if processed == limit => return Ok((processed, false)),
+            (_, Some((time, address))) => {
+                accrue_interest_until(storage, time)?;
+                let mut staker = stakers().load(storage, address.clone())?;
+                settle_staker(storage, &mut staker, time)?;
+                update_loyalty(storage, &address, &mut staker, time)?;
+                stakers().save(storage, address, &staker)?;
+                processed += 1;
+            }
+            _ => return Ok((processed, true)),
+        }
+    }
+}
+
+/// Returns the stakers in the loyalty queue that reached their next tier until `now`, in order of
+/// time
+fn loyalty_updates(
+    storage: &dyn Storage,
+    now: u64,
+) -> impl Iterator<Item = StdResult<(u64, Addr)>> + '_ {
+    LOYALTY_UPDATES.keys(
+        storage,
+        None,
+        Some(Bound::exclusive((now + 1, Addr::unchecked("")))),
+        Order::Ascending,
+    )
+}
+
+/// Returns the first staker in the loyalty queue that reached its next tier until `now`
+fn next_loyalty_update(storage: &dyn Storage, now: u64) -> StdResult<Option<(u64, Addr)>> {
+    loyalty_updates(storage, now).next().transpose()
+}
+
+/// Returns the locks in the expiry queue that expired until `now`, in order of their unlock time
+fn expired_locks(
+    storage: &dyn Storage,
+    now: u64,
+) -> impl Iterator<Item = StdResult<((u64, u64), Addr)>> + '_ {
+    LOCK_EXPIRIES.range(
+        storage,
+        None,
+        Some(Bound::inclusive((now, u64::MAX))),
+        Order::Ascending,
+    )
+}
+
+/// Returns the first lock in the expiry queue that expired until `now`
+fn next_expired_lock(storage: &dyn Storage, now: u64) -> StdResult<Option<((u64, u64), Addr)>> {
+    expired_locks(storage, now).next().transpose()
 }
 
+fn accrue_interest_until(storage: &mut dyn Storage, now: u64) -> Result<(), ContractError> {
+    let interest_per_wattpeak = pending_interest_per_wattpeak(storage, now)?;
+    if !interest_per_wattpeak.is_zero() {
+        INTEREST_INDEX.update(storage, |index| -> StdResult<_> {
+            Ok(index.checked_add(interest_per_wattpeak)?)
+        })?;
+
+        // Every staker earns the same interest per unit of weight, so the total only depends on the total weight
+        let total_interest_earned = total_weight(storage)?.checked_mul(interest_per_wattpeak)?;
+        TOTAL_INTEREST_WATTPEAK.update(storage, |total| -> StdResult<_> {
+            Ok(total.checked_add(total_interest_earned)?)
+        })?;
+        update_current_epoch(storage, |record| {
+            record.interest_wattpeak = record
+                .interest_wattpeak
+                .checked_add(total_interest_earned)?;
+            Ok(())
+        })?;
+    }
+    let last_accrual_time = LAST_ACCRUAL_TIME.load(storage)?;
+    if now > last_accrual_time {
+        // The last rate change that took effect until now is the rate from now on
+        let last_change = rate_changes(storage, last_accrual_time, now, Order::Descending)
+            .next()
+            .transpose()?;
+        if let Some((_, rate)) = last_change {
+            CONFIG.update(storage, |mut config| -> StdResult<_> {
+                config.rewards_percentage = rate;
+                Ok(config)
+            })?;
+        }
+        LAST_ACCRUAL_TIME.save(storage, &now)?;
+    }
+    Ok(())
+}
+
+/// Returns the interest index as it would be after accruing up to `now`
+pub fn interest_index_at(storage: &dyn Storage, now: u64) -> Result<Decimal, ContractError> {
+    let pending = pending_interest_per_wattpeak(storage, now)?;
+    Ok(INTEREST_INDEX.load(storage)?.checked_add(pending)?)
+}
+
+/// Returns the total interest wattpeak as it would be after accruing up to `now`. Locks that
+/// expired since the last accrual keep their boost until they are released
+pub fn total_interest_at(storage: &dyn Storage, now: u64) -> Result<Decimal, ContractError> {
+    let pending = pending_interest_per_wattpeak(storage, now)?;
+    Ok(TOTAL_INTEREST_WATTPEAK
+        .load(storage)?
+        .checked_add(total_weight(storage)?.checked_mul(pending)?)?)
+}
+
+pub fn calculate_interest_after_epoch(
+    deps: DepsMut,
+    env: Env,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let last_epoch_time = LAST_EPOCH_TIME.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    // Anyone can start a new epoch, but only once the current one has ended
+    if now < last_epoch_time + config.epoch_length {
+        return Err(ContractError::EpochNotEnded {
+            next_epoch_time: last_epoch_time + config.epoch_length,
+        });
+    }
+
+    // Catch up on every epoch that ended since the last call
+    let epochs = (now - last_epoch_time) / config.epoch_length;
+    let epoch_end_time = last_epoch_time + epochs * config.epoch_length;
+
+    // Interest accrues by the second, the epoch only needs to be brought up to date
+    accrue_interest(deps.storage, epoch_end_time)?;
+    let (paid_from_reserve, streamed) = release_epoch_rewards(deps.storage, epoch_end_time)?;
+
+    // Close the record of the epoch that ended, missed epochs are covered by the same record.
+    // Rate changes scheduled until the end of the epoch took effect during the accrual
+    let total_wattpeak_staked = TOTAL_WATTPEAK_STAKED.load(deps.storage)?;
+    let rewards_percentage = CONFIG.load(deps.storage)?.rewards_percentage;
+    update_current_epoch(deps.storage, |record| {
+        record.end_time = epoch_end_time;
+        record.epochs = epochs;
+        record.total_wattpeak_staked = total_wattpeak_staked;
+        record.rewards_percentage = rewards_percentage;
+        Ok(())
+    })?;
+
+    // Epochs keep their original schedule, the time past the last ended epoch counts towards the next one
+    LAST_EPOCH_TIME.save(deps.storage, &epoch_end_time)?;
+    let epoch = EPOCH_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + epochs) })?;
+    EPOCHS.save(
+        deps.storage,
+        epoch + 1,
+        &EpochRecord {
+            start_time: epoch_end_time,
+            ..EpochRecord::default()
+        },
+    )?;
+    accrue_interest(deps.storage, now)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "new_epoch")
+        .add_attribute("epoch", epoch.to_string())
+        .add_attribute("epochs_processed", epochs.to_string())
+        .add_attribute("paid_from_reserve", paid_from_reserve.to_string())
+        .add_attribute(
+            "streamed",
+            streamed
+                .iter()
+                .map(|coin| coin.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        ))
+}
 
 pub fn calculate_staker_share_of_reward(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     amount: Uint128,
-) -> StdResult<()> {
-    let total_interest_wattpeak = TOTAL_INTEREST_WATTPEAK.load(deps.storage)?;
-    let stakers = STAKERS
-        .range(deps.storage, None, None, Order::Ascending)
+    denom_amounts: &[Coin],
+) -> Result<(), ContractError> {
+    // The deposit is shared over all interest earned up to now
+    let now = env.block.time.seconds();
+    accrue_interest(deps.storage, now)?;
+    let interest_wattpeak = TOTAL_INTEREST_WATTPEAK.load(deps.storage)?;
+    distribute_rewards(deps.storage, amount, denom_amounts)?;
+
+    // Every deposit keeps its own record next to the totals of the epoch
+    let config = CONFIG.load(deps.storage)?;
+    let mut rewards_deposited = vec![];
+    if !amount.is_zero() {
+        rewards_deposited.push(Coin {
+            denom: config.wattpeak_denom,
+            amount,
+        });
+    }
+    rewards_deposited.extend(denom_amounts.iter().cloned());
+    let id = DEPOSIT_COUNT.load(deps.storage)? + 1;
+    let record = DepositRecord {
+        epoch: EPOCH_COUNT.load(deps.storage)? + 1,
+        time: now,
+        total_wattpeak_staked: TOTAL_WATTPEAK_STAKED.load(deps.storage)?,
+        rewards_percentage: config.rewards_percentage,
+        interest_wattpeak,
+        rewards_deposited,
+    };
+    DEPOSIT_COUNT.save(deps.storage, &id)?;
+    DEPOSITS.save(deps.storage, id, &record)?;
+    Ok(())
+}
+
+/// Distributes the rewards due at the end of an epoch in one reward round: the interest accrued
+/// so far paid from the reward reserve, as far as the reserve covers it, and the rewards the
+/// streams released until `now`. An underfunded reserve pays out what it holds, shared over all
+/// the interest accrued. Returns the wattpeak paid from the reserve and the rewards streamed
+pub fn release_epoch_rewards(
+    storage: &mut dyn Storage,
+    now: u64,
+) -> Result<(Uint128, Vec<Coin>), ContractError> {
+    // Without interest there is no one to distribute to, the streams wait
+    let promised = TOTAL_INTEREST_WATTPEAK.load(storage)?;
+    if promised.is_zero() {
+        return Ok((Uint128::zero(), vec![]));
+    }
+
+    // Only whole wattpeak are paid, the interest is shared over the whole part of the promise
+    let reserve = REWARD_RESERVE.may_load(storage)?.unwrap_or_default();
+    let paid_from_reserve = reserve.min(promised.to_uint_floor());
+    let streamed = release_streams(storage, now)?;
+
+    let wattpeak_denom = CONFIG.load(storage)?.wattpeak_denom;
+    let mut amount = paid_from_reserve;
+    let mut denom_amounts = vec![];
+    for coin in &streamed {
+        match coin.denom == wattpeak_denom {
+            true => amount = amount.checked_add(coin.amount)?,
+            false => denom_amounts.push(coin.clone()),
+        }
+    }
+    if amount.is_zero() && denom_amounts.is_empty() {
+        return Ok((Uint128::zero(), vec![]));
+    }
+    distribute_rewards(storage, amount, &denom_amounts)?;
+    REWARD_RESERVE.save(storage, &(reserve - paid_from_reserve))?;
+    Ok((paid_from_reserve, streamed))
+}
+
+/// Releases the share of every reward stream due until `now`, streams released in full are
+/// removed. Returns the released rewards per denom
+fn release_streams(storage: &mut dyn Storage, now: u64) -> StdResult<Vec<Coin>> {
+    let streams = REWARD_STREAMS
+        .range(storage, None, None, Order::Ascending)
         .collect::<StdResult<Vec<_>>>()?;
-    let decimal_amount = Decimal::from_str(&amount.to_string()).unwrap();
+    let mut released = vec![];
+    for (id, mut stream) in streams {
+        if now <= stream.start {
+            continue;
+        }
+        let due = stream.amount.multiply_ratio(
+            now.min(stream.end) - stream.start,
+            stream.end - stream.start,
+        );
+        let amount = due.checked_sub(stream.released)?;
+        if !amount.is_zero() {
+            add_coin(
+                &mut released,
+                Coin {
+                    denom: stream.denom.clone(),
+                    amount,
+                },
+            )?;
+        }
+        stream.released = due;
+        match stream.released == stream.amount {
+            true => REWARD_STREAMS.remove(storage, id),
+            false => REWARD_STREAMS.save(storage, id, &stream)?,
+        }
+    }
+    Ok(released)
+}
+
+/// Closes the reward round accrued up to now, sharing `amount` of wattpeak and the
+/// `denom_amounts` over all interest accrued during the round
+fn distribute_rewards(
+    storage: &mut dyn Storage,
+    amount: Uint128,
+    denom_amounts: &[Coin],
+) -> Result<(), ContractError> {
+    let total_interest_wattpeak = TOTAL_INTEREST_WATTPEAK.load(storage)?;
+    if total_interest_wattpeak.is_zero() {
+        return Err(ContractError::NoInterest {});
+    }
+
+    let round = REWARD_ROUND.load(storage)?;
+    let interest_index = INTEREST_INDEX.load(storage)?;
+    let previous = previous_round(storage, round)?;
+
+    // Every interest_wattpeak accrued during the round gets the same share of the deposit
+    let reward_per_interest = to_decimal(amount)?.checked_div(total_interest_wattpeak)?;
+    let wattpeak_denom = CONFIG.load(storage)?.wattpeak_denom;
+    add_reward_dust(
+        storage,
+        &wattpeak_denom,
+        undistributed(amount, reward_per_interest, total_interest_wattpeak)?,
+    )?;
+
+    // A wattpeak staked for the whole round accrued the difference in the interest index
+    let round_interest_per_wattpeak = interest_index.checked_sub(previous.interest_index)?;
+    let reward_index = previous
+        .reward_index
+        .checked_add(round_interest_per_wattpeak.checked_mul(reward_per_interest)?)?;
+
+    // Reward denoms deposited in earlier rounds carry their index over, new ones start from zero
+    let mut denom_rewards = previous.denom_rewards;
+    for coin in denom_amounts {
+        if !denom_rewards.iter().any(|index| index.denom == coin.denom) {
+            denom_rewards.push(DenomRewardIndex {
+                denom: coin.denom.clone(),
+                reward_per_interest: Decimal::zero(),
+                reward_index: Decimal::zero(),
+            });
+        }
+    }
+    for index in denom_rewards.iter_mut() {
+        let deposited = denom_amounts
+            .iter()
+            .filter(|coin| coin.denom == index.denom)
+            .map(|coin| coin.amount)
+            .sum::<Uint128>();
+        index.reward_per_interest = to_decimal(deposited)?.checked_div(total_interest_wattpeak)?;
+        add_reward_dust(
+            storage,
+            &index.denom,
+            undistributed(
+                deposited,
+                index.reward_per_interest,
+                total_interest_wattpeak,
+            )?,
+        )?;
+        index.reward_index = index
+            .reward_index
+            .checked_add(round_interest_per_wattpeak.checked_mul(index.reward_per_interest)?)?;
+    }
+
+    REWARD_ROUNDS.save(
+        storage,
+        round,
+        &RewardRound {
+            interest_index,
+            reward_per_interest,
+            reward_index,
+            denom_rewards,
+        },
+    )?;
+    REWARD_ROUND.save(storage, &(round + 1))?;
+    TOTAL_INTEREST_WATTPEAK.save(storage, &Decimal::zero())?;
+
+    update_current_epoch(storage, |record| {
+        if !amount.is_zero() {
+            add_coin(
+                &mut record.rewards_deposited,
+                Coin {
+                    denom: wattpeak_denom.clone(),
+                    amount,
+                },
+            )?;
+        }
+        for coin in denom_amounts {
+            add_coin(&mut record.rewards_deposited, coin.clone())?;
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Applies `update` to the record of the epoch in progress
+pub fn update_current_epoch(
+    storage: &mut dyn Storage,
+    update: impl FnOnce(&mut EpochRecord) -> StdResult<()>,
+) -> StdResult<()> {
+    let epoch = EPOCH_COUNT.load(storage)? + 1;
+    let mut record = EPOCHS.may_load(storage, epoch)?.unwrap_or_default();
+    update(&mut record)?;
+    EPOCHS.save(storage, epoch, &record)
+}
+
+/// Adds the staking fees taken from rewards to the record of the epoch in progress
+pub fn record_staking_fees(storage: &mut dyn Storage, staking_fees: &[Coin]) -> StdResult<()> {
+    if staking_fees.iter().all(|coin| coin.amount.is_zero()) {
+        return Ok(());
+    }
+    update_current_epoch(storage, |record| {
+        for coin in staking_fees {
+            add_coin(&mut record.staking_fees, coin.clone())?;
+        }
+        Ok(())
+    })
+}
+
+/// Adds `coin` to the coin of the same denom in `coins`
+pub fn add_coin(coins: &mut Vec<Coin>, coin: Coin) -> StdResult<()> {
+    if coin.amount.is_zero() {
+        return Ok(());
+    }
+    match coins.iter_mut().find(|c| c.denom == coin.denom) {
+        Some(c) => c.amount = c.amount.checked_add(coin.amount)?,
+        None => coins.push(coin),
+    }
+    Ok(())
+}
+
+/// Returns the part of a deposit lost to rounding down its reward per interest
+fn undistributed(
+    amount: Uint128,
+    reward_per_interest: Decimal,
+    total_interest_wattpeak: Decimal,
+) -> Result<Decimal, ContractError> {
+    let distributed = reward_per_interest.checked_mul(total_interest_wattpeak)?;
+    Ok(to_decimal(amount)?.saturating_sub(distributed))
+}
+
+/// Returns the round closed before `round`, or an empty round if `round` is the first one
+fn previous_round(storage: &dyn Storage, round: u64) -> StdResult<RewardRound> {
+    if round == 0 {
+        return Ok(RewardRound {
+            interest_index: Decimal::zero(),
+            reward_per_interest: Decimal::zero(),
+            reward_index: Decimal::zero(),
+            denom_rewards: vec![],
+        });
+    }
+    REWARD_ROUNDS.load(storage, round - 1)
+}
+
+/// Creates an empty staker record that starts accruing from the current index and round,
+/// the record is counted in STAKER_COUNT
+pub fn new_staker(storage: &mut dyn Storage, stake_start_time: u64) -> StdResult<Staker> {
+    STAKER_COUNT.update(storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+    Ok(Staker {
+        stake_start_time,
+        interest_index: INTEREST_INDEX.load(storage)?,
+        reward_round: REWARD_ROUND.load(storage)?,
+        ..Staker::default()
+    })
+}
+
+/// Adds wattpeak staked since `stake_start_time` to a staker. The stake start time moves to the
+/// average of the start times weighted by amount, so a top-up neither resets nor keeps the
+/// loyalty of the stake
+pub fn add_stake(staker: &mut Staker, amount: Uint128, stake_start_time: u64) -> StdResult<()> {
+    let wattpeak_staked = staker.wattpeak_staked.checked_add(amount)?;
+    let weighted_start_time = Uint128::from(staker.stake_start_time)
+        .full_mul(staker.wattpeak_staked)
+        .checked_add(Uint128::from(stake_start_time).full_mul(amount))?
+        .checked_div(wattpeak_staked.into())?;
+    staker.stake_start_time = Uint128::try_from(weighted_start_time)?.u128() as u64;
+    staker.wattpeak_staked = wattpeak_staked;
+    Ok(())
+}
+
+/// Returns the stake of `address` in each bridged denom
+pub fn bridged_stake(storage: &dyn Storage, address: &Addr) -> StdResult<Vec<Coin>> {
+    BRIDGED_STAKES
+        .prefix(address.clone())
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (denom, amount) = item?;
+            Ok(Coin { denom, amount })
+        })
+        .collect()
+}
+
+/// Returns the part of the stake of a staker in wattpeak_denom, the stake that is not bridged
+pub fn canonical_stake(
+    storage: &dyn Storage,
+    address: &Addr,
+    staker: &Staker,
+) -> StdResult<Uint128> {
+    let bridged = bridged_stake(storage, address)?
+        .iter()
+        .map(|coin| coin.amount)
+        .sum::<Uint128>();
+    Ok(staker.wattpeak_staked.checked_sub(bridged)?)
+}
+
+/// Records `amount` of the stake of `address` as staked in the bridged `denom`
+pub fn add_bridged_stake(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    denom: &str,
+    amount: Uint128,
+) -> StdResult<()> {
+    BRIDGED_STAKES.update(storage, (address.clone(), denom), |stake| -> StdResult<_> {
+        Ok(stake.unwrap_or_default().checked_add(amount)?)
+    })?;
+    TOTAL_BRIDGED_STAKED.update(storage, denom, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(amount)?)
+    })?;
+    Ok(())
+}
+
+/// Removes `amount` from the stake of `address` in the bridged `denom`
+pub fn remove_bridged_stake(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    denom: &str,
+    amount: Uint128,
+) -> StdResult<()> {
+    let key = (address.clone(), denom);
+    let stake = BRIDGED_STAKES
+        .may_load(storage, key.clone())?
+        .unwrap_or_default()
+        .checked_sub(amount)?;
+    match stake.is_zero() {
+        true => BRIDGED_STAKES.remove(storage, key),
+        false => BRIDGED_STAKES.save(storage, key, &stake)?,
+    }
+    let total = TOTAL_BRIDGED_STAKED
+        .may_load(storage, denom)?
+        .unwrap_or_default()
+        .checked_sub(amount)?;
+    match total.is_zero() {
+        true => TOTAL_BRIDGED_STAKED.remove(storage, denom),
+        false => TOTAL_BRIDGED_STAKED.save(storage, denom, &total)?,
+    }
+    Ok(())
+}
+
+/// Reads the denom of the wattpeak minted by `minter` from the storage of the minter
+pub fn query_minter_denom(deps: Deps, minter: &Addr) -> Result<String, ContractError> {
+    let denom = deps
+        .querier
+        .query_wasm_raw(minter, MINTER_FULL_DENOM_KEY.as_bytes())?
+        .ok_or(ContractError::MinterDenomNotFound {})?;
+    Ok(from_json(denom)?)
+}
+
+/// Sets the loyalty boost of a staker settled up to `now` to the loyalty tier its stake reached,
+/// and queues the staker to be updated again when it reaches the next tier
+pub fn update_loyalty(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    staker: &mut Staker,
+    now: u64,
+) -> Result<(), ContractError> {
+    let loyalty_tiers = CONFIG.load(storage)?.loyalty_tiers;
+    let staked_for = now.saturating_sub(staker.stake_start_time);
+
+    let multiplier = loyalty_tiers
+        .iter()
+        .filter(|tier| tier.duration <= staked_for)
+        .max_by_key(|tier| tier.duration)
+        .map_or(Decimal::one(), |tier| tier.multiplier);
+    let loyalty_boost = to_decimal(staker.wattpeak_staked)?
+        .checked_mul(multiplier - Decimal::one())?
+        .to_uint_floor();
+    if loyalty_boost != staker.loyalty_boost {
+        TOTAL_LOYALTY_BOOST.update(storage, |total| -> StdResult<_> {
+            Ok(total
+                .checked_sub(staker.loyalty_boost)?
+                .checked_add(loyalty_boost)?)
+        })?;
+        staker.loyalty_boost = loyalty_boost;
+    }
+
+    if staker.loyalty_update_time != 0 {
+        LOYALTY_UPDATES.remove(storage, (staker.loyalty_update_time, address.clone()));
+    }
+    staker.loyalty_update_time = loyalty_tiers
+        .iter()
+        .filter(|tier| tier.duration > staked_for)
+        .map(|tier| staker.stake_start_time + tier.duration)
+        .min()
+        .filter(|_| !staker.wattpeak_staked.is_zero())
+        .unwrap_or_default();
+    if staker.loyalty_update_time != 0 {
+        LOYALTY_UPDATES.save(
+            storage,
+            (staker.loyalty_update_time, address.clone()),
+            &Empty {},
+        )?;
+    }
+    Ok(())
+}
+
+/// Saves a staker record settled up to `now`, its loyalty boost is brought up to date with its
+/// stake. The stake is snapshotted at `height` when it changed so the voting power of the staker
+/// can be queried at a height. Returns the hooks to notify of the change
+pub fn save_staker(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    staker: &mut Staker,
+    now: u64,
+    height: u64,
+) -> Result<Vec<SubMsg<TokenFactoryMsg>>, ContractError> {
+    update_loyalty(storage, address, staker, now)?;
+    sync_reward_buckets(storage, staker)?;
+    stakers().save(storage, address.clone(), staker)?;
+    let old_amount = STAKED_BALANCES
+        .may_load(storage, address)?
+        .unwrap_or_default();
+    if old_amount == staker.wattpeak_staked {
+        return Ok(vec![]);
+    }
+    STAKED_BALANCES.save(storage, address, &staker.wattpeak_staked, height)?;
+    Ok(stake_changed_hooks(
+        storage,
+        address,
+        old_amount,
+        staker.wattpeak_staked,
+    )?)
+}
+
+/// Builds a StakeChangedHook submessage for every registered hook. Hooks that fail on error are
+/// plain submessages, the errors of the others are caught in reply
+pub fn stake_changed_hooks(
+    storage: &dyn Storage,
+    address: &Addr,
+    old_amount: Uint128,
+    new_amount: Uint128,
+) -> StdResult<Vec<SubMsg<TokenFactoryMsg>>> {
+    let msg = to_json_binary(&StakeChangedHookMsg::StakeChangedHook {
+        address: address.clone(),
+        old_amount,
+        new_amount,
+    })?;
+    HOOKS
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (contract_addr, fail_on_error) = item?;
+            let execute = WasmMsg::Execute {
+                contract_addr: contract_addr.to_string(),
+                msg: msg.clone(),
+                funds: vec![],
+            };
+            Ok(match fail_on_error {
+                true => SubMsg::new(execute),
+                false => SubMsg::reply_on_error(execute, STAKE_CHANGED_HOOK_REPLY_ID),
+            })
+        })
+        .collect()
+}
+
+/// Brings the staker's interest_wattpeak and claimable_rewards up to date with the interest
+/// earned until `now` and every reward round closed since the staker was last settled
+pub fn settle_staker(
+    storage: &dyn Storage,
+    staker: &mut Staker,
+    now: u64,
+) -> Result<(), ContractError> {
+    let interest_index = interest_index_at(storage, now)?;
+    let round = REWARD_ROUND.load(storage)?;
+    // Interest is earned on the stake boosted by the staker's locks and loyalty
+    let wattpeak_staked = to_decimal(staker.weight()?)?;
+
+    if staker.reward_round < round {
+        let first = REWARD_ROUNDS.load(storage, staker.reward_round)?;
+        let last = REWARD_ROUNDS.load(storage, round - 1)?;
+        let (interest_in_round, rewards) = round_rewards(
+            wattpeak_staked,
+            staker.interest_wattpeak,
+            staker.interest_index,
+            &first,
+            &last,
+        )?;
+        staker.claimable_rewards = staker.claimable_rewards.checked_add(rewards)?;
+
+        // Same for every reward denom, a denom missing from the staker's round was not deposited yet
+        for last_denom in &last.denom_rewards {
+            let first_denom = first
+                .denom_rewards
+                .iter()
+                .find(|index| index.denom == last_denom.denom);
+            let (first_reward_per_interest, first_reward_index) = first_denom
+                .map(|index| (index.reward_per_interest, index.reward_index))
+                .unwrap_or_default();
+            let rewards = interest_in_round
+                .checked_mul(first_reward_per_interest)?
+                .checked_add(index_delta(
+                    wattpeak_staked,
+                    first_reward_index,
+                    last_denom.reward_index,
+                )?)?;
+            add_denom_reward(
+                &mut staker.claimable_denom_rewards,
+                &last_denom.denom,
+                rewards,
+            )?;
+        }
 
-    for (key, mut staker) in stakers {
-        let share = staker
-            .interest_wattpeak
-            .checked_div(total_interest_wattpeak)
-            .unwrap();
-        let reward = decimal_amount.checked_mul(share).unwrap();
-        staker.claimable_rewards = staker.claimable_rewards.checked_add(reward).unwrap();
         staker.interest_wattpeak = Decimal::zero();
+        staker.interest_index = last.interest_index;
+        staker.reward_round = round;
+    }
+
+    staker.interest_wattpeak = staker.interest_wattpeak.checked_add(index_delta(
+        wattpeak_staked,
+        staker.interest_index,
+        interest_index,
+    )?)?;
+    staker.interest_index = interest_index;
+    Ok(())
+}
+
+/// What a stake of `weight` earned while an index grew from `from` to `to`
+pub fn index_delta(weight: Decimal, from: Decimal, to: Decimal) -> Result<Decimal, ContractError> {
+    Ok(weight.checked_mul(to.checked_sub(from)?)?)
+}
 
-        // Save the updated staker information
-        STAKERS.save(deps.storage, key, &staker)?;
+/// Settles a stake of `weight`, last settled at `interest_index` in the round `first`, against
+/// every reward round up to and including `last`. Interest accrued in its own round gets that
+/// round's reward per interest, and the stake was unchanged for every round after that one.
+/// Returns the interest accrued in its own round and the wattpeak rewards earned
+pub fn round_rewards(
+    weight: Decimal,
+    interest_wattpeak: Decimal,
+    interest_index: Decimal,
+    first: &RewardRound,
+    last: &RewardRound,
+) -> Result<(Decimal, Decimal), ContractError> {
+    let interest_in_round = interest_wattpeak.checked_add(index_delta(
+        weight,
+        interest_index,
+        first.interest_index,
+    )?)?;
+    let rewards = interest_in_round
+        .checked_mul(first.reward_per_interest)?
+        .checked_add(index_delta(weight, first.reward_index, last.reward_index)?)?;
+    Ok((interest_in_round, rewards))
+}
+
+/// Adds `amount` to the staker's claimable rewards in `denom`
+pub fn add_denom_reward(
+    claimable: &mut Vec<DenomReward>,
+    denom: &str,
+    amount: Decimal,
+) -> StdResult<()> {
+    if amount.is_zero() {
+        return Ok(());
+    }
+    match claimable.iter_mut().find(|reward| reward.denom == denom) {
+        Some(reward) => reward.amount = reward.amount.checked_add(amount)?,
+        None => claimable.push(DenomReward {
+            denom: denom.to_string(),
+            amount,
+        }),
     }
-    TOTAL_INTEREST_WATTPEAK.save(deps.storage, &Decimal::zero())?;
     Ok(())
 }
+
+/// Brings the reward buckets of a staker in line with its claimable rewards. Rewards settled since
+/// the buckets were last synced go into the bucket of the epoch in progress, rewards that were
+/// claimed, restaked or transferred are taken from the oldest buckets first
+pub fn sync_reward_buckets(storage: &dyn Storage, staker: &mut Staker) -> StdResult<()> {
+    if CONFIG.load(storage)?.reward_expiry.is_none() {
+        staker.reward_buckets.clear();
+        return Ok(());
+    }
+    let epoch = EPOCH_COUNT.load(storage)? + 1;
+
+    // Wattpeak is tracked as the denom None, a denom left in the buckets only is no longer claimable
+    let mut claimable = vec![(None, staker.claimable_rewards)];
+    for reward in &staker.claimable_denom_rewards {
+        claimable.push((Some(reward.denom.clone()), reward.amount));
+    }
+    for bucket in &staker.reward_buckets {
+        for reward in &bucket.denom_rewards {
+            if !claimable
+                .iter()
+                .any(|(denom, _)| denom.as_deref() == Some(reward.denom.as_str()))
+            {
+                claimable.push((Some(reward.denom.clone()), Decimal::zero()));
+            }
+        }
+    }
+
+    for (denom, amount) in claimable {
+        let mut bucketed = Decimal::zero();
+        for bucket in staker.reward_buckets.iter_mut() {
+            bucketed = bucketed.checked_add(*bucket_amount(bucket, denom.as_deref()))?;
+        }
+        if amount > bucketed {
+            if staker.reward_buckets.last().map(|bucket| bucket.epoch) != Some(epoch) {
+                staker.reward_buckets.push(RewardBucket {
+                    epoch,
+                    rewards: Decimal::zero(),
+                    denom_rewards: vec![],
+                });
+            }
+            let bucket = staker.reward_buckets.last_mut().unwrap();
+            let bucket_amount = bucket_amount(bucket, denom.as_deref());
+            *bucket_amount = bucket_amount.checked_add(amount.checked_sub(bucketed)?)?;
+        } else {
+            let mut taken = bucketed.checked_sub(amount)?;
+            for bucket in staker.reward_buckets.iter_mut() {
+                let bucket_amount = bucket_amount(bucket, denom.as_deref());
+                let take = taken.min(*bucket_amount);
+                *bucket_amount = bucket_amount.checked_sub(take)?;
+                taken = taken.checked_sub(take)?;
+            }
+        }
+    }
+
+    for bucket in staker.reward_buckets.iter_mut() {
+        bucket
+            .denom_rewards
+            .retain(|reward| !reward.amount.is_zero());
+    }
+    staker
+        .reward_buckets
+        .retain(|bucket| !bucket.rewards.is_zero() || !bucket.denom_rewards.is_empty());
+    Ok(())
+}
+
+/// Adds a bucket of another staker to the buckets of a staker, in the order of their epochs
+pub fn merge_reward_bucket(buckets: &mut Vec<RewardBucket>, bucket: RewardBucket) -> StdResult<()> {
+    let index = buckets.partition_point(|other| other.epoch < bucket.epoch);
+    match buckets.get_mut(index) {
+        Some(other) if other.epoch == bucket.epoch => {
+            other.rewards = other.rewards.checked_add(bucket.rewards)?;
+            for reward in bucket.denom_rewards {
+                add_denom_reward(&mut other.denom_rewards, &reward.denom, reward.amount)?;
+            }
+        }
+        _ => buckets.insert(index, bucket),
+    }
+    Ok(())
+}
+
+/// The amount of a bucket in `denom`, wattpeak if the denom is None
+fn bucket_amount<'a>(bucket: &'a mut RewardBucket, denom: Option<&str>) -> &'a mut Decimal {
+    let Some(denom) = denom else {
+        return &mut bucket.rewards;
+    };
+    let index = match bucket
+        .denom_rewards
+        .iter()
+        .position(|reward| reward.denom == denom)
+    {
+        Some(index) => index,
+        None => {
+            bucket.denom_rewards.push(DenomReward {
+                denom: denom.to_string(),
+                amount: Decimal::zero(),
+            });
+            bucket.denom_rewards.len() - 1
+        }
+    };
+    &mut bucket.denom_rewards[index].amount
+}
+
+/// Takes the rewards of every expired bucket out of a synced staker's claimable rewards, returns
+/// the expired rewards per denom
+pub fn take_expired_rewards(
+    staker: &mut Staker,
+    expiry_epochs: u64,
+    epoch: u64,
+    wattpeak_denom: &str,
+) -> StdResult<Vec<DenomReward>> {
+    let (expired, buckets): (Vec<_>, Vec<_>) = std::mem::take(&mut staker.reward_buckets)
+        .into_iter()
+        .partition(|bucket| bucket.epoch + expiry_epochs < epoch);
+    staker.reward_buckets = buckets;
+
+    let mut rewards = vec![];
+    for bucket in expired {
+        staker.claimable_rewards = staker.claimable_rewards.checked_sub(bucket.rewards)?;
+        add_denom_reward(&mut rewards, wattpeak_denom, bucket.rewards)?;
+        for reward in bucket.denom_rewards {
+            if let Some(claimable) = staker
+                .claimable_denom_rewards
+                .iter_mut()
+                .find(|claimable| claimable.denom == reward.denom)
+            {
+                claimable.amount = claimable.amount.checked_sub(reward.amount)?;
+            }
+            add_denom_reward(&mut rewards, &reward.denom, reward.amount)?;
+        }
+    }
+    staker
+        .claimable_denom_rewards
+        .retain(|reward| !reward.amount.is_zero());
+    Ok(rewards)
+}
+
+/// Takes the whole units out of a claimable reward balance, the fraction stays claimable.
+/// The staking fee is rounded down, returns the payout and the staking fee
+pub fn take_whole_rewards(
+    claimable: &mut Decimal,
+    staking_fee_percentage: Decimal,
+) -> Result<(Uint128, Uint128), ContractError> {
+    let rewards = claimable.to_uint_floor();
+    let staking_fee = to_decimal(rewards)?
+        .checked_mul(staking_fee_percentage)?
+        .to_uint_floor();
+    *claimable = claimable.checked_sub(to_decimal(rewards)?)?;
+    Ok((rewards.checked_sub(staking_fee)?, staking_fee))
+}
+
+/// Adds `amount` of `denom` to the rewards that can no longer be paid out to anyone
+pub fn add_reward_dust(
+    storage: &mut dyn Storage,
+    denom: &str,
+    amount: Decimal,
+) -> Result<(), ContractError> {
+    if amount.is_zero() {
+        return Ok(());
+    }
+    REWARD_DUST.update(storage, denom, |dust| -> StdResult<_> {
+        Ok(dust.unwrap_or_default().checked_add(amount)?)
+    })?;
+    Ok(())
+}
+
+/// Whether the contract holds wattpeak in `denom` that it owes: stake, including the liquid pool,
+/// stakers with interest or rewards left to claim, stake and rewards in the pools, pending
+/// unbonding entries, the reward reserve and reward streams still to be released in `denom`
+pub fn wattpeak_denom_in_use(storage: &dyn Storage, denom: &str) -> StdResult<bool> {
+    if !TOTAL_WATTPEAK_STAKED
+        .may_load(storage)?
+        .unwrap_or_default()
+        .is_zero()
+        || STAKER_COUNT.may_load(storage)?.unwrap_or_default() > 0
+        || !POOL_STAKES.is_empty(storage)
+        || !UNBONDINGS.is_empty(storage)
+        || !REWARD_RESERVE
+            .may_load(storage)?
+            .unwrap_or_default()
+            .is_zero()
+    {
+        return Ok(true);
+    }
+    for stream in REWARD_STREAMS.range(storage, None, None, Order::Ascending) {
+        if stream?.1.denom == denom {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Removes the record of a staker that has nothing staked, no interest left to be rewarded and
+/// less than one of every reward denom claimable. The fractions it still holds become reward dust
+pub fn remove_staker_if_empty(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    staker: &Staker,
+    height: u64,
+) -> Result<(), ContractError> {
+    if !staker.wattpeak_staked.is_zero()
+        || !staker.interest_wattpeak.is_zero()
+        || !staker.claimable_rewards.to_uint_floor().is_zero()
+        || staker
+            .claimable_denom_rewards
+            .iter()
+            .any(|reward| !reward.amount.to_uint_floor().is_zero())
+    {
+        return Ok(());
+    }
+
+    let wattpeak_denom = CONFIG.load(storage)?.wattpeak_denom;
+    add_reward_dust(storage, &wattpeak_denom, staker.claimable_rewards)?;
+    for reward in &staker.claimable_denom_rewards {
+        add_reward_dust(storage, &reward.denom, reward.amount)?;
+    }
+    stakers().remove(storage, address.clone())?;
+    STAKED_BALANCES.remove(storage, address, height)?;
+    STAKER_COUNT.update(storage, |count| -> StdResult<_> {
+        Ok(count.saturating_sub(1))
+    })?;
+    Ok(())
+}
+
+/// Moves the whole wattpeak of a settled staker's claimable_rewards into its stake, the fraction
+/// stays claimable. The restaked rewards count as staked at `now`, the same as a manual top up.
+/// Returns the restaked amount and the staking fee that has to be paid out
+pub fn compound_staker(
+    storage: &mut dyn Storage,
+    staker: &mut Staker,
+    now: u64,
+    height: u64,
+) -> Result<(Uint128, Uint128), ContractError> {
+    let config = CONFIG.load(storage)?;
+    let (restaked, staking_fee) =
+        take_whole_rewards(&mut staker.claimable_rewards, config.staking_fee_percentage)?;
+    if restaked.is_zero() {
+        return Ok((restaked, staking_fee));
+    }
+    record_staking_fees(
+        storage,
+        &[Coin {
+            denom: config.wattpeak_denom,
+            amount: staking_fee,
+        }],
+    )?;
+
+    add_stake(staker, restaked, now)?;
+    TOTAL_WATTPEAK_STAKED.update(storage, height, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(restaked)?)
+    })?;
+
+    Ok((restaked, staking_fee))
+}
+
+/// Creates the stWP denom with its metadata, the same way the minter creates the wattpeak denom
+pub fn create_liquid_denom_msg(full_denom: String) -> TokenFactoryMsg {
+    TokenFactoryMsg::CreateDenom {
+        subdenom: LIQUID_SUBDENOM.to_string(),
+        metadata: Some(Metadata {
+            name: Some(LIQUID_NAME.to_string()),
+            description: Some(LIQUID_DESCRIPTION.to_string()),
+            denom_units: vec![
+                DenomUnit {
+                    denom: full_denom.clone(),
+                    exponent: 0,
+                    aliases: vec![],
+                },
+                DenomUnit {
+                    denom: LIQUID_SYMBOL.to_string(),
+                    exponent: LIQUID_DECIMALS,
+                    aliases: vec![],
+                },
+            ],
+            base: Some(full_denom),
+            display: Some(LIQUID_SYMBOL.to_string()),
+            symbol: Some(LIQUID_SYMBOL.to_string()),
+        }),
+    }
+}
+
+/// Loads the staker record of the liquid staking pool, which is held by the contract itself and
+/// auto compounds, settled and with its rewards restaked. Returns the record and the staking fee
+/// taken from the restaked rewards. Rewards in other denoms than wattpeak are paid out to the
+/// stWP holders as they redeem, and in emergency mode no rewards are restaked so the pool only
+/// pays out its principal and those rewards
+pub fn load_liquid_pool(
+    storage: &mut dyn Storage,
+    pool_address: &Addr,
+    now: u64,
+    height: u64,
+) -> Result<(Staker, Uint128), ContractError> {
+    let mut pool = match stakers().may_load(storage, pool_address.clone())? {
+        Some(pool) => pool,
+        None => Staker {
+            auto_compound: true,
+            ..new_staker(storage, now)?
+        },
+    };
+    settle_staker(storage, &mut pool, now)?;
+    if EMERGENCY_TIME.may_load(storage)?.is_some() {
+        return Ok((pool, Uint128::zero()));
+    }
+    let (_, staking_fee) = compound_staker(storage, &mut pool, now, height)?;
+    Ok((pool, staking_fee))
+}
+
+/// Removes a lock position of a staker whose lock expired or is unlocked early. The staker is
+/// settled first, so the boost of the lock earns until `now`. Returns the removed position
+pub fn release_lock(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    id: u64,
+    now: u64,
+) -> Result<LockPosition, ContractError> {
+    let lock = LOCKS.load(storage, (address.clone(), id))?;
+    let mut staker = stakers().load(storage, address.clone())?;
+    settle_staker(storage, &mut staker, now)?;
+    staker.locked_wattpeak = staker.locked_wattpeak.checked_sub(lock.amount)?;
+    staker.lock_boost = staker.lock_boost.checked_sub(lock.boost)?;
+    stakers().save(storage, address.clone(), &staker)?;
+
+    TOTAL_LOCK_BOOST.update(storage, |total| -> StdResult<_> {
+        Ok(total.checked_sub(lock.boost)?)
+    })?;
+    LOCKS.remove(storage, (address.clone(), id));
+    LOCK_EXPIRIES.remove(storage, (lock.unlock_time, id));
+    Ok(lock)
+}
+
+/// Rewrites the staker records stored before the reward index existed. Their interest and
+/// rewards were settled eagerly, so they start from the current index and round
+pub fn migrate_legacy_stakers(storage: &mut dyn Storage) -> StdResult<u64> {
+    let interest_index = INTEREST_INDEX.may_load(storage)?.unwrap_or_default();
+    let round = REWARD_ROUND.may_load(storage)?.unwrap_or_default();
+    INTEREST_INDEX.save(storage, &interest_index)?;
+    REWARD_ROUND.save(storage, &round)?;
+    if TOTAL_INTEREST_WATTPEAK.may_load(storage)?.is_none() {
+        TOTAL_INTEREST_WATTPEAK.save(storage, &Decimal::zero())?;
+    }
+
+    let legacy_stakers = LEGACY_STAKERS
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let migrated = legacy_stakers.len() as u64;
+
+    for (address, legacy) in legacy_stakers {
+        let staker = Staker {
+            wattpeak_staked: legacy.wattpeak_staked,
+            interest_wattpeak: legacy.interest_wattpeak,
+            stake_start_time: legacy.stake_start_time,
+            claimable_rewards: legacy.claimable_rewards,
+            interest_index,
+            reward_round: round,
+            auto_compound: false,
+            claimable_denom_rewards: vec![],
+            locked_wattpeak: Uint128::zero(),
+            lock_boost: Uint128::zero(),
+            loyalty_boost: Uint128::zero(),
+            loyalty_update_time: 0,
+            reward_buckets: vec![],
+        };
+        LEGACY_STAKERS.remove(storage, address.clone());
+        stakers().save(storage, address, &staker)?;
+    }
+    Ok(migrated)
+}
+
+/// Writes the secondary indexes of the staker records stored before the stakers were indexed,
+/// returns the number of staker records
+pub fn index_stakers(storage: &mut dyn Storage) -> StdResult<u64> {
+    let records = stakers()
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let count = records.len() as u64;
+    for (address, staker) in records {
+        stakers().save(storage, address, &staker)?;
+    }
+    Ok(count)
+}
+
+/// Snapshots the stake of every staker record at `height`, for the records stored before staked
+/// balances were snapshotted
+pub fn snapshot_staked_balances(storage: &mut dyn Storage, height: u64) -> StdResult<()> {
+    let records = stakers()
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (address, staker) in records {
+        STAKED_BALANCES.save(storage, &address, &staker.wattpeak_staked, height)?;
+    }
+    let total_wattpeak_staked = TOTAL_WATTPEAK_STAKED.may_load(storage)?.unwrap_or_default();
+    TOTAL_WATTPEAK_STAKED.save(storage, &total_wattpeak_staked, height)
+}
 #[cfg(test)]
 mod tests {
 
@@ -103,6 +1290,13 @@ mod tests {
         Addr,
     };
 
+    fn settled_staker(storage: &dyn Storage, address: Addr) -> Staker {
+        let mut staker = stakers().load(storage, address).unwrap();
+        let now = LAST_ACCRUAL_TIME.load(storage).unwrap();
+        settle_staker(storage, &mut staker, now).unwrap();
+        staker
+    }
+
     #[test]
     fn test_calculate_interest_after_epoch() {
         let mut deps = mock_dependencies();
@@ -114,10 +1308,20 @@ mod tests {
             wattpeak_denom: "watt".to_string(),
             staking_fee_address: Addr::unchecked("staking_fee_address"),
             staking_fee_percentage: Decimal::percent(5),
+            unbonding_period: 0,
+            reward_denoms: vec![],
+            lock_tiers: vec![],
+            early_unstake_fee: None,
+            loyalty_tiers: vec![],
+            minter_address: None,
+            cap_interest_to_reserve: false,
+            reward_expiry: None,
+            guardian: None,
+            bridged_denoms: vec![],
         };
         CONFIG.save(&mut deps.storage, &config).unwrap();
 
-        let env = mock_env();
+        let mut env = mock_env();
         let info = mock_info("admin", &[]);
         let msg = InstantiateMsg { config };
         let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
@@ -168,11 +1372,13 @@ mod tests {
         )
         .unwrap();
 
-        calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
+        env.block.time = env.block.time.plus_seconds(86000);
 
-        let updated_staker1 = STAKERS.load(&deps.storage, staker_info1.sender).unwrap();
-        let updated_staker2 = STAKERS.load(&deps.storage, staker_info2.sender).unwrap();
-        let updated_staker3 = STAKERS.load(&deps.storage, staker_info3.sender).unwrap();
+        calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+
+        let updated_staker1 = settled_staker(&deps.storage, staker_info1.sender);
+        let updated_staker2 = settled_staker(&deps.storage, staker_info2.sender);
+        let updated_staker3 = settled_staker(&deps.storage, staker_info3.sender);
 
         let total_interest = TOTAL_INTEREST_WATTPEAK.load(&deps.storage);
         assert_eq!(
@@ -204,10 +1410,20 @@ mod tests {
             wattpeak_denom: "watt".to_string(),
             staking_fee_address: Addr::unchecked("staking_fee_address"),
             staking_fee_percentage: Decimal::percent(5),               // Example epoch length
+            unbonding_period: 0,
+            reward_denoms: vec![],
+            lock_tiers: vec![],
+            early_unstake_fee: None,
+            loyalty_tiers: vec![],
+            minter_address: None,
+            cap_interest_to_reserve: false,
+            reward_expiry: None,
+            guardian: None,
+            bridged_denoms: vec![],
         };
         CONFIG.save(&mut deps.storage, &config).unwrap();
 
-        let env = mock_env();
+        let mut env = mock_env();
         let info = mock_info("admin", &[]);
         let msg = InstantiateMsg { config };
         let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
@@ -258,13 +1474,17 @@ mod tests {
         )
         .unwrap();
 
-        calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
-        calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
-        calculate_interest_after_epoch(deps.as_mut(), info.clone()).unwrap();
+        env.block.time = env.block.time.plus_seconds(86000);
+
+        calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+        env.block.time = env.block.time.plus_seconds(86000);
+        calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+        env.block.time = env.block.time.plus_seconds(86000);
+        calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
 
-        let updated_staker1 = STAKERS.load(&deps.storage, staker_info1.sender).unwrap();
-        let updated_staker2 = STAKERS.load(&deps.storage, staker_info2.sender).unwrap();
-        let updated_staker3 = STAKERS.load(&deps.storage, staker_info3.sender).unwrap();
+        let updated_staker1 = settled_staker(&deps.storage, staker_info1.sender);
+        let updated_staker2 = settled_staker(&deps.storage, staker_info2.sender);
+        let updated_staker3 = settled_staker(&deps.storage, staker_info3.sender);
         let total_interest = TOTAL_INTEREST_WATTPEAK.load(&deps.storage);
         assert_eq!(
             total_interest.unwrap(),
@@ -285,7 +1505,7 @@ mod tests {
         assert_eq!(EPOCH_COUNT.load(&deps.storage).unwrap(), 3)
     }
     #[test]
-    fn unauthorized_new_epoch() {
+    fn new_epoch_is_time_gated_for_everyone() {
         let mut deps = mock_dependencies();
 
         let config = Config {
@@ -295,10 +1515,20 @@ mod tests {
             wattpeak_denom: "watt".to_string(),
             staking_fee_address: Addr::unchecked("staking_fee_address"),
             staking_fee_percentage: Decimal::percent(5),                     // Example epoch length
+            unbonding_period: 0,
+            reward_denoms: vec![],
+            lock_tiers: vec![],
+            early_unstake_fee: None,
+            loyalty_tiers: vec![],
+            minter_address: None,
+            cap_interest_to_reserve: false,
+            reward_expiry: None,
+            guardian: None,
+            bridged_denoms: vec![],
         };
         CONFIG.save(&mut deps.storage, &config).unwrap();
 
-        let env = mock_env();
+        let mut env = mock_env();
         let info = mock_info("admin", &[]);
         let msg = InstantiateMsg { config };
         let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
@@ -349,8 +1579,369 @@ mod tests {
         )
         .unwrap();
 
-        let unauthorized_info = mock_info("addr1", &[]);
-        let res = calculate_interest_after_epoch(deps.as_mut(), unauthorized_info.clone());
-        assert_eq!(res.unwrap_err().to_string(), "Generic error: Unauthorized");
+        // Anyone can start a new epoch, but not before the current one has ended
+        let next_epoch_time = env.block.time.seconds() + 86000;
+        env.block.time = env.block.time.plus_seconds(85999);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr1", &[]),
+            ExecuteMsg::NewEpoch {},
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            ContractError::EpochNotEnded { next_epoch_time }
+        );
+
+        env.block.time = env.block.time.plus_seconds(1);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr1", &[]),
+            ExecuteMsg::NewEpoch {},
+        )
+        .unwrap();
+        assert_eq!(EPOCH_COUNT.load(&deps.storage).unwrap(), 1);
+        assert_eq!(
+            LAST_EPOCH_TIME.load(&deps.storage).unwrap(),
+            next_epoch_time
+        );
+
+        // The epoch that just started has not ended yet
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr2", &[]),
+            ExecuteMsg::NewEpoch {},
+        );
+        assert!(res.is_err());
+    }
+    #[test]
+    fn new_epoch_catches_up_on_missed_epochs() {
+        let mut deps = mock_dependencies();
+
+        let config = Config {
+            admin: Addr::unchecked("admin"),
+            rewards_percentage: Decimal::percent(10),
+            epoch_length: 86000,
+            wattpeak_denom: "watt".to_string(),
+            staking_fee_address: Addr::unchecked("staking_fee_address"),
+            staking_fee_percentage: Decimal::percent(5),
+            unbonding_period: 0,
+            reward_denoms: vec![],
+            lock_tiers: vec![],
+            early_unstake_fee: None,
+            loyalty_tiers: vec![],
+            minter_address: None,
+            cap_interest_to_reserve: false,
+            reward_expiry: None,
+            guardian: None,
+            bridged_denoms: vec![],
+        };
+
+        let mut env = mock_env();
+        let start = env.block.time.seconds();
+        let info = mock_info("admin", &[]);
+        let msg = InstantiateMsg { config };
+        instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let staker_info1 = mock_info("addr1", &[Coin::new(100000000u128, "watt")]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            staker_info1.clone(),
+            ExecuteMsg::Stake {},
+        )
+        .unwrap();
+
+        // Three and a half epochs pass without anyone calling NewEpoch
+        env.block.time = env.block.time.plus_seconds(3 * 86000 + 43000);
+        let res = calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+        assert_eq!(res.attributes[2].value, "3");
+
+        // Interest accrues for the whole time staked, including the half epoch
+        let staker1 = settled_staker(&deps.storage, staker_info1.sender);
+        assert_eq!(
+            staker1.interest_wattpeak,
+            Decimal::from_ratio(953831814923925u128, 10000000000u128)
+        );
+        assert_eq!(EPOCH_COUNT.load(&deps.storage).unwrap(), 3);
+
+        // The half epoch counts towards the next one
+        assert_eq!(
+            LAST_EPOCH_TIME.load(&deps.storage).unwrap(),
+            start + 3 * 86000
+        );
+        env.block.time = env.block.time.plus_seconds(43000);
+        calculate_interest_after_epoch(deps.as_mut(), env).unwrap();
+        assert_eq!(EPOCH_COUNT.load(&deps.storage).unwrap(), 4);
+    }
+    #[test]
+    fn new_epoch_and_deposits_are_recorded() {
+        let mut deps = mock_dependencies();
+
+        let config = Config {
+            admin: Addr::unchecked("admin"),
+            rewards_percentage: Decimal::percent(10),
+            epoch_length: 86000,
+            wattpeak_denom: "watt".to_string(),
+            staking_fee_address: Addr::unchecked("staking_fee_address"),
+            staking_fee_percentage: Decimal::percent(5),
+            unbonding_period: 0,
+            reward_denoms: vec![],
+            lock_tiers: vec![],
+            early_unstake_fee: None,
+            loyalty_tiers: vec![],
+            minter_address: None,
+            cap_interest_to_reserve: false,
+            reward_expiry: None,
+            guardian: None,
+            bridged_denoms: vec![],
+        };
+
+        let mut env = mock_env();
+        let start = env.block.time.seconds();
+        let info = mock_info("admin", &[]);
+        let msg = InstantiateMsg { config };
+        instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let staker_info1 = mock_info("addr1", &[Coin::new(100000000u128, "watt")]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            staker_info1.clone(),
+            ExecuteMsg::Stake {},
+        )
+        .unwrap();
+
+        env.block.time = env.block.time.plus_seconds(86000);
+        calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+
+        let record = EPOCHS.load(&deps.storage, 1).unwrap();
+        assert_eq!(record.start_time, start);
+        assert_eq!(record.end_time, start + 86000);
+        assert_eq!(record.epochs, 1);
+        assert_eq!(record.total_wattpeak_staked, Uint128::new(100000000));
+        assert_eq!(record.rewards_percentage, Decimal::percent(10));
+        assert_eq!(
+            record.interest_wattpeak,
+            TOTAL_INTEREST_WATTPEAK.load(&deps.storage).unwrap()
+        );
+
+        // Deposits and fees during the next epoch go to its record
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("admin", &[Coin::new(1000u128, "watt")]),
+            ExecuteMsg::DepositRewards {},
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            staker_info1,
+            ExecuteMsg::ClaimReward {
+                recipient: None,
+                ibc: None,
+            },
+        )
+        .unwrap();
+
+        let record = EPOCHS.load(&deps.storage, 2).unwrap();
+        assert_eq!(record.start_time, start + 86000);
+        assert_eq!(record.end_time, 0);
+        assert_eq!(record.rewards_deposited, vec![Coin::new(1000u128, "watt")]);
+        assert_eq!(record.staking_fees, vec![Coin::new(49u128, "watt")]);
+
+        // Every deposit also keeps a record of its own
+        env.block.time = env.block.time.plus_seconds(600);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("admin", &[Coin::new(500u128, "watt")]),
+            ExecuteMsg::DepositRewards {},
+        )
+        .unwrap();
+        let record = EPOCHS.load(&deps.storage, 2).unwrap();
+        assert_eq!(record.rewards_deposited, vec![Coin::new(1500u128, "watt")]);
+        let first = DEPOSITS.load(&deps.storage, 1).unwrap();
+        assert_eq!(first.epoch, 2);
+        assert_eq!(first.time, start + 86000);
+        assert_eq!(first.total_wattpeak_staked, Uint128::new(100000000));
+        assert_eq!(first.rewards_percentage, Decimal::percent(10));
+        assert!(!first.interest_wattpeak.is_zero());
+        assert_eq!(first.rewards_deposited, vec![Coin::new(1000u128, "watt")]);
+        let second = DEPOSITS.load(&deps.storage, 2).unwrap();
+        assert_eq!(second.epoch, 2);
+        assert_eq!(second.time, start + 86600);
+        assert_eq!(second.rewards_deposited, vec![Coin::new(500u128, "watt")]);
+        assert_eq!(DEPOSIT_COUNT.load(&deps.storage).unwrap(), 2);
+    }
+    #[test]
+    fn interest_is_weighted_by_time_staked() {
+        let mut deps = mock_dependencies();
+
+        let config = Config {
+            admin: Addr::unchecked("admin"),
+            rewards_percentage: Decimal::percent(10),
+            epoch_length: 86000,
+            wattpeak_denom: "watt".to_string(),
+            staking_fee_address: Addr::unchecked("staking_fee_address"),
+            staking_fee_percentage: Decimal::percent(5),
+            unbonding_period: 0,
+            reward_denoms: vec![],
+            lock_tiers: vec![],
+            early_unstake_fee: None,
+            loyalty_tiers: vec![],
+            minter_address: None,
+            cap_interest_to_reserve: false,
+            reward_expiry: None,
+            guardian: None,
+            bridged_denoms: vec![],
+        };
+
+        let mut env = mock_env();
+        let info = mock_info("admin", &[]);
+        let msg = InstantiateMsg { config };
+        instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let staker_info1 = mock_info("addr1", &[Coin::new(100000000u128, "watt")]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            staker_info1.clone(),
+            ExecuteMsg::Stake {},
+        )
+        .unwrap();
+
+        // addr1 unstakes half of its stake halfway through the epoch
+        env.block.time = env.block.time.plus_seconds(43000);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr1", &[]),
+            ExecuteMsg::Unstake {
+                amount: Uint128::new(50000000),
+                denom: None,
+            },
+        )
+        .unwrap();
+
+        // addr2 stakes the same amount one second before the epoch ends
+        env.block.time = env.block.time.plus_seconds(42999);
+        let staker_info2 = mock_info("addr2", &[Coin::new(100000000u128, "watt")]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            staker_info2.clone(),
+            ExecuteMsg::Stake {},
+        )
+        .unwrap();
+
+        env.block.time = env.block.time.plus_seconds(1);
+        calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+
+        let interest_for =
+            |seconds: u64| Decimal::percent(10) * Decimal::from_ratio(seconds, SECONDS_PER_YEAR);
+
+        // addr1 earns on the full stake for half an epoch and on half the stake for the other half
+        let staker1 = settled_staker(&deps.storage, staker_info1.sender);
+        assert_eq!(
+            staker1.interest_wattpeak,
+            to_decimal(Uint128::new(100000000)).unwrap() * interest_for(43000)
+                + to_decimal(Uint128::new(50000000)).unwrap()
+                    * (interest_for(42999) + interest_for(1))
+        );
+
+        // addr2 only earns for the one second it was staked
+        let staker2 = settled_staker(&deps.storage, staker_info2.sender);
+        assert_eq!(
+            staker2.interest_wattpeak,
+            to_decimal(Uint128::new(100000000)).unwrap() * interest_for(1)
+        );
+
+        assert_eq!(
+            TOTAL_INTEREST_WATTPEAK.load(&deps.storage).unwrap(),
+            staker1.interest_wattpeak + staker2.interest_wattpeak
+        );
+    }
+    #[test]
+    fn settle_staker_across_reward_rounds() {
+        let mut deps = mock_dependencies();
+
+        let config = Config {
+            admin: Addr::unchecked("admin"),
+            rewards_percentage: Decimal::percent(10),
+            epoch_length: 86000,
+            wattpeak_denom: "watt".to_string(),
+            staking_fee_address: Addr::unchecked("staking_fee_address"),
+            staking_fee_percentage: Decimal::percent(5),
+            unbonding_period: 0,
+            reward_denoms: vec![],
+            lock_tiers: vec![],
+            early_unstake_fee: None,
+            loyalty_tiers: vec![],
+            minter_address: None,
+            cap_interest_to_reserve: false,
+            reward_expiry: None,
+            guardian: None,
+            bridged_denoms: vec![],
+        };
+
+        let mut env = mock_env();
+        let info = mock_info("admin", &[]);
+        let msg = InstantiateMsg { config };
+        instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let staker_info1 = mock_info("addr1", &[Coin::new(100u128, "watt")]);
+        let staker_info2 = mock_info("addr2", &[Coin::new(300u128, "watt")]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            staker_info1.clone(),
+            ExecuteMsg::Stake {},
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            staker_info2.clone(),
+            ExecuteMsg::Stake {},
+        )
+        .unwrap();
+
+        // First round: 100 and 300 staked
+        env.block.time = env.block.time.plus_seconds(86000);
+        calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+        calculate_staker_share_of_reward(deps.as_mut(), env.clone(), Uint128::from(400u128), &[])
+            .unwrap();
+
+        // Second round: addr1 tops up to 300, both stakers now earn the same
+        let top_up = mock_info("addr1", &[Coin::new(200u128, "watt")]);
+        execute(deps.as_mut(), env.clone(), top_up, ExecuteMsg::Stake {}).unwrap();
+        env.block.time = env.block.time.plus_seconds(86000);
+        calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+        calculate_staker_share_of_reward(deps.as_mut(), env.clone(), Uint128::from(600u128), &[])
+            .unwrap();
+
+        // Third round is still open
+        env.block.time = env.block.time.plus_seconds(86000);
+        calculate_interest_after_epoch(deps.as_mut(), env.clone()).unwrap();
+
+        let staker1 = settled_staker(&deps.storage, staker_info1.sender);
+        let staker2 = settled_staker(&deps.storage, staker_info2.sender);
+        assert!(
+            Decimal::from_ratio(400u128, 1u128) - staker1.claimable_rewards < Decimal::permille(1)
+        );
+        assert!(
+            Decimal::from_ratio(600u128, 1u128) - staker2.claimable_rewards < Decimal::permille(1)
+        );
+
+        // Interest of the open round only, and it adds up to the total
+        assert_eq!(staker1.interest_wattpeak, staker2.interest_wattpeak);
+        assert_eq!(
+            staker1.interest_wattpeak + staker2.interest_wattpeak,
+            TOTAL_INTEREST_WATTPEAK.load(&deps.storage).unwrap()
+        );
     }
 }
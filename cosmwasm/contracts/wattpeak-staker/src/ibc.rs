@@ -0,0 +1,198 @@
+use cosmwasm_std::{
+    Addr, BankMsg, Binary, Coin, CosmosMsg, DepsMut, Env, Reply, Response, StdError, Storage,
+    SubMsg,
+};
+use prost::Message;
+use token_bindings::TokenFactoryMsg;
+
+use crate::{
+    error::ContractError,
+    msg::{IbcClaim, IbcLifecycleComplete},
+    state::{IbcTransfer, IBC_TRANSFERS, PENDING_IBC_TRANSFERS},
+};
+
+/// Reply id of the IBC transfers of claimed rewards, the reply holds the sequence of the transfer
+pub const IBC_TRANSFER_REPLY_ID: u64 = 2;
+
+pub const DEFAULT_IBC_TIMEOUT: u64 = 600;
+
+const TRANSFER_PORT: &str = "transfer";
+const MSG_TRANSFER_TYPE_URL: &str = "/ibc.applications.transfer.v1.MsgTransfer";
+
+/// MsgTransfer of the ICS20 transfer module. Unlike IbcMsg::Transfer it carries a memo, which
+/// asks the ibc-hooks module to call the contract back with the acknowledgement
+#[derive(Clone, PartialEq, Message)]
+struct MsgTransfer {
+    #[prost(string, tag = "1")]
+    source_port: String,
+    #[prost(string, tag = "2")]
+    source_channel: String,
+    #[prost(message, optional, tag = "3")]
+    token: Option<ProtoCoin>,
+    #[prost(string, tag = "4")]
+    sender: String,
+    #[prost(string, tag = "5")]
+    receiver: String,
+    #[prost(message, optional, tag = "6")]
+    timeout_height: Option<Height>,
+    #[prost(uint64, tag = "7")]
+    timeout_timestamp: u64,
+    #[prost(string, tag = "8")]
+    memo: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoCoin {
+    #[prost(string, tag = "1")]
+    denom: String,
+    #[prost(string, tag = "2")]
+    amount: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Height {
+    #[prost(uint64, tag = "1")]
+    revision_number: u64,
+    #[prost(uint64, tag = "2")]
+    revision_height: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MsgTransferResponse {
+    #[prost(uint64, tag = "1")]
+    pub sequence: u64,
+}
+
+/// Builds an IBC transfer of every coin claimed by `staker` to the account of the claim. The
+/// transfers are queued until their replies return the sequence they were sent with
+pub fn ibc_transfer_msgs(
+    storage: &mut dyn Storage,
+    env: &Env,
+    staker: &Addr,
+    claim: &IbcClaim,
+    coins: Vec<Coin>,
+) -> Result<Vec<SubMsg<TokenFactoryMsg>>, ContractError> {
+    if claim.channel_id.is_empty() {
+        return Err(ContractError::EmptyIbcChannel {});
+    }
+    if claim.receiver.is_empty() {
+        return Err(ContractError::EmptyIbcReceiver {});
+    }
+    if claim.timeout == Some(0) {
+        return Err(ContractError::ZeroIbcTimeout {});
+    }
+
+    let timeout = env
+        .block
+        .time
+        .plus_seconds(claim.timeout.unwrap_or(DEFAULT_IBC_TIMEOUT));
+    let memo = format!(r#"{{"ibc_callback":"{}"}}"#, env.contract.address);
+
+    let mut pending = PENDING_IBC_TRANSFERS.may_load(storage)?.unwrap_or_default();
+    let mut msgs = vec![];
+    for coin in coins {
+        let transfer = MsgTransfer {
+            source_port: TRANSFER_PORT.to_string(),
+            source_channel: claim.channel_id.clone(),
+            token: Some(ProtoCoin {
+                denom: coin.denom.clone(),
+                amount: coin.amount.to_string(),
+            }),
+            sender: env.contract.address.to_string(),
+            receiver: claim.receiver.clone(),
+            timeout_height: None,
+            timeout_timestamp: timeout.nanos(),
+            memo: memo.clone(),
+        };
+        msgs.push(SubMsg::reply_on_success(
+            CosmosMsg::Stargate {
+                type_url: MSG_TRANSFER_TYPE_URL.to_string(),
+                value: Binary(transfer.encode_to_vec()),
+            },
+            IBC_TRANSFER_REPLY_ID,
+        ));
+        pending.push(IbcTransfer {
+            staker: staker.clone(),
+            channel: claim.channel_id.clone(),
+            amount: coin,
+        });
+    }
+    PENDING_IBC_TRANSFERS.save(storage, &pending)?;
+    Ok(msgs)
+}
+
+/// Keeps the first pending IBC transfer under the channel and sequence it was sent with
+pub fn handle_ibc_transfer_reply(
+    deps: DepsMut,
+    msg: Reply,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let data = msg
+        .result
+        .into_result()
+        .map_err(StdError::generic_err)?
+        .data
+        .ok_or(ContractError::IbcReplyWithoutData {})?;
+    let sequence = MsgTransferResponse::decode(data.as_slice())
+        .map_err(|e| StdError::parse_err("MsgTransferResponse", e.to_string()))?
+        .sequence;
+
+    let mut pending = PENDING_IBC_TRANSFERS.load(deps.storage)?;
+    if pending.is_empty() {
+        return Err(ContractError::NoPendingIbcTransfer {});
+    }
+    let transfer = pending.remove(0);
+    match pending.is_empty() {
+        true => PENDING_IBC_TRANSFERS.remove(deps.storage),
+        false => PENDING_IBC_TRANSFERS.save(deps.storage, &pending)?,
+    }
+
+    IBC_TRANSFERS.save(deps.storage, (&transfer.channel, sequence), &transfer)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "ibc_transfer")
+        .add_attribute("channel", transfer.channel)
+        .add_attribute("sequence", sequence.to_string()))
+}
+
+/// Settles an IBC transfer of claimed rewards once it is acknowledged or timed out. The rewards
+/// of a transfer that failed are refunded to the staker on this chain
+pub fn ibc_lifecycle_complete(
+    deps: DepsMut,
+    _env: Env,
+    msg: IbcLifecycleComplete,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    let (channel, sequence, success) = match msg {
+        IbcLifecycleComplete::IbcAck {
+            channel,
+            sequence,
+            success,
+            ..
+        } => (channel, sequence, success),
+        IbcLifecycleComplete::IbcTimeout { channel, sequence } => (channel, sequence, false),
+    };
+    let transfer = IBC_TRANSFERS
+        .may_load(deps.storage, (&channel, sequence))?
+        .ok_or_else(|| ContractError::UnknownIbcTransfer {
+            sequence,
+            channel: channel.to_string(),
+        })?;
+    IBC_TRANSFERS.remove(deps.storage, (&channel, sequence));
+
+    let response = Response::new()
+        .add_attribute("action", "ibc_transfer_complete")
+        .add_attribute("channel", channel)
+        .add_attribute("sequence", sequence.to_string())
+        .add_attribute("success", success.to_string());
+    if success {
+        return Ok(response);
+    }
+
+    // The transfer module returned the tokens to the contract
+    Ok(response
+        .add_message(BankMsg::Send {
+            to_address: transfer.staker.to_string(),
+            amount: vec![transfer.amount.clone()],
+        })
+        .add_attribute("refund_to", transfer.staker)
+        .add_attribute("refund", transfer.amount.to_string()))
+}
@@ -1,41 +1,195 @@
 pub mod error;
 pub mod execute;
 pub mod helpers;
+pub mod ibc;
 pub mod msg;
+pub mod pools;
 pub mod query;
 pub mod state;
 
-use crate::msg::InstantiateMsg;
-use cosmwasm_std::{entry_point, DepsMut, Env, MessageInfo, Response, StdResult};
-use helpers::set_yearly_percentage;
-use state::{CONFIG, EPOCH_COUNT, TOTAL_WATTPEAK_STAKED};
+use crate::error::ContractError;
+use crate::msg::{InstantiateMsg, MigrateMsg, SudoMsg};
+use cosmwasm_std::{entry_point, Decimal, DepsMut, Env, MessageInfo, Reply, Response, Uint128};
+use cw2::{get_contract_version, set_contract_version};
+use helpers::{
+    index_stakers, migrate_legacy_stakers, query_minter_denom, snapshot_staked_balances,
+    STAKE_CHANGED_HOOK_REPLY_ID,
+};
+use ibc::{handle_ibc_transfer_reply, ibc_lifecycle_complete, IBC_TRANSFER_REPLY_ID};
+use state::{
+    EpochRecord, CONFIG, DEPOSIT_COUNT, EPOCHS, EPOCH_COUNT, INTEREST_INDEX, LAST_ACCRUAL_TIME,
+    LAST_EPOCH_TIME, LOCK_COUNT, RATE_SCHEDULE, REWARD_RESERVE, REWARD_ROUND, REWARD_STREAM_COUNT,
+    STAKER_COUNT, TOTAL_INTEREST_WATTPEAK, TOTAL_LOCK_BOOST, TOTAL_LOYALTY_BOOST,
+    TOTAL_WATTPEAK_STAKED, UNBONDING_COUNT,
+};
+use token_bindings::TokenFactoryMsg;
+
+pub const CONTRACT_NAME: &str = "crates.io:wattpeak-staker";
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[entry_point]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     _info: MessageInfo,
     msg: InstantiateMsg,
-) -> StdResult<Response> {
-    msg.config.validate(deps.as_ref())?;
-    CONFIG.save(deps.storage, &msg.config)?;
-    TOTAL_WATTPEAK_STAKED.save(deps.storage, &0u64.into())?;
+) -> Result<Response, ContractError> {
+    let mut config = msg.config;
+    // A linked minter sets the wattpeak denom to the denom it mints
+    if let Some(minter_address) = &config.minter_address {
+        config.wattpeak_denom = query_minter_denom(deps.as_ref(), minter_address)?;
+    }
+    config.validate(deps.as_ref())?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    CONFIG.save(deps.storage, &config)?;
+    TOTAL_WATTPEAK_STAKED.save(deps.storage, &0u64.into(), env.block.height)?;
     EPOCH_COUNT.save(deps.storage, &0u64)?;
+    DEPOSIT_COUNT.save(deps.storage, &0u64)?;
+    UNBONDING_COUNT.save(deps.storage, &0u64)?;
+    LOCK_COUNT.save(deps.storage, &0u64)?;
+    TOTAL_LOCK_BOOST.save(deps.storage, &Uint128::zero())?;
+    TOTAL_LOYALTY_BOOST.save(deps.storage, &Uint128::zero())?;
+    REWARD_RESERVE.save(deps.storage, &Uint128::zero())?;
+    REWARD_STREAM_COUNT.save(deps.storage, &0u64)?;
+    RATE_SCHEDULE.save(
+        deps.storage,
+        env.block.time.seconds(),
+        &config.rewards_percentage,
+    )?;
+    STAKER_COUNT.save(deps.storage, &0u64)?;
+    TOTAL_INTEREST_WATTPEAK.save(deps.storage, &Decimal::zero())?;
+    INTEREST_INDEX.save(deps.storage, &Decimal::zero())?;
+    REWARD_ROUND.save(deps.storage, &0u64)?;
+    LAST_EPOCH_TIME.save(deps.storage, &env.block.time.seconds())?;
+    LAST_ACCRUAL_TIME.save(deps.storage, &env.block.time.seconds())?;
+    EPOCHS.save(
+        deps.storage,
+        1,
+        &EpochRecord {
+            start_time: env.block.time.seconds(),
+            ..EpochRecord::default()
+        },
+    )?;
+
+    Ok(Response::new().add_attribute("method", "instantiate"))
+}
+
+#[entry_point]
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    // Contracts deployed before the reward index still hold eagerly settled staker records
+    let mut migrated_stakers = 0u64;
+    if INTEREST_INDEX.may_load(deps.storage)?.is_none() {
+        migrated_stakers = migrate_legacy_stakers(deps.storage)?;
+    }
 
-    let epoch_length = msg.config.epoch_length;
+    // Staker records were stored without secondary indexes or a count before
+    if STAKER_COUNT.may_load(deps.storage)?.is_none() {
+        let count = index_stakers(deps.storage)?;
+        STAKER_COUNT.save(deps.storage, &count)?;
+    }
 
-    set_yearly_percentage(deps, epoch_length)?;
+    // Contracts deployed without a contract version did not snapshot stakes, voting power is
+    // known from the migration onwards
+    if get_contract_version(deps.storage).is_err() {
+        snapshot_staked_balances(deps.storage, env.block.height)?;
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-    Ok(Response::new().add_attribute("method", "instantiate"))
+    if UNBONDING_COUNT.may_load(deps.storage)?.is_none() {
+        UNBONDING_COUNT.save(deps.storage, &0u64)?;
+    }
+
+    if LOCK_COUNT.may_load(deps.storage)?.is_none() {
+        LOCK_COUNT.save(deps.storage, &0u64)?;
+        TOTAL_LOCK_BOOST.save(deps.storage, &Uint128::zero())?;
+    }
+    if TOTAL_LOYALTY_BOOST.may_load(deps.storage)?.is_none() {
+        TOTAL_LOYALTY_BOOST.save(deps.storage, &Uint128::zero())?;
+    }
+    if REWARD_RESERVE.may_load(deps.storage)?.is_none() {
+        REWARD_RESERVE.save(deps.storage, &Uint128::zero())?;
+    }
+    if REWARD_STREAM_COUNT.may_load(deps.storage)?.is_none() {
+        REWARD_STREAM_COUNT.save(deps.storage, &0u64)?;
+    }
+    if DEPOSIT_COUNT.may_load(deps.storage)?.is_none() {
+        DEPOSIT_COUNT.save(deps.storage, &0u64)?;
+    }
+    // The history of the rate starts with the rate at the migration
+    if RATE_SCHEDULE.is_empty(deps.storage) {
+        let rewards_percentage = CONFIG.load(deps.storage)?.rewards_percentage;
+        RATE_SCHEDULE.save(deps.storage, env.block.time.seconds(), &rewards_percentage)?;
+    }
+
+    // Epochs were not time gated before, the first epoch after the migration starts now
+    if LAST_EPOCH_TIME.may_load(deps.storage)?.is_none() {
+        LAST_EPOCH_TIME.save(deps.storage, &env.block.time.seconds())?;
+    }
+
+    // Interest used to accrue per epoch, from now on it accrues by the second and the share of a
+    // year an epoch is no longer stored
+    if LAST_ACCRUAL_TIME.may_load(deps.storage)?.is_none() {
+        LAST_ACCRUAL_TIME.save(deps.storage, &env.block.time.seconds())?;
+    }
+    deps.storage.remove(b"percentage_of_year");
+
+    // Epoch records are kept from the epoch in progress at the time of the migration onwards
+    let epoch = EPOCH_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    if EPOCHS.may_load(deps.storage, epoch)?.is_none() {
+        let start_time = LAST_EPOCH_TIME.load(deps.storage)?;
+        EPOCHS.save(
+            deps.storage,
+            epoch,
+            &EpochRecord {
+                start_time,
+                ..EpochRecord::default()
+            },
+        )?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("migrated_stakers", migrated_stakers.to_string()))
+}
+
+#[entry_point]
+pub fn reply(
+    deps: DepsMut,
+    _env: Env,
+    msg: Reply,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    match msg.id {
+        // A hook that does not fail on error can not block a stake change
+        STAKE_CHANGED_HOOK_REPLY_ID => Ok(Response::new()
+            .add_attribute("action", "stake_changed_hook_failed")
+            .add_attribute("error", msg.result.into_result().err().unwrap_or_default())),
+        IBC_TRANSFER_REPLY_ID => handle_ibc_transfer_reply(deps, msg),
+        id => Err(ContractError::UnknownReplyId { id }),
+    }
+}
+
+#[entry_point]
+pub fn sudo(
+    deps: DepsMut,
+    env: Env,
+    msg: SudoMsg,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    match msg {
+        SudoMsg::IbcLifecycleComplete(msg) => ibc_lifecycle_complete(deps, env, msg),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{msg::InstantiateMsg, state::Config};
+    use crate::{
+        helpers::settle_staker,
+        msg::InstantiateMsg,
+        state::{stakers, Config, LegacyStaker, LEGACY_STAKERS, STAKED_BALANCES},
+    };
     use cosmwasm_std::{
         testing::{mock_dependencies, mock_env, mock_info},
-        Addr, Decimal, Timestamp, Uint128,
+        Addr, Decimal, Order, StdResult, Timestamp, Uint128,
     };
 
     #[test]
@@ -52,6 +206,16 @@ mod tests {
                 wattpeak_denom: "watt".to_string(),
                 staking_fee_address: Addr::unchecked("staking_fee_address"),
                 staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
             },
         };
 
@@ -69,4 +233,94 @@ mod tests {
             .into();
         assert_eq!(total_wattpeak, Uint128::zero().into());
     }
+
+    #[test]
+    fn migrate_legacy_staker_records() {
+        let mut deps = mock_dependencies();
+        let config = Config {
+            admin: Addr::unchecked("admin"),
+            rewards_percentage: Decimal::percent(10),
+            epoch_length: 86400,
+            wattpeak_denom: "watt".to_string(),
+            staking_fee_address: Addr::unchecked("staking_fee_address"),
+            staking_fee_percentage: Decimal::percent(5),
+            unbonding_period: 0,
+            reward_denoms: vec![],
+            lock_tiers: vec![],
+            early_unstake_fee: None,
+            loyalty_tiers: vec![],
+            minter_address: None,
+            cap_interest_to_reserve: false,
+            reward_expiry: None,
+            guardian: None,
+            bridged_denoms: vec![],
+        };
+
+        // State as written by the contract before the reward index existed
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+        TOTAL_WATTPEAK_STAKED
+            .save(deps.as_mut().storage, &Uint128::from(300u128), 1)
+            .unwrap();
+        TOTAL_INTEREST_WATTPEAK
+            .save(deps.as_mut().storage, &Decimal::percent(30))
+            .unwrap();
+        EPOCH_COUNT.save(deps.as_mut().storage, &3u64).unwrap();
+        deps.as_mut()
+            .storage
+            .set(b"percentage_of_year", b"\"0.002737907006988507\"");
+        let legacy = LegacyStaker {
+            wattpeak_staked: Uint128::from(100u128),
+            interest_wattpeak: Decimal::percent(10),
+            stake_start_time: 1_600_000_000,
+            claimable_rewards: Decimal::percent(250),
+        };
+        LEGACY_STAKERS
+            .save(deps.as_mut().storage, Addr::unchecked("addr1"), &legacy)
+            .unwrap();
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(res.attributes[1].value, "1");
+        assert_eq!(deps.as_ref().storage.get(b"percentage_of_year"), None);
+
+        let mut staker = stakers()
+            .load(deps.as_ref().storage, Addr::unchecked("addr1"))
+            .unwrap();
+        assert_eq!(staker.interest_index, Decimal::zero());
+        assert_eq!(staker.reward_round, 0);
+
+        // The migrated record is counted and indexed by its stake
+        assert_eq!(STAKER_COUNT.load(deps.as_ref().storage).unwrap(), 1);
+        let by_stake = stakers()
+            .idx
+            .wattpeak_staked
+            .prefix(legacy.wattpeak_staked.u128())
+            .keys(deps.as_ref().storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(by_stake, vec![Addr::unchecked("addr1")]);
+
+        // Its stake is snapshotted as voting power from the block after the migration
+        let height = mock_env().block.height;
+        let power = STAKED_BALANCES
+            .may_load_at_height(deps.as_ref().storage, &Addr::unchecked("addr1"), height + 1)
+            .unwrap();
+        assert_eq!(power, Some(legacy.wattpeak_staked));
+        let version = get_contract_version(deps.as_ref().storage).unwrap();
+        assert_eq!(version.contract, CONTRACT_NAME);
+
+        // Settling right after the migration leaves the record untouched
+        settle_staker(
+            deps.as_ref().storage,
+            &mut staker,
+            mock_env().block.time.seconds(),
+        )
+        .unwrap();
+        assert_eq!(staker.wattpeak_staked, legacy.wattpeak_staked);
+        assert_eq!(staker.interest_wattpeak, legacy.interest_wattpeak);
+        assert_eq!(staker.claimable_rewards, legacy.claimable_rewards);
+
+        // Running the migration again is a no-op
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(res.attributes[1].value, "0");
+    }
 }
@@ -1,7 +1,13 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Decimal, Uint128};
+use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
+use cw2::ContractVersion;
 
-use crate::state::{Config, Staker};
+use serde::{Deserialize, Deserializer};
+
+use crate::state::{
+    Config, DenomReward, DepositRecord, EarlyUnstakeFee, EpochRecord, LockPosition, LockTier,
+    LoyaltyTier, PauseFlags, Pool, PoolStake, RewardBucket, RewardExpiry, RewardStream, Staker,
+};
 
 #[cw_serde]
 pub struct InstantiateMsg {
@@ -9,19 +15,277 @@ pub struct InstantiateMsg {
 }
 
 #[cw_serde]
+pub struct MigrateMsg {}
+
+#[cw_serde]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
+    /// Update the config, only the admin can update it. Fields that are not provided keep their
+    /// value and every changed field is listed in a config_change event. The optional settings
+    /// are removed by setting them to null. The admin is changed with ProposeAdmin and AcceptAdmin
     UpdateConfig {
-        admin: Option<String>,
         rewards_percentage: Option<Decimal>,
         epoch_length: Option<u64>,
+        unbonding_period: Option<u64>,
+        reward_denoms: Option<Vec<String>>,
+        lock_tiers: Option<Vec<LockTier>>,
+        /// Null removes the early unstake fee
+        #[serde(
+            default,
+            deserialize_with = "set_or_clear",
+            skip_serializing_if = "Option::is_none"
+        )]
+        early_unstake_fee: Option<Option<EarlyUnstakeFee>>,
+        loyalty_tiers: Option<Vec<LoyaltyTier>>,
+        /// Sets wattpeak_denom to the denom the minter mints, null unlinks the minter and keeps
+        /// the denom
+        #[serde(
+            default,
+            deserialize_with = "set_or_clear",
+            skip_serializing_if = "Option::is_none"
+        )]
+        minter_address: Option<Option<String>>,
+        cap_interest_to_reserve: Option<bool>,
+        /// Null removes the claim window, rewards no longer expire
+        #[serde(
+            default,
+            deserialize_with = "set_or_clear",
+            skip_serializing_if = "Option::is_none"
+        )]
+        reward_expiry: Option<Option<RewardExpiry>>,
+        /// Null removes the guardian
+        #[serde(
+            default,
+            deserialize_with = "set_or_clear",
+            skip_serializing_if = "Option::is_none"
+        )]
+        guardian: Option<Option<String>>,
+        /// Can only change while the contract holds no wattpeak staked, unbonding or for rewards
+        wattpeak_denom: Option<String>,
+        staking_fee_percentage: Option<Decimal>,
+        staking_fee_address: Option<String>,
+        /// Stake in a removed denom can still be unstaked
+        bridged_denoms: Option<Vec<String>>,
     },
+    /// Propose a new admin, only the admin can propose. The admin changes once the proposed
+    /// address accepts, a new proposal replaces the pending one
+    ProposeAdmin {
+        admin: String,
+    },
+    /// Accept the pending admin proposal, only the proposed address can accept it
+    AcceptAdmin {},
+    /// Cancel the pending admin proposal, only the admin can cancel it
+    CancelAdminProposal {},
+    /// Stake the sent wattpeak, in wattpeak_denom or one of the bridged denoms. Only one coin can
+    /// be sent
     Stake {},
+    /// Stake the sent wattpeak locked for the duration of one of the lock tiers
+    Lock {
+        duration: u64,
+    },
+    /// Unlock a lock of the sender before it expires, paying the early unlock penalty of its tier.
+    /// The unlocked wattpeak stays staked
+    Unlock {
+        id: u64,
+    },
+    /// Unstake wattpeak that is not locked, paying the early unstake fee on a recent stake. The
+    /// wattpeak is unstaked from the stake in denom, wattpeak_denom if not provided
     Unstake {
         amount: Uint128,
+        denom: Option<String>,
+    },
+    /// Withdraw every unbonding entry of the sender that has been released
+    WithdrawUnbonded {},
+    /// Move unlocked wattpeak staked by the sender in wattpeak_denom to the stake of the recipient, along with the
+    /// sender's accrued interest and claimable rewards if include_rewards is set
+    TransferStake {
+        recipient: String,
+        amount: Uint128,
+        include_rewards: bool,
+    },
+    /// Claim the sender's rewards to the recipient, or else its withdraw address or itself.
+    /// With ibc set the rewards are sent over IBC instead, and refunded to the sender if the
+    /// transfer fails or times out
+    ClaimReward {
+        recipient: Option<String>,
+        ibc: Option<IbcClaim>,
+    },
+    /// Set the address rewards are claimed to by default, or clear it to claim to the sender
+    SetWithdrawAddress {
+        address: Option<String>,
+    },
+    /// Restake the sender's claimable rewards, minus the staking fee
+    Restake {},
+    /// Restake the sender's claimable rewards every time one of its stake messages is executed
+    AutoCompound {
+        enabled: bool,
+    },
+    /// Create the staking pool of a minter project, only the admin can create pools
+    CreatePool {
+        project_id: u64,
+        rewards_percentage: Decimal,
+    },
+    /// Update the interest rate of a staking pool, only the admin can update pools
+    UpdatePool {
+        project_id: u64,
+        rewards_percentage: Decimal,
+    },
+    /// Stake the sent wattpeak in the pool of a project. Wattpeak staked in a pool is apart from
+    /// the stake in the contract, it earns no interest of the contract, carries no voting power
+    /// and sends no StakeChangedHook
+    StakeInPool {
+        project_id: u64,
+    },
+    /// Unstake wattpeak from the pool of a project, the unbonding period applies
+    UnstakeFromPool {
+        project_id: u64,
+        amount: Uint128,
+    },
+    /// Deposit the revenue of a project as rewards of its pool, only the admin can deposit
+    DepositPoolRewards {
+        project_id: u64,
+    },
+    /// Claim the sender's rewards from the pool of a project, minus the staking fee
+    ClaimPoolRewards {
+        project_id: u64,
+    },
+    /// Schedule a change of the rewards percentage, only the admin can schedule rate changes.
+    /// Interest accrues at the old rate until the change takes effect
+    ScheduleRateChange {
+        rewards_percentage: Decimal,
+        start: RateChangeStart,
+    },
+    /// Cancel a rate change that has not taken effect yet, by the time it was scheduled at
+    CancelRateChange {
+        time: u64,
+    },
+    /// Bring the loyalty boost of a staker up to date with the loyalty tiers, open to anyone
+    UpdateLoyalty {
+        address: String,
+    },
+    /// Sweep the expired rewards of up to `limit` stakers to the treasury, open to anyone. Every
+    /// sweep continues after the last staker of the previous one, and stakers left with nothing
+    /// staked and no whole rewards are removed
+    SweepExpired {
+        /// The maximum number of stakers to go through, defaults to 30
+        limit: Option<u32>,
+    },
+    /// Release up to `limit` expired locks and loyalty tier updates, open to anyone. Every message
+    /// that accrues interest also processes up to 30 of them, this works through a longer backlog
+    ProcessExpiries {
+        /// The maximum number of locks and loyalty updates to process, defaults to 30
+        limit: Option<u32>,
     },
-    ClaimReward {},
     DepositRewards {},
+    /// Deposit the sent rewards as streams released linearly between `start` and `end`, only the
+    /// admin can deposit. Released rewards are distributed when an epoch ends, a stream waits
+    /// while no interest is accruing
+    DepositRewardStream {
+        start: u64,
+        end: u64,
+    },
+    /// Fund the reward reserve with the sent wattpeak, only the admin can fund the reserve. The
+    /// interest accrued during an epoch is paid from the reserve when the epoch ends, as far as
+    /// the reserve covers it
+    FundReserve {},
     NewEpoch {},
+    /// Register a contract to receive a StakeChangedHook on every stake change, admin only.
+    /// When fail_on_error is set an error of the hook fails the stake change
+    AddHook {
+        address: String,
+        fail_on_error: bool,
+    },
+    /// Remove a registered hook contract, admin only
+    RemoveHook {
+        address: String,
+    },
+    /// Create the stWP liquid staking denom through TokenFactory, admin only
+    CreateLiquidDenom {},
+    /// Stake the sent wattpeak in the liquid staking pool and mint stWP at the exchange rate
+    LiquidStake {},
+    /// Burn the sent stWP and unstake the wattpeak it is worth from the liquid staking pool
+    LiquidUnstake {},
+    /// Pause or unpause parts of the contract, only the admin or the guardian can pause. Flags
+    /// that are not provided keep their value
+    SetPause {
+        staking: Option<bool>,
+        claiming: Option<bool>,
+        epochs: Option<bool>,
+    },
+    /// Enter emergency mode, only the admin or the guardian can enter it. Interest stops accruing,
+    /// rewards can no longer be claimed and stakers can only withdraw the wattpeak they staked.
+    /// Emergency mode cannot be left
+    EnableEmergencyMode {},
+    /// Withdraw every wattpeak the sender staked, locked or is unbonding without fees or
+    /// penalties, or its stake in the pool of a project. Only open in emergency mode, the
+    /// rewards of the sender are left behind
+    EmergencyWithdraw {
+        project_id: Option<u64>,
+    },
+}
+
+/// IbcClaim is an account on another chain that rewards are claimed to over an ICS20 channel
+#[cw_serde]
+pub struct IbcClaim {
+    pub channel_id: String,
+    pub receiver: String,
+    /// Seconds until the transfer times out, defaults to 10 minutes
+    pub timeout: Option<u64>,
+}
+
+/// Message the ibc-hooks module sudo calls the contract with once an IBC transfer it sent is
+/// acknowledged or timed out
+#[cw_serde]
+pub enum SudoMsg {
+    IbcLifecycleComplete(IbcLifecycleComplete),
+}
+
+#[cw_serde]
+pub enum IbcLifecycleComplete {
+    IbcAck {
+        channel: String,
+        sequence: u64,
+        ack: String,
+        success: bool,
+    },
+    IbcTimeout {
+        channel: String,
+        sequence: u64,
+    },
+}
+
+/// Deserializes an optional setting of UpdateConfig that is present, so that null clears the
+/// setting while leaving the field out keeps it
+fn set_or_clear<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Query of the wattpeak minter, only the variants the staker uses
+#[cw_serde]
+pub enum MinterQueryMsg {
+    Project { id: u64 },
+}
+
+/// The fields of a minter project the staker uses, the others are ignored
+#[derive(Deserialize)]
+pub struct MinterProject {
+    pub name: String,
+    pub minted_wattpeak_count: u64,
+}
+
+/// Message executed on the hook contracts when the wattpeak staked by an address changes. Only
+/// the stake in the contract counts, wattpeak staked in the pools is left out
+#[cw_serde]
+pub enum StakeChangedHookMsg {
+    StakeChangedHook {
+        address: Addr,
+        old_amount: Uint128,
+        new_amount: Uint128,
+    },
 }
 
 #[cw_serde]
@@ -32,16 +296,155 @@ pub enum QueryMsg {
     #[returns(Staker)]
     Staker { address: String },
     #[returns(StakersResponse)]
-    Stakers {},
+    Stakers {
+        /// The address of the staker to start the query after
+        start_after: Option<String>,
+        /// The wattpeak staked of the staker to start the query after, as returned in the last
+        /// page. Required with start_after when ordering by wattpeak staked, so the next page does
+        /// not depend on that staker still staking the same amount
+        start_after_wattpeak_staked: Option<Uint128>,
+        /// The maximum number of stakers to return, defaults to 30
+        limit: Option<u32>,
+        /// Defaults to ordering by address
+        order_by: Option<StakersOrderBy>,
+    },
+    #[returns(StakerCountResponse)]
+    StakerCount {},
     #[returns(TotalWattpeakStakedResponse)] // Add the missing return type here
     TotalWattpeakStaked {},
     #[returns(TotalInterestWattpeakResponse)]
     TotalInterestWattpeak {},
+    #[returns(EpochInfoResponse)]
+    EpochInfo {},
+    #[returns(UnbondingsResponse)]
+    Unbondings {
+        address: String,
+        /// The id of the entry to start the query after
+        start_after: Option<u64>,
+        /// The maximum number of entries to return, defaults to 30
+        limit: Option<u32>,
+    },
+    /// The reward streams that have not been released in full
+    #[returns(RewardStreamsResponse)]
+    RewardStreams {
+        /// The id of the stream to start the query after
+        start_after: Option<u64>,
+        /// The maximum number of streams to return, defaults to 30
+        limit: Option<u32>,
+    },
+    /// The interest promised to stakers against the reward reserve funded to pay it
+    #[returns(SolvencyResponse)]
+    Solvency {},
+    /// Rewards per denom that were deposited but can no longer be paid out
+    #[returns(RewardDustResponse)]
+    RewardDust {},
+    /// History of processed epochs, the epoch in progress is included last
+    #[returns(EpochsResponse)]
+    Epochs {
+        /// The epoch to start the query after
+        start_after: Option<u64>,
+        /// The maximum number of epochs to return, defaults to 30
+        limit: Option<u32>,
+    },
+    /// History of rewards deposits, one record per DepositRewards call
+    #[returns(DepositsResponse)]
+    Deposits {
+        /// The deposit id to start the query after
+        start_after: Option<u64>,
+        /// The maximum number of deposits to return, defaults to 30
+        limit: Option<u32>,
+    },
+    /// Wattpeak staked by a staker at the start of a block, defaults to the current block.
+    /// Follows the DAO DAO voting module interface
+    #[returns(VotingPowerAtHeightResponse)]
+    VotingPowerAtHeight {
+        address: String,
+        height: Option<u64>,
+    },
+    /// Total wattpeak staked at the start of a block, defaults to the current block. Leaves out
+    /// the liquid staking pool, which can't vote
+    #[returns(TotalPowerAtHeightResponse)]
+    TotalPowerAtHeight { height: Option<u64> },
+    #[returns(InfoResponse)]
+    Info {},
+    /// The DAO the staker acts as voting module for, which is the admin
+    #[returns(Addr)]
+    Dao {},
+    #[returns(HooksResponse)]
+    Hooks {},
+    /// The stWP denom and the wattpeak one stWP can be unstaked for
+    #[returns(LiquidInfoResponse)]
+    LiquidInfo {},
+    #[returns(Pool)]
+    Pool { project_id: u64 },
+    #[returns(PoolsResponse)]
+    Pools {
+        /// The project id to start the query after
+        start_after: Option<u64>,
+        /// The maximum number of pools to return, defaults to 30
+        limit: Option<u32>,
+    },
+    /// The stake of an address in the pool of a project, settled up to now
+    #[returns(PoolStake)]
+    PoolStake { project_id: u64, address: String },
+    /// The claimable rewards of a staker by the epoch they were settled in, with the epoch they
+    /// expire at. Empty while rewards do not expire
+    #[returns(RewardBucketsResponse)]
+    RewardBuckets { address: String },
+    /// The parts of the contract that are paused and the time emergency mode was entered at
+    #[returns(PauseInfoResponse)]
+    PauseInfo {},
+    /// The address the admin proposed to hand the contract over to
+    #[returns(PendingAdminResponse)]
+    PendingAdmin {},
+    /// The wattpeak staked by an address in wattpeak_denom and in each bridged denom, or the total
+    /// staked in each denom if no address is provided
+    #[returns(StakedDenomsResponse)]
+    StakedDenoms { address: Option<String> },
+    /// The address rewards of a staker are claimed to by default
+    #[returns(WithdrawAddressResponse)]
+    WithdrawAddress { address: String },
+    /// Every change of the rewards percentage, the ones that took effect and the scheduled ones
+    #[returns(RateScheduleResponse)]
+    RateSchedule {
+        /// The time to start the query after
+        start_after: Option<u64>,
+        /// The maximum number of changes to return, defaults to 30
+        limit: Option<u32>,
+    },
+    /// Lock positions of a staker and the weight it earns interest on
+    #[returns(LocksResponse)]
+    Locks {
+        address: String,
+        /// The id of the lock to start the query after
+        start_after: Option<u64>,
+        /// The maximum number of locks to return, defaults to 30
+        limit: Option<u32>,
+    },
+}
+
+#[cw_serde]
+pub enum StakersOrderBy {
+    /// Ascending by address
+    Address,
+    /// Largest stake first, stakers with the same stake by descending address
+    WattpeakStaked,
+}
+
+#[cw_serde]
+pub struct StakerResponse {
+    pub address: String,
+    pub staker: Staker,
 }
 
 #[cw_serde]
 pub struct StakersResponse {
-    pub stakers: Vec<Staker>,
+    pub stakers: Vec<StakerResponse>,
+}
+
+#[cw_serde]
+pub struct StakerCountResponse {
+    pub count: u64,
 }
 
 #[cw_serde]
@@ -52,3 +455,197 @@ pub struct TotalWattpeakStakedResponse {
 pub struct TotalInterestWattpeakResponse {
     pub total_interest: Decimal,
 }
+
+#[cw_serde]
+pub struct EpochInfoResponse {
+    pub current_epoch: u64,
+    // Time in seconds at which the last processed epoch ended
+    pub last_epoch_time: u64,
+    // Time in seconds from which NewEpoch can be called again
+    pub next_epoch_time: u64,
+}
+
+#[cw_serde]
+pub struct UnbondingResponse {
+    pub id: u64,
+    pub amount: Uint128,
+    pub release_at: u64,
+    pub denom: String,
+}
+
+#[cw_serde]
+pub struct UnbondingsResponse {
+    pub unbondings: Vec<UnbondingResponse>,
+}
+
+#[cw_serde]
+pub struct RewardStreamResponse {
+    pub id: u64,
+    // Amount of the stream left to release
+    pub remaining: Uint128,
+    pub stream: RewardStream,
+}
+
+#[cw_serde]
+pub struct RewardStreamsResponse {
+    pub streams: Vec<RewardStreamResponse>,
+    // Amount left to release per denom, over all streams
+    pub remaining: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct RewardBucketResponse {
+    pub bucket: RewardBucket,
+    // First epoch in which the rewards of the bucket are expired
+    pub expires_at_epoch: u64,
+    // Time in seconds at which that epoch starts with the current epoch length
+    pub expires_at: u64,
+}
+
+#[cw_serde]
+pub struct RewardBucketsResponse {
+    pub buckets: Vec<RewardBucketResponse>,
+}
+
+#[cw_serde]
+pub struct PauseInfoResponse {
+    pub flags: PauseFlags,
+    // None while the contract is not in emergency mode
+    pub emergency_time: Option<u64>,
+}
+
+#[cw_serde]
+pub struct StakedDenomsResponse {
+    // The stake in wattpeak_denom comes first
+    pub stake: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct PendingAdminResponse {
+    // None while no admin change is pending
+    pub admin: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct SolvencyResponse {
+    // Interest wattpeak accrued up to now that has not been paid yet
+    pub promised: Decimal,
+    pub reserve: Uint128,
+    // Reserve left once the promised interest is paid, or the promised interest it cannot pay
+    pub surplus: Decimal,
+    pub shortfall: Decimal,
+    // Time in seconds until which the reserve pays the interest at the current rate and total
+    // weight, none when no interest is accruing
+    pub funded_until: Option<u64>,
+    pub cap_interest_to_reserve: bool,
+}
+
+#[cw_serde]
+pub struct RewardDustResponse {
+    pub dust: Vec<DenomReward>,
+}
+
+#[cw_serde]
+pub struct EpochResponse {
+    pub epoch: u64,
+    pub record: EpochRecord,
+}
+
+#[cw_serde]
+pub struct EpochsResponse {
+    pub epochs: Vec<EpochResponse>,
+}
+
+#[cw_serde]
+pub struct DepositResponse {
+    pub id: u64,
+    pub record: DepositRecord,
+}
+
+#[cw_serde]
+pub struct DepositsResponse {
+    pub deposits: Vec<DepositResponse>,
+}
+
+#[cw_serde]
+pub struct VotingPowerAtHeightResponse {
+    pub power: Uint128,
+    pub height: u64,
+}
+
+#[cw_serde]
+pub struct TotalPowerAtHeightResponse {
+    pub power: Uint128,
+    pub height: u64,
+}
+
+#[cw_serde]
+pub struct InfoResponse {
+    pub info: ContractVersion,
+}
+
+#[cw_serde]
+pub struct HookResponse {
+    pub address: Addr,
+    pub fail_on_error: bool,
+}
+
+#[cw_serde]
+pub struct HooksResponse {
+    pub hooks: Vec<HookResponse>,
+}
+
+#[cw_serde]
+pub struct LiquidInfoResponse {
+    // None until the liquid staking denom is created
+    pub denom: Option<String>,
+    pub supply: Uint128,
+    // Wattpeak staked by the liquid staking pool, including the rewards it would compound now
+    pub wattpeak_staked: Uint128,
+    // Wattpeak per stWP
+    pub exchange_rate: Decimal,
+}
+
+#[cw_serde]
+pub struct LockResponse {
+    pub id: u64,
+    pub lock: LockPosition,
+    // Amount locked plus the boost of its multiplier
+    pub weight: Uint128,
+}
+
+#[cw_serde]
+pub struct LocksResponse {
+    pub locks: Vec<LockResponse>,
+    // Wattpeak staked by the staker plus the boost of all its locks and its loyalty
+    pub weight: Uint128,
+}
+
+/// When a scheduled rate change takes effect, at the start of an epoch or at a time in seconds.
+/// The start of an epoch is fixed with the current epoch length when the change is scheduled
+#[cw_serde]
+pub enum RateChangeStart {
+    Epoch(u64),
+    Time(u64),
+}
+
+#[cw_serde]
+pub struct RateChange {
+    pub time: u64,
+    pub rewards_percentage: Decimal,
+}
+
+#[cw_serde]
+pub struct RateScheduleResponse {
+    pub changes: Vec<RateChange>,
+}
+
+#[cw_serde]
+pub struct WithdrawAddressResponse {
+    pub withdraw_address: Addr,
+}
+
+#[cw_serde]
+pub struct PoolsResponse {
+    pub pools: Vec<Pool>,
+}
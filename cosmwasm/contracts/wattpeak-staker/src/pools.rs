@@ -0,0 +1,149 @@
+use cosmwasm_std::{Addr, Decimal, Deps, Storage, Uint128};
+
+use crate::{
+    error::ContractError,
+    helpers::{
+        accrual_time, add_reward_dust, index_delta, round_rewards, to_decimal, SECONDS_PER_YEAR,
+    },
+    msg::{MinterProject, MinterQueryMsg},
+    state::{Pool, PoolStake, RewardRound, CONFIG, POOLS, POOL_ROUNDS, POOL_STAKES},
+};
+
+/// Loads a pool with its interest accrued up to `now`
+pub fn load_pool(storage: &dyn Storage, project_id: u64, now: u64) -> Result<Pool, ContractError> {
+    let mut pool = POOLS
+        .may_load(storage, project_id)?
+        .ok_or(ContractError::PoolNotFound { project_id })?;
+    accrue_pool(&mut pool, accrual_time(storage, now)?)?;
+    Ok(pool)
+}
+
+/// Accrues the interest of everything staked in a pool up to `now`, at the rate of the pool
+pub fn accrue_pool(pool: &mut Pool, now: u64) -> Result<(), ContractError> {
+    if now <= pool.last_accrual_time {
+        return Ok(());
+    }
+    let percentage_of_year = Decimal::from_ratio(now - pool.last_accrual_time, SECONDS_PER_YEAR);
+    let interest_per_wattpeak = pool.rewards_percentage.checked_mul(percentage_of_year)?;
+    pool.interest_index = pool.interest_index.checked_add(interest_per_wattpeak)?;
+    pool.total_interest = pool
+        .total_interest
+        .checked_add(to_decimal(pool.total_staked)?.checked_mul(interest_per_wattpeak)?)?;
+    pool.last_accrual_time = now;
+    Ok(())
+}
+
+/// Loads the stake of an address in a pool settled up to the pool's accrual, or an empty stake
+pub fn load_pool_stake(
+    storage: &dyn Storage,
+    pool: &Pool,
+    address: &Addr,
+) -> Result<PoolStake, ContractError> {
+    match POOL_STAKES.may_load(storage, (pool.project_id, address.clone()))? {
+        Some(mut stake) => {
+            settle_pool_stake(storage, pool, &mut stake)?;
+            Ok(stake)
+        }
+        None => Ok(PoolStake {
+            wattpeak_staked: Uint128::zero(),
+            interest_wattpeak: Decimal::zero(),
+            interest_index: pool.interest_index,
+            reward_round: pool.reward_round,
+            claimable_rewards: Decimal::zero(),
+        }),
+    }
+}
+
+/// Brings the interest and claimable rewards of a stake in a pool up to date with the pool, the
+/// same way staker records are settled against the reward rounds of the contract
+pub fn settle_pool_stake(
+    storage: &dyn Storage,
+    pool: &Pool,
+    stake: &mut PoolStake,
+) -> Result<(), ContractError> {
+    let wattpeak_staked = to_decimal(stake.wattpeak_staked)?;
+
+    if stake.reward_round < pool.reward_round {
+        let first = POOL_ROUNDS.load(storage, (pool.project_id, stake.reward_round))?;
+        let last = POOL_ROUNDS.load(storage, (pool.project_id, pool.reward_round - 1))?;
+        let (_, rewards) = round_rewards(
+            wattpeak_staked,
+            stake.interest_wattpeak,
+            stake.interest_index,
+            &first,
+            &last,
+        )?;
+        stake.claimable_rewards = stake.claimable_rewards.checked_add(rewards)?;
+
+        stake.interest_wattpeak = Decimal::zero();
+        stake.interest_index = last.interest_index;
+        stake.reward_round = pool.reward_round;
+    }
+
+    stake.interest_wattpeak = stake.interest_wattpeak.checked_add(index_delta(
+        wattpeak_staked,
+        stake.interest_index,
+        pool.interest_index,
+    )?)?;
+    stake.interest_index = pool.interest_index;
+    Ok(())
+}
+
+/// Closes the reward round of a pool accrued up to now, sharing `amount` over all interest
+/// accrued in the pool during the round
+pub fn distribute_pool_rewards(
+    storage: &mut dyn Storage,
+    pool: &mut Pool,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    if pool.total_interest.is_zero() {
+        return Err(ContractError::NoInterest {});
+    }
+    let previous = match pool.reward_round {
+        0 => RewardRound {
+            interest_index: Decimal::zero(),
+            reward_per_interest: Decimal::zero(),
+            reward_index: Decimal::zero(),
+            denom_rewards: vec![],
+        },
+        round => POOL_ROUNDS.load(storage, (pool.project_id, round - 1))?,
+    };
+
+    let reward_per_interest = to_decimal(amount)?.checked_div(pool.total_interest)?;
+    let distributed = reward_per_interest.checked_mul(pool.total_interest)?;
+    let wattpeak_denom = CONFIG.load(storage)?.wattpeak_denom;
+    add_reward_dust(
+        storage,
+        &wattpeak_denom,
+        to_decimal(amount)?.saturating_sub(distributed),
+    )?;
+
+    let round_interest_per_wattpeak = pool.interest_index.checked_sub(previous.interest_index)?;
+    POOL_ROUNDS.save(
+        storage,
+        (pool.project_id, pool.reward_round),
+        &RewardRound {
+            interest_index: pool.interest_index,
+            reward_per_interest,
+            reward_index: previous
+                .reward_index
+                .checked_add(round_interest_per_wattpeak.checked_mul(reward_per_interest)?)?,
+            denom_rewards: vec![],
+        },
+    )?;
+    pool.reward_round += 1;
+    pool.total_interest = Decimal::zero();
+    Ok(())
+}
+
+/// Queries a project from the minter, the pool of a project can only be created once the
+/// minter knows it
+pub fn query_minter_project(deps: Deps, project_id: u64) -> Result<MinterProject, ContractError> {
+    let minter_address = CONFIG
+        .load(deps.storage)?
+        .minter_address
+        .ok_or(ContractError::MinterNotSet {})?;
+    deps.querier
+        .query_wasm_smart(minter_address, &MinterQueryMsg::Project { id: project_id })
+        .map_err(|_| ContractError::ProjectNotFound { project_id })
+}
@@ -1,70 +1,643 @@
 use cosmwasm_std::{
-    entry_point, to_json_binary, Addr, Binary, Decimal, Deps, Env, Order, StdResult, Uint128,
+    entry_point, to_json_binary, Addr, Binary, Coin, Decimal, Deps, Env, Order, StdResult, Uint128,
 };
+use cw_storage_plus::Bound;
 
 use crate::{
-    msg::{QueryMsg, StakersResponse},
-    state::{Config, Staker, CONFIG, STAKERS, TOTAL_INTEREST_WATTPEAK, TOTAL_WATTPEAK_STAKED},
+    error::ContractError,
+    helpers::{
+        accrual_time, bridged_stake, settle_staker, sync_reward_buckets, take_whole_rewards,
+        to_decimal, total_interest_at, total_weight, SECONDS_PER_YEAR,
+    },
+    msg::{
+        DepositResponse, DepositsResponse, EpochInfoResponse, EpochResponse, EpochsResponse,
+        HookResponse, HooksResponse, InfoResponse, LiquidInfoResponse, LockResponse, LocksResponse,
+        PauseInfoResponse, PendingAdminResponse, PoolsResponse, QueryMsg, RateChange,
+        RateScheduleResponse, RewardBucketResponse, RewardBucketsResponse, RewardDustResponse,
+        RewardStreamResponse, RewardStreamsResponse, SolvencyResponse, StakedDenomsResponse,
+        StakerCountResponse, StakerResponse, StakersOrderBy, StakersResponse,
+        TotalPowerAtHeightResponse, UnbondingResponse, UnbondingsResponse,
+        VotingPowerAtHeightResponse, WithdrawAddressResponse,
+    },
+    pools::{accrue_pool, load_pool, load_pool_stake},
+    state::{
+        stakers, Config, DenomReward, Pool, PoolStake, Staker, CONFIG, DEPOSITS, EMERGENCY_TIME,
+        EPOCHS, EPOCH_COUNT, HOOKS, LAST_EPOCH_TIME, LIQUID_DENOM, LIQUID_SUPPLY, LOCKS,
+        PAUSE_FLAGS, PENDING_ADMIN, POOLS, RATE_SCHEDULE, REWARD_DUST, REWARD_RESERVE,
+        REWARD_STREAMS, STAKED_BALANCES, STAKER_COUNT, TOTAL_BRIDGED_STAKED, TOTAL_WATTPEAK_STAKED,
+        UNBONDINGS, WITHDRAW_ADDRESSES,
+    },
 };
 
+pub const DEFAULT_LIMIT: u32 = 30;
+pub const MAX_LIMIT: u32 = 100;
+
 #[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
+    Ok(match msg {
         QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
-        QueryMsg::Staker { address } => to_json_binary(&query_staker(deps, address)?),
-        QueryMsg::Stakers {} => to_json_binary(&query_stakers(deps)?),
+        QueryMsg::Staker { address } => to_json_binary(&query_staker(deps, env, address)?),
+        QueryMsg::Stakers {
+            start_after,
+            start_after_wattpeak_staked,
+            limit,
+            order_by,
+        } => to_json_binary(&query_stakers(
+            deps,
+            env,
+            start_after,
+            start_after_wattpeak_staked,
+            limit,
+            order_by,
+        )?),
+        QueryMsg::StakerCount {} => to_json_binary(&query_staker_count(deps)?),
         QueryMsg::TotalWattpeakStaked {} => to_json_binary(&query_total_wattpeak_staked(deps)?),
-        QueryMsg::TotalInterestWattpeak {} => to_json_binary(&query_total_interest_wattpeak(deps)?),
-    }
+        QueryMsg::TotalInterestWattpeak {} => {
+            to_json_binary(&query_total_interest_wattpeak(deps, env)?)
+        }
+        QueryMsg::EpochInfo {} => to_json_binary(&query_epoch_info(deps)?),
+        QueryMsg::Unbondings {
+            address,
+            start_after,
+            limit,
+        } => to_json_binary(&query_unbondings(deps, address, start_after, limit)?),
+        QueryMsg::RewardStreams { start_after, limit } => {
+            to_json_binary(&query_reward_streams(deps, start_after, limit)?)
+        }
+        QueryMsg::Solvency {} => to_json_binary(&query_solvency(deps, env)?),
+        QueryMsg::RewardDust {} => to_json_binary(&query_reward_dust(deps)?),
+        QueryMsg::Epochs { start_after, limit } => {
+            to_json_binary(&query_epochs(deps, start_after, limit)?)
+        }
+        QueryMsg::Deposits { start_after, limit } => {
+            to_json_binary(&query_deposits(deps, start_after, limit)?)
+        }
+        QueryMsg::VotingPowerAtHeight { address, height } => {
+            to_json_binary(&query_voting_power_at_height(deps, env, address, height)?)
+        }
+        QueryMsg::TotalPowerAtHeight { height } => {
+            to_json_binary(&query_total_power_at_height(deps, env, height)?)
+        }
+        QueryMsg::Info {} => to_json_binary(&query_info(deps)?),
+        QueryMsg::Dao {} => to_json_binary(&CONFIG.load(deps.storage)?.admin),
+        QueryMsg::Hooks {} => to_json_binary(&query_hooks(deps)?),
+        QueryMsg::LiquidInfo {} => to_json_binary(&query_liquid_info(deps, env)?),
+        QueryMsg::Locks {
+            address,
+            start_after,
+            limit,
+        } => to_json_binary(&query_locks(deps, address, start_after, limit)?),
+        QueryMsg::RewardBuckets { address } => {
+            to_json_binary(&query_reward_buckets(deps, env, address)?)
+        }
+        QueryMsg::PauseInfo {} => to_json_binary(&query_pause_info(deps)?),
+        QueryMsg::PendingAdmin {} => to_json_binary(&query_pending_admin(deps)?),
+        QueryMsg::StakedDenoms { address } => to_json_binary(&query_staked_denoms(deps, address)?),
+        QueryMsg::WithdrawAddress { address } => {
+            to_json_binary(&query_withdraw_address(deps, address)?)
+        }
+        QueryMsg::RateSchedule { start_after, limit } => {
+            to_json_binary(&query_rate_schedule(deps, start_after, limit)?)
+        }
+        QueryMsg::Pool { project_id } => to_json_binary(&query_pool(deps, env, project_id)?),
+        QueryMsg::Pools { start_after, limit } => {
+            to_json_binary(&query_pools(deps, env, start_after, limit)?)
+        }
+        QueryMsg::PoolStake {
+            project_id,
+            address,
+        } => to_json_binary(&query_pool_stake(deps, env, project_id, address)?),
+    }?)
 }
 
-fn query_config(deps: Deps) -> StdResult<Config> {
+fn query_config(deps: Deps) -> Result<Config, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     Ok(config)
 }
 
-fn query_staker(deps: Deps, address: String) -> StdResult<Staker> {
-    let staker = STAKERS
-        .may_load(deps.storage, Addr::unchecked(address))?
-        .unwrap_or_default();
-    Ok(staker)
+fn query_staker(deps: Deps, env: Env, address: String) -> Result<Staker, ContractError> {
+    match stakers().may_load(deps.storage, Addr::unchecked(address))? {
+        Some(mut staker) => {
+            settle_staker(deps.storage, &mut staker, env.block.time.seconds())?;
+            sync_reward_buckets(deps.storage, &mut staker)?;
+            Ok(staker)
+        }
+        None => Ok(Staker::default()),
+    }
 }
 
-fn query_stakers(deps: Deps) -> StdResult<StakersResponse> {
-    let stakers = STAKERS
-        .range(deps.storage, None, None, Order::Ascending)
-        .map(|item| {
-            let (_, staker) = item?;
-            Ok(staker)
+fn query_stakers(
+    deps: Deps,
+    env: Env,
+    start_after: Option<String>,
+    start_after_wattpeak_staked: Option<Uint128>,
+    limit: Option<u32>,
+    order_by: Option<StakersOrderBy>,
+) -> Result<StakersResponse, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after
+        .map(|address| deps.api.addr_validate(&address))
+        .transpose()?;
+
+    let records = match order_by.unwrap_or(StakersOrderBy::Address) {
+        StakersOrderBy::Address => stakers()
+            .range(
+                deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .collect::<StdResult<Vec<_>>>()?,
+        StakersOrderBy::WattpeakStaked => {
+            // The index is keyed by the amount staked and the address, the cursor holds both so
+            // it stays valid when that staker changed its stake or left since the last page
+            let max = match start_after {
+                Some(address) => {
+                    let wattpeak_staked = start_after_wattpeak_staked
+                        .ok_or(ContractError::MissingStakersCursorAmount {})?;
+                    Some(Bound::exclusive((wattpeak_staked.u128(), address)))
+                }
+                None => None,
+            };
+            stakers()
+                .idx
+                .wattpeak_staked
+                .range(deps.storage, None, max, Order::Descending)
+                .take(limit)
+                .collect::<StdResult<Vec<_>>>()?
+        }
+    };
+
+    let stakers = records
+        .into_iter()
+        .map(|(address, mut staker)| {
+            settle_staker(deps.storage, &mut staker, env.block.time.seconds())?;
+            Ok(StakerResponse {
+                address: address.to_string(),
+                staker,
+            })
         })
-        .collect::<StdResult<Vec<Staker>>>()?;
+        .collect::<Result<Vec<_>, ContractError>>()?;
     Ok(StakersResponse { stakers })
 }
 
-fn query_total_wattpeak_staked(deps: Deps) -> StdResult<Uint128> {
+fn query_staker_count(deps: Deps) -> Result<StakerCountResponse, ContractError> {
+    let count = STAKER_COUNT.may_load(deps.storage)?.unwrap_or_default();
+    Ok(StakerCountResponse { count })
+}
+
+fn query_total_wattpeak_staked(deps: Deps) -> Result<Uint128, ContractError> {
     let total_wattpeak_staked = TOTAL_WATTPEAK_STAKED.may_load(deps.storage)?.unwrap_or_default();
     Ok(total_wattpeak_staked)
 }
 
-fn query_total_interest_wattpeak(deps: Deps) -> StdResult<Decimal> {
-    let total_interest_wattpeak = TOTAL_INTEREST_WATTPEAK
-        .may_load(deps.storage)?
-        .unwrap_or_else(Decimal::zero);
+fn query_total_interest_wattpeak(deps: Deps, env: Env) -> Result<Decimal, ContractError> {
+    let total_interest_wattpeak = total_interest_at(deps.storage, env.block.time.seconds())?;
     Ok(total_interest_wattpeak)
 }
 
+fn query_epoch_info(deps: Deps) -> Result<EpochInfoResponse, ContractError> {
+    let epoch_length = CONFIG.load(deps.storage)?.epoch_length;
+    let last_epoch_time = LAST_EPOCH_TIME.load(deps.storage)?;
+    Ok(EpochInfoResponse {
+        current_epoch: EPOCH_COUNT.load(deps.storage)?,
+        last_epoch_time,
+        next_epoch_time: last_epoch_time + epoch_length,
+    })
+}
+
+fn query_unbondings(
+    deps: Deps,
+    address: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> Result<UnbondingsResponse, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let wattpeak_denom = CONFIG.load(deps.storage)?.wattpeak_denom;
+    let unbondings = UNBONDINGS
+        .prefix(address)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, entry) = item?;
+            Ok(UnbondingResponse {
+                id,
+                amount: entry.amount,
+                release_at: entry.release_at,
+                denom: entry.denom.unwrap_or_else(|| wattpeak_denom.clone()),
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(UnbondingsResponse { unbondings })
+}
+
+fn query_reward_streams(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> Result<RewardStreamsResponse, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let streams = REWARD_STREAMS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, stream) = item?;
+            Ok(RewardStreamResponse {
+                id,
+                remaining: stream.amount.checked_sub(stream.released)?,
+                stream,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    // The total left to release covers every stream, not only the page
+    let mut remaining: Vec<Coin> = vec![];
+    for item in REWARD_STREAMS.range(deps.storage, None, None, Order::Ascending) {
+        let (_, stream) = item?;
+        let amount = stream.amount.checked_sub(stream.released)?;
+        match remaining.iter_mut().find(|coin| coin.denom == stream.denom) {
+            Some(coin) => coin.amount = coin.amount.checked_add(amount)?,
+            None => remaining.push(Coin {
+                denom: stream.denom,
+                amount,
+            }),
+        }
+    }
+    Ok(RewardStreamsResponse { streams, remaining })
+}
+
+fn query_solvency(deps: Deps, env: Env) -> Result<SolvencyResponse, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+    let promised = total_interest_at(deps.storage, now)?;
+    let reserve = REWARD_RESERVE.may_load(deps.storage)?.unwrap_or_default();
+    let surplus = to_decimal(reserve)?.saturating_sub(promised);
+    let shortfall = promised.saturating_sub(to_decimal(reserve)?);
+
+    // Interest promised per year at the current rate and total weight
+    let yearly_interest = total_weight(deps.storage)?.checked_mul(config.rewards_percentage)?;
+    let funded_until = match yearly_interest.is_zero() {
+        true => None,
+        false => {
+            let funded_for = surplus
+                .checked_div(yearly_interest)?
+                .checked_mul(Decimal::from_ratio(SECONDS_PER_YEAR, 1u64))?
+                .to_uint_floor();
+            Some(now.saturating_add(u64::try_from(funded_for.u128()).unwrap_or(u64::MAX)))
+        }
+    };
+
+    Ok(SolvencyResponse {
+        promised,
+        reserve,
+        surplus,
+        shortfall,
+        funded_until,
+        cap_interest_to_reserve: config.cap_interest_to_reserve,
+    })
+}
+
+fn query_reward_dust(deps: Deps) -> Result<RewardDustResponse, ContractError> {
+    let dust = REWARD_DUST
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (denom, amount) = item?;
+            Ok(DenomReward { denom, amount })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(RewardDustResponse { dust })
+}
+
+fn query_epochs(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> Result<EpochsResponse, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let epochs = EPOCHS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (epoch, record) = item?;
+            Ok(EpochResponse { epoch, record })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(EpochsResponse { epochs })
+}
+
+fn query_deposits(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> Result<DepositsResponse, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let deposits = DEPOSITS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, record) = item?;
+            Ok(DepositResponse { id, record })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(DepositsResponse { deposits })
+}
+
+fn query_voting_power_at_height(
+    deps: Deps,
+    env: Env,
+    address: String,
+    height: Option<u64>,
+) -> Result<VotingPowerAtHeightResponse, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    let height = height.unwrap_or(env.block.height);
+    // The stake of the contract is the liquid staking pool, which can't vote
+    if address == env.contract.address {
+        return Ok(VotingPowerAtHeightResponse {
+            power: Uint128::zero(),
+            height,
+        });
+    }
+    let power = STAKED_BALANCES
+        .may_load_at_height(deps.storage, &address, height)?
+        .unwrap_or_default();
+    Ok(VotingPowerAtHeightResponse { power, height })
+}
+
+fn query_total_power_at_height(
+    deps: Deps,
+    env: Env,
+    height: Option<u64>,
+) -> Result<TotalPowerAtHeightResponse, ContractError> {
+    let height = height.unwrap_or(env.block.height);
+    // The liquid staking pool can't vote, so it doesn't count towards the quorum either
+    let liquid_pool = STAKED_BALANCES
+        .may_load_at_height(deps.storage, &env.contract.address, height)?
+        .unwrap_or_default();
+    let power = TOTAL_WATTPEAK_STAKED
+        .may_load_at_height(deps.storage, height)?
+        .unwrap_or_default()
+        .checked_sub(liquid_pool)?;
+    Ok(TotalPowerAtHeightResponse { power, height })
+}
+
+fn query_info(deps: Deps) -> Result<InfoResponse, ContractError> {
+    let info = cw2::get_contract_version(deps.storage)?;
+    Ok(InfoResponse { info })
+}
+
+fn query_hooks(deps: Deps) -> Result<HooksResponse, ContractError> {
+    let hooks = HOOKS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (address, fail_on_error) = item?;
+            Ok(HookResponse {
+                address,
+                fail_on_error,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(HooksResponse { hooks })
+}
+
+fn query_liquid_info(deps: Deps, env: Env) -> Result<LiquidInfoResponse, ContractError> {
+    let supply = LIQUID_SUPPLY.may_load(deps.storage)?.unwrap_or_default();
+    let wattpeak_staked = match stakers().may_load(deps.storage, env.contract.address)? {
+        Some(mut pool) => {
+            settle_staker(deps.storage, &mut pool, env.block.time.seconds())?;
+            let staking_fee_percentage = CONFIG.load(deps.storage)?.staking_fee_percentage;
+            let (restaked, _) =
+                take_whole_rewards(&mut pool.claimable_rewards, staking_fee_percentage)?;
+            pool.wattpeak_staked.checked_add(restaked)?
+        }
+        None => Uint128::zero(),
+    };
+    let exchange_rate = match supply.is_zero() {
+        true => Decimal::one(),
+        false => Decimal::from_ratio(wattpeak_staked, supply),
+    };
+    Ok(LiquidInfoResponse {
+        denom: LIQUID_DENOM.may_load(deps.storage)?,
+        supply,
+        wattpeak_staked,
+        exchange_rate,
+    })
+}
+
+fn query_locks(
+    deps: Deps,
+    address: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> Result<LocksResponse, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let locks = LOCKS
+        .prefix(address.clone())
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, lock) = item?;
+            Ok(LockResponse {
+                id,
+                weight: lock.amount.checked_add(lock.boost)?,
+                lock,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let weight = match stakers().may_load(deps.storage, address)? {
+        Some(staker) => staker.weight()?,
+        None => Uint128::zero(),
+    };
+    Ok(LocksResponse { locks, weight })
+}
+
+fn query_reward_buckets(
+    deps: Deps,
+    env: Env,
+    address: String,
+) -> Result<RewardBucketsResponse, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let staker = query_staker(deps, env, address)?;
+    let Some(expiry) = config.reward_expiry else {
+        return Ok(RewardBucketsResponse { buckets: vec![] });
+    };
+
+    // Epochs to come start every epoch length after the end of the last processed epoch
+    let current_epoch = EPOCH_COUNT.load(deps.storage)? + 1;
+    let last_epoch_time = LAST_EPOCH_TIME.load(deps.storage)?;
+    let buckets = staker
+        .reward_buckets
+        .into_iter()
+        .map(|bucket| {
+            let expires_at_epoch = bucket.epoch + expiry.epochs + 1;
+            // Rewards that expired but were not swept yet expired at the start of an earlier epoch
+            let expires_at = match expires_at_epoch >= current_epoch {
+                true => last_epoch_time + (expires_at_epoch - current_epoch) * config.epoch_length,
+                false => last_epoch_time
+                    .saturating_sub((current_epoch - expires_at_epoch) * config.epoch_length),
+            };
+            RewardBucketResponse {
+                bucket,
+                expires_at_epoch,
+                expires_at,
+            }
+        })
+        .collect();
+    Ok(RewardBucketsResponse { buckets })
+}
+
+fn query_pause_info(deps: Deps) -> Result<PauseInfoResponse, ContractError> {
+    Ok(PauseInfoResponse {
+        flags: PAUSE_FLAGS.may_load(deps.storage)?.unwrap_or_default(),
+        emergency_time: EMERGENCY_TIME.may_load(deps.storage)?,
+    })
+}
+
+fn query_staked_denoms(
+    deps: Deps,
+    address: Option<String>,
+) -> Result<StakedDenomsResponse, ContractError> {
+    let (staked, bridged) = match address {
+        Some(address) => {
+            let address = deps.api.addr_validate(&address)?;
+            let staked = stakers()
+                .may_load(deps.storage, address.clone())?
+                .map(|staker| staker.wattpeak_staked)
+                .unwrap_or_default();
+            (staked, bridged_stake(deps.storage, &address)?)
+        }
+        None => (
+            TOTAL_WATTPEAK_STAKED
+                .may_load(deps.storage)?
+                .unwrap_or_default(),
+            TOTAL_BRIDGED_STAKED
+                .range(deps.storage, None, None, Order::Ascending)
+                .map(|item| {
+                    let (denom, amount) = item?;
+                    Ok(Coin { denom, amount })
+                })
+                .collect::<StdResult<Vec<_>>>()?,
+        ),
+    };
+
+    let bridged_total = bridged.iter().map(|coin| coin.amount).sum::<Uint128>();
+    let mut stake = vec![Coin {
+        denom: CONFIG.load(deps.storage)?.wattpeak_denom,
+        amount: staked.checked_sub(bridged_total)?,
+    }];
+    stake.extend(bridged);
+    Ok(StakedDenomsResponse { stake })
+}
+
+fn query_pending_admin(deps: Deps) -> Result<PendingAdminResponse, ContractError> {
+    Ok(PendingAdminResponse {
+        admin: PENDING_ADMIN.may_load(deps.storage)?,
+    })
+}
+
+fn query_withdraw_address(
+    deps: Deps,
+    address: String,
+) -> Result<WithdrawAddressResponse, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    let withdraw_address = WITHDRAW_ADDRESSES
+        .may_load(deps.storage, address.clone())?
+        .unwrap_or(address);
+    Ok(WithdrawAddressResponse { withdraw_address })
+}
+
+fn query_rate_schedule(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> Result<RateScheduleResponse, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let changes = RATE_SCHEDULE
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (time, rewards_percentage) = item?;
+            Ok(RateChange {
+                time,
+                rewards_percentage,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(RateScheduleResponse { changes })
+}
+
+fn query_pool(deps: Deps, env: Env, project_id: u64) -> Result<Pool, ContractError> {
+    load_pool(deps.storage, project_id, env.block.time.seconds())
+}
+
+fn query_pools(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> Result<PoolsResponse, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let now = accrual_time(deps.storage, env.block.time.seconds())?;
+
+    let pools = POOLS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (_, mut pool) = item?;
+            accrue_pool(&mut pool, now)?;
+            Ok(pool)
+        })
+        .collect::<Result<Vec<_>, ContractError>>()?;
+    Ok(PoolsResponse { pools })
+}
+
+fn query_pool_stake(
+    deps: Deps,
+    env: Env,
+    project_id: u64,
+    address: String,
+) -> Result<PoolStake, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    let pool = load_pool(deps.storage, project_id, env.block.time.seconds())?;
+    load_pool_stake(deps.storage, &pool, &address)
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
     use crate::{
+        execute::execute,
+        helpers::{add_reward_dust, SECONDS_PER_YEAR},
         instantiate,
-        msg::InstantiateMsg,
-        state::{Config, Staker, STAKERS},
+        msg::{ExecuteMsg, InstantiateMsg, RateChangeStart},
+        state::{
+            stakers, Config, LockTier, PauseFlags, RewardExpiry, Staker, UnbondingEntry,
+            TOTAL_INTEREST_WATTPEAK,
+        },
     };
     use cosmwasm_std::{
         testing::{mock_dependencies, mock_env, mock_info},
-        Addr, Decimal, Uint128,
+        Addr, Binary, Coin, ContractResult, Decimal, SystemResult, Uint128, WasmQuery,
     };
 
     #[test]
@@ -80,6 +653,16 @@ mod tests {
                 wattpeak_denom: "watt".to_string(),
                 staking_fee_address: Addr::unchecked("staking_fee_address"),
                 staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
             },
         };
         let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
@@ -102,6 +685,16 @@ mod tests {
                 wattpeak_denom: "watt".to_string(),
                 staking_fee_address: Addr::unchecked("staking_fee_address"),
                 staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
             },
         };
         let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
@@ -111,12 +704,21 @@ mod tests {
             interest_wattpeak: Decimal::percent(5),
             stake_start_time: 1_600_000_000,
             claimable_rewards: Decimal::zero(),
+            interest_index: Decimal::zero(),
+            reward_round: 0,
+            auto_compound: false,
+            claimable_denom_rewards: vec![],
+            locked_wattpeak: Uint128::zero(),
+            lock_boost: Uint128::zero(),
+            loyalty_boost: Uint128::zero(),
+            loyalty_update_time: 0,
+            reward_buckets: vec![],
         };
-        STAKERS
+        stakers()
             .save(deps.as_mut().storage, Addr::unchecked("addr0000"), &staker)
             .unwrap();
 
-        let res = query_staker(deps.as_ref(), "addr0000".to_string()).unwrap();
+        let res = query_staker(deps.as_ref(), mock_env(), "addr0000".to_string()).unwrap();
         assert_eq!(res, staker);
     }
 
@@ -133,6 +735,16 @@ mod tests {
                 wattpeak_denom: "watt".to_string(),
                 staking_fee_address: Addr::unchecked("staking_fee_address"),
                 staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
             },
         };
         let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
@@ -142,24 +754,184 @@ mod tests {
             interest_wattpeak: Decimal::zero(),
             stake_start_time: 1_600_000_000,
             claimable_rewards: Decimal::zero(),
+            interest_index: Decimal::zero(),
+            reward_round: 0,
+            auto_compound: false,
+            claimable_denom_rewards: vec![],
+            locked_wattpeak: Uint128::zero(),
+            lock_boost: Uint128::zero(),
+            loyalty_boost: Uint128::zero(),
+            loyalty_update_time: 0,
+            reward_buckets: vec![],
         };
         let staker2 = Staker {
             wattpeak_staked: Uint128::from(200u128),
             interest_wattpeak: Decimal::zero(),
             stake_start_time: 1_600_000_000,
             claimable_rewards: Decimal::zero(),
+            interest_index: Decimal::zero(),
+            reward_round: 0,
+            auto_compound: false,
+            claimable_denom_rewards: vec![],
+            locked_wattpeak: Uint128::zero(),
+            lock_boost: Uint128::zero(),
+            loyalty_boost: Uint128::zero(),
+            loyalty_update_time: 0,
+            reward_buckets: vec![],
         };
-        STAKERS
+        stakers()
             .save(deps.as_mut().storage, Addr::unchecked("addr0000"), &staker1)
             .unwrap();
-        STAKERS
+        stakers()
             .save(deps.as_mut().storage, Addr::unchecked("addr0001"), &staker2)
             .unwrap();
 
-        let res = query_stakers(deps.as_ref()).unwrap();
+        let res = query_stakers(deps.as_ref(), mock_env(), None, None, None, None).unwrap();
         assert_eq!(res.stakers.len(), 2);
-        assert_eq!(res.stakers[0], staker1);
-        assert_eq!(res.stakers[1], staker2);
+        assert_eq!(res.stakers[0].address, "addr0000");
+        assert_eq!(res.stakers[0].staker, staker1);
+        assert_eq!(res.stakers[1].address, "addr0001");
+        assert_eq!(res.stakers[1].staker, staker2);
+    }
+    #[test]
+    fn test_query_stakers_paginated_and_sorted() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = mock_info("anyone", &[]);
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        for (address, amount) in [
+            ("addr0000", 300u128),
+            ("addr0001", 100u128),
+            ("addr0002", 500u128),
+            ("addr0003", 300u128),
+        ] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(address, &[Coin::new(amount, "watt")]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+        }
+
+        let res = query_staker_count(deps.as_ref()).unwrap();
+        assert_eq!(res.count, 4);
+
+        // Pages by address
+        let res = query_stakers(deps.as_ref(), env.clone(), None, None, Some(3), None).unwrap();
+        let addresses: Vec<_> = res.stakers.iter().map(|s| s.address.as_str()).collect();
+        assert_eq!(addresses, vec!["addr0000", "addr0001", "addr0002"]);
+        let res = query_stakers(
+            deps.as_ref(),
+            env.clone(),
+            Some("addr0002".to_string()),
+            None,
+            Some(3),
+            Some(StakersOrderBy::Address),
+        )
+        .unwrap();
+        let addresses: Vec<_> = res.stakers.iter().map(|s| s.address.as_str()).collect();
+        assert_eq!(addresses, vec!["addr0003"]);
+
+        // Pages by amount staked
+        let res = query_stakers(
+            deps.as_ref(),
+            env.clone(),
+            None,
+            None,
+            Some(2),
+            Some(StakersOrderBy::WattpeakStaked),
+        )
+        .unwrap();
+        let addresses: Vec<_> = res.stakers.iter().map(|s| s.address.as_str()).collect();
+        assert_eq!(addresses, vec!["addr0002", "addr0003"]);
+        assert_eq!(res.stakers[0].staker.wattpeak_staked, Uint128::new(500));
+        let next_page = |deps: Deps| {
+            query_stakers(
+                deps,
+                env.clone(),
+                Some("addr0003".to_string()),
+                Some(Uint128::new(300)),
+                Some(2),
+                Some(StakersOrderBy::WattpeakStaked),
+            )
+            .unwrap()
+        };
+        let res = next_page(deps.as_ref());
+        let addresses: Vec<_> = res.stakers.iter().map(|s| s.address.as_str()).collect();
+        assert_eq!(addresses, vec!["addr0000", "addr0001"]);
+        assert_eq!(
+            query_stakers(
+                deps.as_ref(),
+                env.clone(),
+                Some("addr0003".to_string()),
+                None,
+                Some(2),
+                Some(StakersOrderBy::WattpeakStaked),
+            )
+            .unwrap_err(),
+            ContractError::MissingStakersCursorAmount {}
+        );
+
+        // The index follows changes to the stake and removed stakers leave the count
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr0002", &[]),
+            ExecuteMsg::Unstake {
+                amount: Uint128::new(500),
+                denom: None,
+            },
+        )
+        .unwrap();
+        let res = query_stakers(
+            deps.as_ref(),
+            env.clone(),
+            None,
+            None,
+            Some(1),
+            Some(StakersOrderBy::WattpeakStaked),
+        )
+        .unwrap();
+        assert_eq!(res.stakers[0].address, "addr0003");
+        let res = query_staker_count(deps.as_ref()).unwrap();
+        assert_eq!(res.count, 3);
+
+        // The next page does not depend on the last staker of the previous page
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr0003", &[]),
+            ExecuteMsg::Unstake {
+                amount: Uint128::new(300),
+                denom: None,
+            },
+        )
+        .unwrap();
+        let res = next_page(deps.as_ref());
+        let addresses: Vec<_> = res.stakers.iter().map(|s| s.address.as_str()).collect();
+        assert_eq!(addresses, vec!["addr0000", "addr0001"]);
     }
     #[test]
     fn test_query_total_wattpeak_staked() {
@@ -174,6 +946,16 @@ mod tests {
                 wattpeak_denom: "watt".to_string(),
                 staking_fee_address: Addr::unchecked("staking_fee_address"),
                 staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
             },
         };
         let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
@@ -194,6 +976,16 @@ mod tests {
                 wattpeak_denom: "watt".to_string(),
                 staking_fee_address: Addr::unchecked("staking_fee_address"),
                 staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
             },
         };
         let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
@@ -202,7 +994,7 @@ mod tests {
             .save(deps.as_mut().storage, &total_interest_wattpeak)
             .unwrap();
 
-        let res = query_total_interest_wattpeak(deps.as_ref()).unwrap();
+        let res = query_total_interest_wattpeak(deps.as_ref(), mock_env()).unwrap();
         assert_eq!(res, total_interest_wattpeak);
     }
     #[test]
@@ -218,12 +1010,22 @@ mod tests {
                 wattpeak_denom: "watt".to_string(),
                 staking_fee_address: Addr::unchecked("staking_fee_address"),
                 staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
             },
         };
         let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
 
         // Query a staker that does not exist
-        let res = query_staker(deps.as_ref(), "addr0000".to_string()).unwrap();
+        let res = query_staker(deps.as_ref(), mock_env(), "addr0000".to_string()).unwrap();
 
         // Expected default staker
         let expected_staker = Staker {
@@ -231,6 +1033,15 @@ mod tests {
             interest_wattpeak: Decimal::zero(),
             stake_start_time: 0,
             claimable_rewards: Decimal::zero(),
+            interest_index: Decimal::zero(),
+            reward_round: 0,
+            auto_compound: false,
+            claimable_denom_rewards: vec![],
+            locked_wattpeak: Uint128::zero(),
+            lock_boost: Uint128::zero(),
+            loyalty_boost: Uint128::zero(),
+            loyalty_update_time: 0,
+            reward_buckets: vec![],
         };
 
         // Assert that the returned staker matches the default staker
@@ -250,12 +1061,22 @@ mod tests {
                 wattpeak_denom: "watt".to_string(),
                 staking_fee_address: Addr::unchecked("staking_fee_address"),
                 staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
             },
         };
         let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
 
         // Query stakers when no stakers exist
-        let res = query_stakers(deps.as_ref()).unwrap();
+        let res = query_stakers(deps.as_ref(), mock_env(), None, None, None, None).unwrap();
 
         // Assert that the returned stakers list is empty
         assert_eq!(res.stakers.len(), 0);
@@ -273,12 +1094,22 @@ mod tests {
                 wattpeak_denom: "watt".to_string(),
                 staking_fee_address: Addr::unchecked("staking_fee_address"),
                 staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
             },
         };
         let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
 
         // Query the total interest wattpeak when it is not set
-        let res = query_total_interest_wattpeak(deps.as_ref()).unwrap();
+        let res = query_total_interest_wattpeak(deps.as_ref(), mock_env()).unwrap();
 
         // Assert that the total interest wattpeak is zero
         assert_eq!(res, Decimal::zero());
@@ -296,6 +1127,16 @@ mod tests {
                 wattpeak_denom: "watt".to_string(),
                 staking_fee_address: Addr::unchecked("staking_fee_address"),
                 staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
             },
         };
         let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
@@ -306,4 +1147,1013 @@ mod tests {
         // Assert that the total wattpeak staked is zero
         assert_eq!(res, Uint128::zero());
     }
+    #[test]
+    fn test_query_epoch_info() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = mock_info("anyone", &[]);
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let res = query_epoch_info(deps.as_ref()).unwrap();
+        assert_eq!(res.current_epoch, 0);
+        assert_eq!(res.last_epoch_time, env.block.time.seconds());
+        assert_eq!(res.next_epoch_time, env.block.time.seconds() + 86400);
+    }
+    #[test]
+    fn test_query_reward_dust() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = mock_info("anyone", &[]);
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec!["usdc".to_string()],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        let res = query_reward_dust(deps.as_ref()).unwrap();
+        assert!(res.dust.is_empty());
+
+        add_reward_dust(deps.as_mut().storage, "watt", Decimal::percent(40)).unwrap();
+        add_reward_dust(deps.as_mut().storage, "watt", Decimal::percent(30)).unwrap();
+        add_reward_dust(deps.as_mut().storage, "usdc", Decimal::percent(5)).unwrap();
+
+        let res = query_reward_dust(deps.as_ref()).unwrap();
+        assert_eq!(
+            res.dust,
+            vec![
+                DenomReward {
+                    denom: "usdc".to_string(),
+                    amount: Decimal::percent(5),
+                },
+                DenomReward {
+                    denom: "watt".to_string(),
+                    amount: Decimal::percent(70),
+                },
+            ]
+        );
+    }
+    #[test]
+    fn test_query_epochs() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let start = env.block.time.seconds();
+        let info = mock_info("anyone", &[]);
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        for _ in 0..3 {
+            env.block.time = env.block.time.plus_seconds(86400);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("anyone", &[]),
+                ExecuteMsg::NewEpoch {},
+            )
+            .unwrap();
+        }
+
+        // Three ended epochs and the one in progress
+        let res = query_epochs(deps.as_ref(), None, None).unwrap();
+        let epochs: Vec<u64> = res.epochs.iter().map(|e| e.epoch).collect();
+        assert_eq!(epochs, vec![1, 2, 3, 4]);
+        assert_eq!(res.epochs[0].record.start_time, start);
+        assert_eq!(res.epochs[0].record.end_time, start + 86400);
+        assert_eq!(res.epochs[3].record.end_time, 0);
+
+        let res = query_epochs(deps.as_ref(), Some(1), Some(2)).unwrap();
+        let epochs: Vec<u64> = res.epochs.iter().map(|e| e.epoch).collect();
+        assert_eq!(epochs, vec![2, 3]);
+        assert_eq!(res.epochs[1].record.start_time, start + 2 * 86400);
+    }
+    #[test]
+    fn test_query_deposits() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let start = env.block.time.seconds();
+        let info = mock_info("anyone", &[]);
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec!["usdc".to_string()],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr0000", &[Coin::new(1000000u128, "watt")]),
+            ExecuteMsg::Stake {},
+        )
+        .unwrap();
+
+        // Two deposits in the same epoch and one in the next
+        for (seconds, funds) in [
+            (3600, vec![Coin::new(100u128, "watt")]),
+            (3600, vec![Coin::new(200u128, "usdc")]),
+            (
+                86400,
+                vec![Coin::new(300u128, "watt"), Coin::new(50u128, "usdc")],
+            ),
+        ] {
+            env.block.time = env.block.time.plus_seconds(seconds);
+            if seconds == 86400 {
+                execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info("anyone", &[]),
+                    ExecuteMsg::NewEpoch {},
+                )
+                .unwrap();
+            }
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &funds),
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+        }
+
+        let res = query_deposits(deps.as_ref(), None, None).unwrap();
+        let ids: Vec<u64> = res.deposits.iter().map(|d| d.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        let epochs: Vec<u64> = res.deposits.iter().map(|d| d.record.epoch).collect();
+        assert_eq!(epochs, vec![1, 1, 2]);
+        assert_eq!(res.deposits[0].record.time, start + 3600);
+        assert_eq!(res.deposits[1].record.time, start + 7200);
+        assert_eq!(
+            res.deposits[1].record.rewards_deposited,
+            vec![Coin::new(200u128, "usdc")]
+        );
+        assert_eq!(
+            res.deposits[2].record.rewards_deposited,
+            vec![Coin::new(300u128, "watt"), Coin::new(50u128, "usdc")]
+        );
+
+        let res = query_deposits(deps.as_ref(), Some(1), Some(1)).unwrap();
+        assert_eq!(res.deposits.len(), 1);
+        assert_eq!(res.deposits[0].id, 2);
+    }
+    #[test]
+    fn test_query_voting_power_at_height() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let info = mock_info("anyone", &[]);
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let start = env.block.height;
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr1", &[Coin::new(100u128, "watt")]),
+            ExecuteMsg::Stake {},
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr2", &[Coin::new(50u128, "watt")]),
+            ExecuteMsg::Stake {},
+        )
+        .unwrap();
+
+        env.block.height += 5;
+        env.block.time = env.block.time.plus_seconds(30);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr1", &[]),
+            ExecuteMsg::Unstake {
+                amount: Uint128::new(40),
+                denom: None,
+            },
+        )
+        .unwrap();
+
+        // Powers are those at the start of the block, changes count from the next block
+        let power = |height: u64| {
+            query_voting_power_at_height(
+                deps.as_ref(),
+                env.clone(),
+                "addr1".to_string(),
+                Some(height),
+            )
+            .unwrap()
+            .power
+        };
+        assert_eq!(power(start), Uint128::zero());
+        assert_eq!(power(start + 1), Uint128::new(100));
+        assert_eq!(power(start + 5), Uint128::new(100));
+        assert_eq!(power(start + 6), Uint128::new(60));
+
+        let total = |height: u64| {
+            query_total_power_at_height(deps.as_ref(), env.clone(), Some(height))
+                .unwrap()
+                .power
+        };
+        assert_eq!(total(start), Uint128::zero());
+        assert_eq!(total(start + 1), Uint128::new(150));
+        assert_eq!(total(start + 6), Uint128::new(110));
+
+        // The current height is used by default
+        let res = query_total_power_at_height(deps.as_ref(), env.clone(), None).unwrap();
+        assert_eq!(res.height, env.block.height);
+        assert_eq!(res.power, Uint128::new(150));
+
+        let res = query_info(deps.as_ref()).unwrap();
+        assert_eq!(res.info.contract, crate::CONTRACT_NAME);
+    }
+    #[test]
+    fn test_query_unbondings() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = mock_info("anyone", &[]);
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        for id in 1..=3u64 {
+            UNBONDINGS
+                .save(
+                    deps.as_mut().storage,
+                    (Addr::unchecked("addr0000"), id),
+                    &UnbondingEntry {
+                        amount: Uint128::from(id * 100),
+                        release_at: 1_600_000_000 + id,
+                        denom: None,
+                    },
+                )
+                .unwrap();
+        }
+        UNBONDINGS
+            .save(
+                deps.as_mut().storage,
+                (Addr::unchecked("addr0001"), 4),
+                &UnbondingEntry {
+                    amount: Uint128::from(400u128),
+                    release_at: 1_600_000_004,
+                    denom: None,
+                },
+            )
+            .unwrap();
+
+        let res = query_unbondings(deps.as_ref(), "addr0000".to_string(), None, Some(2)).unwrap();
+        assert_eq!(res.unbondings.len(), 2);
+        assert_eq!(res.unbondings[0].id, 1);
+        assert_eq!(res.unbondings[1].amount, Uint128::from(200u128));
+
+        let res = query_unbondings(deps.as_ref(), "addr0000".to_string(), Some(2), None).unwrap();
+        assert_eq!(
+            res.unbondings,
+            vec![UnbondingResponse {
+                id: 3,
+                amount: Uint128::from(300u128),
+                release_at: 1_600_000_003,
+                denom: "watt".to_string(),
+            }]
+        );
+    }
+    #[test]
+    fn test_query_locks() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = mock_info("anyone", &[]);
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![LockTier {
+                    duration: 86400 * 30,
+                    multiplier: Decimal::percent(150),
+                    early_unlock_penalty: Decimal::percent(5),
+                }],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        for amount in [100u128, 200] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("addr0000", &[Coin::new(amount, "watt")]),
+                ExecuteMsg::Lock {
+                    duration: 86400 * 30,
+                },
+            )
+            .unwrap();
+        }
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr0000", &[Coin::new(50u128, "watt")]),
+            ExecuteMsg::Stake {},
+        )
+        .unwrap();
+
+        let res = query_locks(deps.as_ref(), "addr0000".to_string(), None, None).unwrap();
+        assert_eq!(res.locks.len(), 2);
+        assert_eq!(res.locks[0].id, 1);
+        assert_eq!(res.locks[0].lock.amount, Uint128::new(100));
+        assert_eq!(res.locks[0].lock.boost, Uint128::new(50));
+        assert_eq!(res.locks[0].weight, Uint128::new(150));
+        assert_eq!(
+            res.locks[1].lock.unlock_time,
+            env.block.time.seconds() + 86400 * 30
+        );
+        assert_eq!(res.weight, Uint128::new(500));
+
+        let res = query_locks(deps.as_ref(), "addr0000".to_string(), Some(1), None).unwrap();
+        assert_eq!(res.locks.len(), 1);
+        assert_eq!(res.locks[0].weight, Uint128::new(300));
+
+        let res = query_locks(deps.as_ref(), "addr0001".to_string(), None, None).unwrap();
+        assert!(res.locks.is_empty());
+        assert_eq!(res.weight, Uint128::zero());
+    }
+    #[test]
+    fn test_query_withdraw_address() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        let res = query_withdraw_address(deps.as_ref(), "addr0000".to_string()).unwrap();
+        assert_eq!(res.withdraw_address, Addr::unchecked("addr0000"));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr0000", &[]),
+            ExecuteMsg::SetWithdrawAddress {
+                address: Some("addr0001".to_string()),
+            },
+        )
+        .unwrap();
+        let res = query_withdraw_address(deps.as_ref(), "addr0000".to_string()).unwrap();
+        assert_eq!(res.withdraw_address, Addr::unchecked("addr0001"));
+
+        execute(
+            deps.as_mut(),
+            env,
+            mock_info("addr0000", &[]),
+            ExecuteMsg::SetWithdrawAddress { address: None },
+        )
+        .unwrap();
+        let res = query_withdraw_address(deps.as_ref(), "addr0000".to_string()).unwrap();
+        assert_eq!(res.withdraw_address, Addr::unchecked("addr0000"));
+    }
+    #[test]
+    fn test_query_pools() {
+        let mut deps = mock_dependencies();
+        // The minter mints watt and knows every project
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Raw { .. } => {
+                SystemResult::Ok(ContractResult::Ok(Binary::from(br#""watt""#.as_slice())))
+            }
+            _ => SystemResult::Ok(ContractResult::Ok(Binary::from(
+                br#"{"name":"Solar park","minted_wattpeak_count":5000}"#.as_slice(),
+            ))),
+        });
+        let mut env = mock_env();
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: Some(Addr::unchecked("minter")),
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        instantiate(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+        for (project_id, rewards_percentage) in
+            [(1, Decimal::percent(10)), (2, Decimal::percent(20))]
+        {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[]),
+                ExecuteMsg::CreatePool {
+                    project_id,
+                    rewards_percentage,
+                },
+            )
+            .unwrap();
+        }
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr0000", &[Coin::new(1000u128, "watt")]),
+            ExecuteMsg::StakeInPool { project_id: 1 },
+        )
+        .unwrap();
+
+        env.block.time = env.block.time.plus_seconds(SECONDS_PER_YEAR);
+        let res = query_pools(deps.as_ref(), env.clone(), None, Some(1)).unwrap();
+        assert_eq!(res.pools.len(), 1);
+        assert_eq!(res.pools[0].project_id, 1);
+        assert_eq!(
+            res.pools[0].total_interest,
+            Decimal::from_atomics(100u128, 0).unwrap()
+        );
+        let res = query_pools(deps.as_ref(), env.clone(), Some(1), None).unwrap();
+        assert_eq!(res.pools.len(), 1);
+        assert_eq!(res.pools[0].project_id, 2);
+        assert_eq!(res.pools[0].rewards_percentage, Decimal::percent(20));
+
+        let pool = query_pool(deps.as_ref(), env.clone(), 1).unwrap();
+        assert_eq!(pool.total_staked, Uint128::new(1000));
+        assert_eq!(
+            query_pool(deps.as_ref(), env.clone(), 3).unwrap_err(),
+            ContractError::PoolNotFound { project_id: 3 }
+        );
+
+        let stake =
+            query_pool_stake(deps.as_ref(), env.clone(), 1, "addr0000".to_string()).unwrap();
+        assert_eq!(stake.wattpeak_staked, Uint128::new(1000));
+        assert_eq!(
+            stake.interest_wattpeak,
+            Decimal::from_atomics(100u128, 0).unwrap()
+        );
+        let stake = query_pool_stake(deps.as_ref(), env, 1, "addr0001".to_string()).unwrap();
+        assert_eq!(stake.wattpeak_staked, Uint128::zero());
+        assert_eq!(stake.interest_wattpeak, Decimal::zero());
+    }
+    #[test]
+    fn test_query_solvency() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        instantiate(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("admin", &[Coin::new(100u128, "watt")]),
+            ExecuteMsg::FundReserve {},
+        )
+        .unwrap();
+
+        // Nothing accrues while nothing is staked
+        let res = query_solvency(deps.as_ref(), env.clone()).unwrap();
+        assert_eq!(res.reserve, Uint128::new(100));
+        assert_eq!(res.surplus, Decimal::percent(10000));
+        assert_eq!(res.funded_until, None);
+
+        // 1000 staked at 10% promise 100 a year, which the reserve pays for a year
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr0000", &[Coin::new(1000u128, "watt")]),
+            ExecuteMsg::Stake {},
+        )
+        .unwrap();
+        let funded_until = env.block.time.seconds() + SECONDS_PER_YEAR;
+        let res = query_solvency(deps.as_ref(), env.clone()).unwrap();
+        assert_eq!(res.funded_until, Some(funded_until));
+
+        env.block.time = env.block.time.plus_seconds(SECONDS_PER_YEAR / 2);
+        let res = query_solvency(deps.as_ref(), env.clone()).unwrap();
+        assert_eq!(res.promised, Decimal::percent(5000));
+        assert_eq!(res.surplus, Decimal::percent(5000));
+        assert_eq!(res.shortfall, Decimal::zero());
+        assert_eq!(res.funded_until, Some(funded_until));
+
+        env.block.time = env.block.time.plus_seconds(SECONDS_PER_YEAR);
+        let res = query_solvency(deps.as_ref(), env.clone()).unwrap();
+        assert_eq!(res.promised, Decimal::percent(15000));
+        assert_eq!(res.surplus, Decimal::zero());
+        assert_eq!(res.shortfall, Decimal::percent(5000));
+        assert_eq!(res.funded_until, Some(env.block.time.seconds()));
+        assert!(!res.cap_interest_to_reserve);
+    }
+    #[test]
+    fn test_query_rate_schedule() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let start = env.block.time.seconds();
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        instantiate(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+
+        env.block.time = env.block.time.plus_seconds(100);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("admin", &[]),
+            ExecuteMsg::UpdateConfig {
+                rewards_percentage: Some(Decimal::percent(15)),
+                epoch_length: None,
+                unbonding_period: None,
+                reward_denoms: None,
+                lock_tiers: None,
+                early_unstake_fee: None,
+                loyalty_tiers: None,
+                minter_address: None,
+                cap_interest_to_reserve: None,
+                reward_expiry: None,
+                guardian: None,
+                wattpeak_denom: None,
+                staking_fee_percentage: None,
+                staking_fee_address: None,
+                bridged_denoms: None,
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("admin", &[]),
+            ExecuteMsg::ScheduleRateChange {
+                rewards_percentage: Decimal::percent(20),
+                start: RateChangeStart::Time(start + 1000),
+            },
+        )
+        .unwrap();
+
+        let res = query_rate_schedule(deps.as_ref(), None, None).unwrap();
+        assert_eq!(
+            res.changes,
+            vec![
+                RateChange {
+                    time: start,
+                    rewards_percentage: Decimal::percent(10),
+                },
+                RateChange {
+                    time: start + 100,
+                    rewards_percentage: Decimal::percent(15),
+                },
+                RateChange {
+                    time: start + 1000,
+                    rewards_percentage: Decimal::percent(20),
+                },
+            ]
+        );
+        let res = query_rate_schedule(deps.as_ref(), Some(start), Some(1)).unwrap();
+        assert_eq!(res.changes.len(), 1);
+        assert_eq!(res.changes[0].time, start + 100);
+    }
+    #[test]
+    fn test_query_reward_streams() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let now = env.block.time.seconds();
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        instantiate(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+        for amount in [1000u128, 500u128] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(amount, "watt")]),
+                ExecuteMsg::DepositRewardStream {
+                    start: now,
+                    end: now + 86400,
+                },
+            )
+            .unwrap();
+        }
+
+        let res = query_reward_streams(deps.as_ref(), None, Some(1)).unwrap();
+        assert_eq!(res.streams.len(), 1);
+        assert_eq!(res.streams[0].id, 1);
+        assert_eq!(res.streams[0].remaining, Uint128::new(1000));
+        assert_eq!(res.remaining, vec![Coin::new(1500u128, "watt")]);
+
+        let res = query_reward_streams(deps.as_ref(), Some(1), None).unwrap();
+        assert_eq!(res.streams.len(), 1);
+        assert_eq!(res.streams[0].id, 2);
+        assert_eq!(res.streams[0].stream.amount, Uint128::new(500));
+    }
+    #[test]
+    fn test_query_reward_buckets() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: Some(RewardExpiry {
+                    epochs: 2,
+                    treasury: Addr::unchecked("treasury"),
+                }),
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        instantiate(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr1", &[Coin::new(1000u128, "watt")]),
+            ExecuteMsg::Stake {},
+        )
+        .unwrap();
+
+        // Rewards settled in epochs 1 and 2 go into a bucket each
+        for _ in 0..2 {
+            env.block.time = env.block.time.plus_seconds(43200);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("admin", &[Coin::new(100u128, "watt")]),
+                ExecuteMsg::DepositRewards {},
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("anyone", &[]),
+                ExecuteMsg::SweepExpired { limit: None },
+            )
+            .unwrap();
+            env.block.time = env.block.time.plus_seconds(43200);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("anyone", &[]),
+                ExecuteMsg::NewEpoch {},
+            )
+            .unwrap();
+        }
+
+        let res = query_reward_buckets(deps.as_ref(), env.clone(), "addr1".to_string()).unwrap();
+        assert_eq!(res.buckets.len(), 2);
+        assert_eq!(res.buckets[0].bucket.epoch, 1);
+        assert_eq!(res.buckets[0].expires_at_epoch, 4);
+        assert_eq!(res.buckets[0].expires_at, env.block.time.seconds() + 86400);
+        assert_eq!(res.buckets[1].bucket.epoch, 2);
+        assert_eq!(res.buckets[1].expires_at_epoch, 5);
+        assert_eq!(
+            res.buckets[1].expires_at,
+            env.block.time.seconds() + 2 * 86400
+        );
+
+        // A claim takes the rewards of the oldest bucket first
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr1", &[]),
+            ExecuteMsg::ClaimReward {
+                recipient: None,
+                ibc: None,
+            },
+        )
+        .unwrap();
+        let res = query_reward_buckets(deps.as_ref(), env, "addr1".to_string()).unwrap();
+        assert_eq!(res.buckets.len(), 1);
+        assert_eq!(res.buckets[0].bucket.epoch, 2);
+        assert!(res.buckets[0].bucket.rewards < Decimal::one());
+    }
+    #[test]
+    fn test_query_pause_info() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        instantiate(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+        let res = query_pause_info(deps.as_ref()).unwrap();
+        assert_eq!(res.flags, PauseFlags::default());
+        assert_eq!(res.emergency_time, None);
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("admin", &[]),
+            ExecuteMsg::SetPause {
+                staking: None,
+                claiming: Some(true),
+                epochs: None,
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("admin", &[]),
+            ExecuteMsg::EnableEmergencyMode {},
+        )
+        .unwrap();
+
+        let res = query_pause_info(deps.as_ref()).unwrap();
+        assert_eq!(
+            res.flags,
+            PauseFlags {
+                staking: false,
+                claiming: true,
+                epochs: false,
+            }
+        );
+        assert_eq!(res.emergency_time, Some(env.block.time.seconds()));
+    }
+
+    #[test]
+    fn test_query_pending_admin() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec![],
+            },
+        };
+        instantiate(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+        assert_eq!(query_pending_admin(deps.as_ref()).unwrap().admin, None);
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("admin", &[]),
+            ExecuteMsg::ProposeAdmin {
+                admin: "new_admin".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            query_pending_admin(deps.as_ref()).unwrap().admin,
+            Some(Addr::unchecked("new_admin"))
+        );
+    }
+
+    #[test]
+    fn test_query_staked_denoms() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let msg = InstantiateMsg {
+            config: Config {
+                admin: Addr::unchecked("admin"),
+                rewards_percentage: Decimal::percent(10),
+                epoch_length: 86400,
+                wattpeak_denom: "watt".to_string(),
+                staking_fee_address: Addr::unchecked("staking_fee_address"),
+                staking_fee_percentage: Decimal::percent(5),
+                unbonding_period: 0,
+                reward_denoms: vec![],
+                lock_tiers: vec![],
+                early_unstake_fee: None,
+                loyalty_tiers: vec![],
+                minter_address: None,
+                cap_interest_to_reserve: false,
+                reward_expiry: None,
+                guardian: None,
+                bridged_denoms: vec!["ibc/WATTPEAK".to_string()],
+            },
+        };
+        instantiate(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+        for (address, coin) in [
+            ("addr0000", Coin::new(1000u128, "watt")),
+            ("addr0000", Coin::new(300u128, "ibc/WATTPEAK")),
+            ("addr0001", Coin::new(200u128, "ibc/WATTPEAK")),
+        ] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(address, &[coin]),
+                ExecuteMsg::Stake {},
+            )
+            .unwrap();
+        }
+
+        let res = query_staked_denoms(deps.as_ref(), Some("addr0000".to_string())).unwrap();
+        assert_eq!(
+            res.stake,
+            vec![
+                Coin::new(1000u128, "watt"),
+                Coin::new(300u128, "ibc/WATTPEAK")
+            ]
+        );
+        let res = query_staked_denoms(deps.as_ref(), None).unwrap();
+        assert_eq!(
+            res.stake,
+            vec![
+                Coin::new(1000u128, "watt"),
+                Coin::new(500u128, "ibc/WATTPEAK")
+            ]
+        );
+    }
 }
@@ -1,6 +1,10 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Decimal, Deps, StdError, StdResult, Uint128};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{Addr, Coin, Decimal, Deps, Empty, StdResult, Uint128};
+use cw_storage_plus::{
+    Index, IndexList, IndexedMap, Item, Map, MultiIndex, SnapshotItem, SnapshotMap, Strategy,
+};
+
+use crate::error::ContractError;
 
 #[cw_serde]
 pub struct Config {
@@ -11,19 +15,182 @@ pub struct Config {
     pub wattpeak_denom: String,
     pub staking_fee_percentage: Decimal,
     pub staking_fee_address: Addr,
+    // Time in seconds unstaked wattpeak stays locked before it can be withdrawn
+    #[serde(default)]
+    pub unbonding_period: u64,
+    // Denoms other than wattpeak_denom that are accepted as reward deposits
+    #[serde(default)]
+    pub reward_denoms: Vec<String>,
+    // Durations wattpeak can be locked for, with the reward multiplier of each
+    #[serde(default)]
+    pub lock_tiers: Vec<LockTier>,
+    // Fee paid on wattpeak unstaked shortly after it was staked
+    #[serde(default)]
+    pub early_unstake_fee: Option<EarlyUnstakeFee>,
+    // Staking durations after which the stake earns interest with a loyalty multiplier
+    #[serde(default)]
+    pub loyalty_tiers: Vec<LoyaltyTier>,
+    // Minter the projects of the staking pools are checked against
+    #[serde(default)]
+    pub minter_address: Option<Addr>,
+    // Interest stops accruing once the promised interest reaches the reward reserve
+    #[serde(default)]
+    pub cap_interest_to_reserve: bool,
+    // Rewards left unclaimed for too long expire and are swept to a treasury
+    #[serde(default)]
+    pub reward_expiry: Option<RewardExpiry>,
+    // Address that can pause the contract and enter emergency mode along with the admin
+    #[serde(default)]
+    pub guardian: Option<Addr>,
+    // IBC-bridged wattpeak denoms staked as wattpeak next to wattpeak_denom. The stake in each
+    // denom is tracked separately and unstaked in the denom it was staked in
+    #[serde(default)]
+    pub bridged_denoms: Vec<String>,
+}
+
+/// LockTier is a duration wattpeak can be locked for. Locked wattpeak earns interest as if
+/// `multiplier` times the amount was staked, and can only be unlocked before the duration ends
+/// by paying `early_unlock_penalty` of the amount to the staking fee address
+#[cw_serde]
+pub struct LockTier {
+    // Lock duration in seconds
+    pub duration: u64,
+    pub multiplier: Decimal,
+    pub early_unlock_penalty: Decimal,
+}
+
+/// EarlyUnstakeFee is paid to the staking fee address on wattpeak unstaked within `period`
+/// seconds of the stake start time. The fee declines linearly from `percentage` of the amount
+/// when the stake starts to nothing once the period has passed
+#[cw_serde]
+pub struct EarlyUnstakeFee {
+    pub period: u64,
+    pub percentage: Decimal,
+}
+
+/// LoyaltyTier is a duration of uninterrupted staking after which the whole stake earns interest
+/// as if `multiplier` times the amount was staked. The tier with the longest duration reached applies
+#[cw_serde]
+pub struct LoyaltyTier {
+    // Staking duration in seconds
+    pub duration: u64,
+    pub multiplier: Decimal,
+}
+
+/// RewardExpiry is the claim window of rewards. Rewards settled into a staker's record during an
+/// epoch can be claimed for `epochs` full epochs after it, then they expire and anyone can sweep
+/// them to `treasury`
+#[cw_serde]
+pub struct RewardExpiry {
+    pub epochs: u64,
+    pub treasury: Addr,
 }
 
 impl Config {
-    pub fn validate(&self, deps: Deps) -> StdResult<()> {
-        deps.api.addr_validate(&self.admin.to_string())?;
+    /// Whether `sender` can pause the contract, the admin or the guardian
+    pub fn can_pause(&self, sender: &Addr) -> bool {
+        *sender == self.admin || self.guardian.as_ref() == Some(sender)
+    }
 
+    pub fn validate(&self, deps: Deps) -> Result<(), ContractError> {
+        deps.api.addr_validate(self.admin.as_str())?;
+        deps.api.addr_validate(self.staking_fee_address.as_str())?;
+        if let Some(minter_address) = &self.minter_address {
+            deps.api.addr_validate(minter_address.as_str())?;
+        }
+        if let Some(guardian) = &self.guardian {
+            deps.api.addr_validate(guardian.as_str())?;
+        }
+        if let Some(expiry) = &self.reward_expiry {
+            deps.api.addr_validate(expiry.treasury.as_str())?;
+            if expiry.epochs == 0 {
+                return Err(ContractError::RewardExpiryEpochsZero {});
+            }
+        }
+
+        if self.wattpeak_denom.is_empty() {
+            return Err(ContractError::WattpeakDenomEmpty {});
+        }
         if self.epoch_length == 0 {
-            return Err(StdError::generic_err("epoch_length cannot be zero"));
+            return Err(ContractError::EpochLengthZero {});
+        }
+        if self.staking_fee_percentage > MAX_STAKING_FEE_PERCENTAGE {
+            return Err(ContractError::StakingFeeTooHigh {
+                max: MAX_STAKING_FEE_PERCENTAGE,
+            });
         }
         if self.rewards_percentage > Decimal::percent(100) {
-            return Err(StdError::generic_err(
-                "rewards_percentage cannot be greater than 100%",
-            ));
+            return Err(ContractError::RewardsPercentageTooHigh {});
+        }
+        for (i, denom) in self.reward_denoms.iter().enumerate() {
+            if denom.is_empty() || *denom == self.wattpeak_denom {
+                return Err(ContractError::InvalidRewardDenom {
+                    denom: denom.to_string(),
+                });
+            }
+            if self.reward_denoms[..i].contains(denom) {
+                return Err(ContractError::DuplicateRewardDenom {
+                    denom: denom.to_string(),
+                });
+            }
+        }
+        for (i, denom) in self.bridged_denoms.iter().enumerate() {
+            if denom.is_empty()
+                || *denom == self.wattpeak_denom
+                || self.reward_denoms.contains(denom)
+            {
+                return Err(ContractError::InvalidBridgedDenom {
+                    denom: denom.to_string(),
+                });
+            }
+            if self.bridged_denoms[..i].contains(denom) {
+                return Err(ContractError::DuplicateBridgedDenom {
+                    denom: denom.to_string(),
+                });
+            }
+        }
+        for (i, tier) in self.lock_tiers.iter().enumerate() {
+            if tier.duration == 0 {
+                return Err(ContractError::LockDurationZero {});
+            }
+            if self.lock_tiers[..i]
+                .iter()
+                .any(|other| other.duration == tier.duration)
+            {
+                return Err(ContractError::DuplicateLockDuration {
+                    duration: tier.duration,
+                });
+            }
+            if tier.multiplier < Decimal::one() {
+                return Err(ContractError::LockMultiplierTooLow {});
+            }
+            if tier.early_unlock_penalty > Decimal::percent(100) {
+                return Err(ContractError::EarlyUnlockPenaltyTooHigh {});
+            }
+        }
+        if let Some(fee) = &self.early_unstake_fee {
+            if fee.period == 0 {
+                return Err(ContractError::EarlyUnstakeFeePeriodZero {});
+            }
+            if fee.percentage > Decimal::percent(100) {
+                return Err(ContractError::EarlyUnstakeFeeTooHigh {});
+            }
+        }
+        for (i, tier) in self.loyalty_tiers.iter().enumerate() {
+            if tier.duration == 0 {
+                return Err(ContractError::LoyaltyDurationZero {});
+            }
+            if self.loyalty_tiers[..i]
+                .iter()
+                .any(|other| other.duration == tier.duration)
+            {
+                return Err(ContractError::DuplicateLoyaltyDuration {
+                    duration: tier.duration,
+                });
+            }
+            if tier.multiplier < Decimal::one() {
+                return Err(ContractError::LoyaltyMultiplierTooLow {});
+            }
         }
         Ok(())
     }
@@ -34,8 +201,45 @@ pub struct Staker {
     pub wattpeak_staked: Uint128,
     // Total amount of interest earned in interest_wattpeak, represents a pro rata fraction of total wattpeak interest earned
     pub interest_wattpeak: Decimal,
+    // Start of the uninterrupted stake, top-ups move it to the start time weighted by amount
     pub stake_start_time: u64,
     pub claimable_rewards: Decimal,
+    // Value of INTEREST_INDEX the last time the staker was settled
+    pub interest_index: Decimal,
+    // Reward round the staker was last settled in
+    pub reward_round: u64,
+    // Whether claimable_rewards are restaked whenever the staker is settled by an execute message
+    #[serde(default)]
+    pub auto_compound: bool,
+    // Rewards claimable in the whitelisted reward denoms
+    #[serde(default)]
+    pub claimable_denom_rewards: Vec<DenomReward>,
+    // Part of wattpeak_staked that is locked
+    #[serde(default)]
+    pub locked_wattpeak: Uint128,
+    // Weight the multipliers of the staker's locks add to wattpeak_staked when interest is earned
+    #[serde(default)]
+    pub lock_boost: Uint128,
+    // Weight the loyalty multiplier reached adds to wattpeak_staked when interest is earned
+    #[serde(default)]
+    pub loyalty_boost: Uint128,
+    // Time at which the staker reaches the next loyalty tier, zero if there is none
+    #[serde(default)]
+    pub loyalty_update_time: u64,
+    // Claimable rewards by the epoch they were settled in, oldest first. Only kept while rewards
+    // expire
+    #[serde(default)]
+    pub reward_buckets: Vec<RewardBucket>,
+}
+
+impl Staker {
+    /// Weight the staker earns interest on, the stake boosted by its locks and loyalty
+    pub fn weight(&self) -> StdResult<Uint128> {
+        Ok(self
+            .wattpeak_staked
+            .checked_add(self.lock_boost)?
+            .checked_add(self.loyalty_boost)?)
+    }
 }
 
 impl Default for Staker {
@@ -45,19 +249,375 @@ impl Default for Staker {
             interest_wattpeak: Decimal::zero(),
             stake_start_time: 0,
             claimable_rewards: Decimal::zero(),
+            interest_index: Decimal::zero(),
+            reward_round: 0,
+            auto_compound: false,
+            claimable_denom_rewards: vec![],
+            locked_wattpeak: Uint128::zero(),
+            lock_boost: Uint128::zero(),
+            loyalty_boost: Uint128::zero(),
+            loyalty_update_time: 0,
+            reward_buckets: vec![],
         }
     }
 }
 
+/// RewardBucket is the part of a staker's claimable rewards that was settled during an epoch.
+/// Claims and restakes take rewards from the oldest buckets first
+#[cw_serde]
+pub struct RewardBucket {
+    pub epoch: u64,
+    pub rewards: Decimal,
+    pub denom_rewards: Vec<DenomReward>,
+}
+
+/// DenomReward is an amount of rewards in one of the whitelisted reward denoms
+#[cw_serde]
+pub struct DenomReward {
+    pub denom: String,
+    pub amount: Decimal,
+}
+
+/// UnbondingEntry is wattpeak that has been unstaked and can be withdrawn once released
+#[cw_serde]
+pub struct UnbondingEntry {
+    pub amount: Uint128,
+    // Time in seconds at which the entry can be withdrawn
+    pub release_at: u64,
+    // Bridged denom the entry is paid in, none for wattpeak_denom
+    #[serde(default)]
+    pub denom: Option<String>,
+}
+
+/// LockPosition is wattpeak locked by a staker until unlock_time
+#[cw_serde]
+pub struct LockPosition {
+    pub amount: Uint128,
+    pub multiplier: Decimal,
+    pub early_unlock_penalty: Decimal,
+    // Weight added to the staker's stake by the multiplier
+    pub boost: Uint128,
+    pub start_time: u64,
+    pub unlock_time: u64,
+}
+
+/// RewardStream is a reward deposit released linearly into the reward distribution between its
+/// start and end time
+#[cw_serde]
+pub struct RewardStream {
+    pub denom: String,
+    pub amount: Uint128,
+    // Amount released into the reward distribution so far
+    pub released: Uint128,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Pool is a staking pool of a minter project. Wattpeak staked in a pool earns interest at the
+/// rate of the pool and shares the rewards deposited for the project, apart from the stake and
+/// rewards of the contract
+#[cw_serde]
+pub struct Pool {
+    pub project_id: u64,
+    // Name of the project in the minter
+    pub name: String,
+    pub rewards_percentage: Decimal,
+    pub total_staked: Uint128,
+    // Cumulative interest_wattpeak earned per wattpeak staked in the pool
+    pub interest_index: Decimal,
+    // Time in seconds up to which interest_index has been accrued
+    pub last_accrual_time: u64,
+    // Interest wattpeak accrued in the pool since the last reward deposit
+    pub total_interest: Decimal,
+    // Id of the reward round of the pool currently accruing interest
+    pub reward_round: u64,
+}
+
+/// PoolStake is the wattpeak an address staked in a pool, settled like a staker record
+#[cw_serde]
+pub struct PoolStake {
+    pub wattpeak_staked: Uint128,
+    pub interest_wattpeak: Decimal,
+    pub interest_index: Decimal,
+    pub reward_round: u64,
+    pub claimable_rewards: Decimal,
+}
+
+/// LegacyStaker is the staker record stored before the reward index was introduced,
+/// it is only used to migrate existing records
+#[cw_serde]
+pub struct LegacyStaker {
+    pub wattpeak_staked: Uint128,
+    pub interest_wattpeak: Decimal,
+    pub stake_start_time: u64,
+    pub claimable_rewards: Decimal,
+}
+
+/// RewardRound is written every time rewards are deposited, closing the round of interest
+/// that the deposit is distributed over
+#[cw_serde]
+pub struct RewardRound {
+    // Value of INTEREST_INDEX when the round was closed
+    pub interest_index: Decimal,
+    // Rewards paid out per interest_wattpeak accrued during the round
+    pub reward_per_interest: Decimal,
+    // Cumulative rewards per staked wattpeak up to and including this round
+    pub reward_index: Decimal,
+    // The same values for every whitelisted reward denom deposited up to and including this round
+    #[serde(default)]
+    pub denom_rewards: Vec<DenomRewardIndex>,
+}
+
+/// DenomRewardIndex holds the reward values of a round for one of the whitelisted reward denoms
+#[cw_serde]
+pub struct DenomRewardIndex {
+    pub denom: String,
+    pub reward_per_interest: Decimal,
+    pub reward_index: Decimal,
+}
+
+/// EpochRecord keeps what happened during an epoch for accounting. The record of the epoch in
+/// progress is updated as interest accrues and rewards are deposited or paid out
+#[cw_serde]
+#[derive(Default)]
+pub struct EpochRecord {
+    // Time in seconds at which the epoch started
+    pub start_time: u64,
+    // Time in seconds at which the epoch ended, zero while the epoch is in progress
+    pub end_time: u64,
+    // Number of epochs covered by the record, more than one when NewEpoch caught up on missed epochs
+    pub epochs: u64,
+    // Total wattpeak staked and the rewards percentage when the epoch ended
+    pub total_wattpeak_staked: Uint128,
+    pub rewards_percentage: Decimal,
+    // Interest wattpeak accrued by all stakers during the epoch
+    pub interest_wattpeak: Decimal,
+    pub rewards_deposited: Vec<Coin>,
+    pub staking_fees: Vec<Coin>,
+}
+
+/// DepositRecord keeps a single DepositRewards call for accounting, so deposits can be reconciled
+/// one by one next to the totals of their epoch
+#[cw_serde]
+pub struct DepositRecord {
+    // Epoch in progress when the rewards were deposited
+    pub epoch: u64,
+    // Time in seconds at which the rewards were deposited
+    pub time: u64,
+    // Total wattpeak staked and the rewards percentage at the time of the deposit
+    pub total_wattpeak_staked: Uint128,
+    pub rewards_percentage: Decimal,
+    // Interest wattpeak accrued by all stakers since the last reward round, the deposit is shared
+    // over it
+    pub interest_wattpeak: Decimal,
+    pub rewards_deposited: Vec<Coin>,
+}
+
 /// CONFIG is the configuration of the contract
 pub const CONFIG: Item<Config> = Item::new("config");
 
-pub const STAKERS: Map<Addr, Staker> = Map::new("stakers");
+/// MAX_STAKING_FEE_PERCENTAGE is the highest staking fee the admin can set
+pub const MAX_STAKING_FEE_PERCENTAGE: Decimal = Decimal::percent(10);
 
-pub const TOTAL_WATTPEAK_STAKED: Item<Uint128> = Item::new("total_wattpeak_in_contract");
+/// MINTER_FULL_DENOM_KEY is the storage key the wattpeak minter keeps the denom it mints under
+pub const MINTER_FULL_DENOM_KEY: &str = "token_full_denom";
 
-pub const TOTAL_INTEREST_WATTPEAK: Item<Decimal> = Item::new("total_interest_wattpeak_in_contract");
+/// PENDING_ADMIN is the address the admin proposed to hand the contract over to, it becomes the
+/// admin once it accepts
+pub const PENDING_ADMIN: Item<Addr> = Item::new("pending_admin");
+
+pub struct StakerIndexes<'a> {
+    // Stakers by the amount of wattpeak they have staked
+    pub wattpeak_staked: MultiIndex<'a, u128, Staker, Addr>,
+}
 
-pub const PERCENTAGE_OF_YEAR: Item<Decimal> = Item::new("percentage_of_year");
+impl<'a> IndexList<Staker> for StakerIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Staker>> + '_> {
+        let v: Vec<&dyn Index<Staker>> = vec![&self.wattpeak_staked];
+        Box::new(v.into_iter())
+    }
+}
+
+/// stakers holds every staker record, indexed by staker address and by the amount staked
+pub fn stakers<'a>() -> IndexedMap<'a, Addr, Staker, StakerIndexes<'a>> {
+    let indexes = StakerIndexes {
+        wattpeak_staked: MultiIndex::new(
+            |_pk, staker| staker.wattpeak_staked.u128(),
+            "stakers",
+            "stakers__wattpeak_staked",
+        ),
+    };
+    IndexedMap::new("stakers", indexes)
+}
+
+/// STAKER_COUNT is the number of staker records
+pub const STAKER_COUNT: Item<u64> = Item::new("staker_count");
+
+pub const LEGACY_STAKERS: Map<Addr, LegacyStaker> = Map::new("stakers");
+
+/// UNBONDINGS holds the unbonding entries of every staker, indexed by staker address and entry id
+pub const UNBONDINGS: Map<(Addr, u64), UnbondingEntry> = Map::new("unbondings");
+
+/// UNBONDING_COUNT is the number of unbonding entries ever created, used as the next entry id
+pub const UNBONDING_COUNT: Item<u64> = Item::new("unbonding_count");
+
+/// BRIDGED_STAKES holds the part of the stake of every staker staked in a bridged denom, indexed
+/// by staker address and denom. The rest of its stake is in wattpeak_denom
+pub const BRIDGED_STAKES: Map<(Addr, &str), Uint128> = Map::new("bridged_stakes");
+
+/// TOTAL_BRIDGED_STAKED is the total amount staked in each bridged denom
+pub const TOTAL_BRIDGED_STAKED: Map<&str, Uint128> = Map::new("total_bridged_staked");
+
+/// TOTAL_WATTPEAK_STAKED is the total amount of wattpeak staked, snapshotted every block it
+/// changes so the total voting power can be queried at a height. Wattpeak staked in the pools is
+/// left out, it earns the interest of its pool instead
+pub const TOTAL_WATTPEAK_STAKED: SnapshotItem<Uint128> = SnapshotItem::new(
+    "total_wattpeak_in_contract",
+    "total_wattpeak_in_contract__checkpoints",
+    "total_wattpeak_in_contract__changelog",
+    Strategy::EveryBlock,
+);
+
+/// STAKED_BALANCES mirrors the wattpeak staked by every staker, snapshotted every block it changes
+/// so the voting power of a staker can be queried at a height
+pub const STAKED_BALANCES: SnapshotMap<&Addr, Uint128> = SnapshotMap::new(
+    "staked_balances",
+    "staked_balances__checkpoints",
+    "staked_balances__changelog",
+    Strategy::EveryBlock,
+);
+
+/// LOCKS holds the lock positions of every staker, indexed by staker address and lock id
+pub const LOCKS: Map<(Addr, u64), LockPosition> = Map::new("locks");
+
+/// LOCK_EXPIRIES queues the lock positions by unlock time and lock id, to be released in order
+/// as interest accrues
+pub const LOCK_EXPIRIES: Map<(u64, u64), Addr> = Map::new("lock_expiries");
+
+/// LOCK_COUNT is the number of lock positions ever created, used as the next lock id
+pub const LOCK_COUNT: Item<u64> = Item::new("lock_count");
+
+/// TOTAL_LOCK_BOOST is the weight the multipliers of all locks add to the total staked
+pub const TOTAL_LOCK_BOOST: Item<Uint128> = Item::new("total_lock_boost");
+
+/// LOYALTY_UPDATES queues the stakers by the time they reach their next loyalty tier, to be
+/// updated in order as interest accrues
+pub const LOYALTY_UPDATES: Map<(u64, Addr), Empty> = Map::new("loyalty_updates");
+
+/// TOTAL_LOYALTY_BOOST is the weight the loyalty multipliers of all stakers add to the total staked
+pub const TOTAL_LOYALTY_BOOST: Item<Uint128> = Item::new("total_loyalty_boost");
+
+pub const TOTAL_INTEREST_WATTPEAK: Item<Decimal> = Item::new("total_interest_wattpeak_in_contract");
 
 pub const EPOCH_COUNT: Item<u64> = Item::new("epoch_count");
+
+/// LAST_EPOCH_TIME is the time in seconds at which the last processed epoch ended
+pub const LAST_EPOCH_TIME: Item<u64> = Item::new("last_epoch_time");
+
+/// INTEREST_INDEX is the cumulative interest_wattpeak earned per staked wattpeak
+pub const INTEREST_INDEX: Item<Decimal> = Item::new("interest_index");
+
+/// LAST_ACCRUAL_TIME is the time in seconds up to which INTEREST_INDEX has been accrued
+pub const LAST_ACCRUAL_TIME: Item<u64> = Item::new("last_accrual_time");
+
+/// REWARD_ROUND is the id of the reward round currently accruing interest
+pub const REWARD_ROUND: Item<u64> = Item::new("reward_round");
+
+/// REWARD_ROUNDS holds every closed reward round, indexed by the round id
+pub const REWARD_ROUNDS: Map<u64, RewardRound> = Map::new("reward_rounds");
+
+/// RATE_SCHEDULE holds every change of the rewards percentage by the time in seconds it takes
+/// effect at. Changes that took effect are kept as the history of the rate
+pub const RATE_SCHEDULE: Map<u64, Decimal> = Map::new("rate_schedule");
+
+/// REWARD_RESERVE is the wattpeak funded in advance to pay the interest promised to stakers.
+/// The interest accrued during an epoch is paid from it when the epoch ends
+pub const REWARD_RESERVE: Item<Uint128> = Item::new("reward_reserve");
+
+/// REWARD_STREAMS holds the reward streams that have not been released in full, by stream id
+pub const REWARD_STREAMS: Map<u64, RewardStream> = Map::new("reward_streams");
+
+/// REWARD_STREAM_COUNT is the number of reward streams ever created, used as the next stream id
+pub const REWARD_STREAM_COUNT: Item<u64> = Item::new("reward_stream_count");
+
+/// REWARD_DUST holds, per denom, the rewards that were deposited but can no longer be paid out:
+/// deposits lost to rounding and the fractions left by stakers that closed their position
+pub const REWARD_DUST: Map<&str, Decimal> = Map::new("reward_dust");
+
+/// PauseFlags are the parts of the contract that are paused
+#[cw_serde]
+#[derive(Default)]
+pub struct PauseFlags {
+    // Staking, locking and transferring stake
+    pub staking: bool,
+    // Claiming and restaking rewards
+    pub claiming: bool,
+    // Processing epochs
+    pub epochs: bool,
+}
+
+pub const PAUSE_FLAGS: Item<PauseFlags> = Item::new("pause_flags");
+
+/// EMERGENCY_TIME is the time in seconds emergency mode was entered at. Interest stops accruing
+/// at that time and stakers can only withdraw the wattpeak they staked
+pub const EMERGENCY_TIME: Item<u64> = Item::new("emergency_time");
+
+/// SWEEP_CURSOR is the address of the last staker the previous sweep of expired rewards went
+/// through, none once a sweep reached the last staker
+pub const SWEEP_CURSOR: Item<Addr> = Item::new("sweep_cursor");
+
+/// EPOCHS holds a record of every epoch indexed by the epoch number, starting at 1. The epoch
+/// in progress is EPOCH_COUNT + 1
+pub const EPOCHS: Map<u64, EpochRecord> = Map::new("epochs");
+
+/// DEPOSITS holds a record of every DepositRewards call indexed by the deposit id, starting at 1
+pub const DEPOSITS: Map<u64, DepositRecord> = Map::new("deposits");
+
+/// DEPOSIT_COUNT is the number of rewards deposits, the id of the last deposit
+pub const DEPOSIT_COUNT: Item<u64> = Item::new("deposit_count");
+
+/// HOOKS holds the contracts notified of every stake change, with whether an error of the hook
+/// fails the transaction
+pub const HOOKS: Map<Addr, bool> = Map::new("hooks");
+
+/// POOLS holds the staking pool of every minter project that has one, by project id
+pub const POOLS: Map<u64, Pool> = Map::new("pools");
+
+/// POOL_ROUNDS holds every closed reward round of the pools, by project id and round id
+pub const POOL_ROUNDS: Map<(u64, u64), RewardRound> = Map::new("pool_rounds");
+
+/// POOL_STAKES holds the stakes in the pools, by project id and staker address. They are left out
+/// of STAKED_BALANCES and TOTAL_WATTPEAK_STAKED, so they carry no voting power
+pub const POOL_STAKES: Map<(u64, Addr), PoolStake> = Map::new("pool_stakes");
+
+/// LIQUID_DENOM is the full denom of the stWP liquid staking token, saved once it is created
+pub const LIQUID_DENOM: Item<String> = Item::new("liquid_denom");
+
+/// LIQUID_SUPPLY is the amount of stWP minted and not yet redeemed
+pub const LIQUID_SUPPLY: Item<Uint128> = Item::new("liquid_supply");
+
+/// WITHDRAW_ADDRESSES holds the address rewards of a staker are claimed to by default
+pub const WITHDRAW_ADDRESSES: Map<Addr, Addr> = Map::new("withdraw_addresses");
+
+/// IbcTransfer is rewards claimed by a staker over IBC, refunded to the staker if the transfer
+/// fails or times out
+#[cw_serde]
+pub struct IbcTransfer {
+    pub staker: Addr,
+    pub channel: String,
+    pub amount: Coin,
+}
+
+/// PENDING_IBC_TRANSFERS holds the IBC transfers sent in the current transaction, in the order
+/// their replies come in with the sequence of each transfer
+pub const PENDING_IBC_TRANSFERS: Item<Vec<IbcTransfer>> = Item::new("pending_ibc_transfers");
+
+/// IBC_TRANSFERS holds the IBC transfers awaiting an acknowledgement, by channel and sequence
+pub const IBC_TRANSFERS: Map<(&str, u64), IbcTransfer> = Map::new("ibc_transfers");
+
+pub const LIQUID_SUBDENOM: &str = "ustwattpeak";
+pub const LIQUID_DESCRIPTION: &str =
+    "stWP is a receipt for wattpeak staked in the liquid staking pool of the wattpeak staker";
+pub const LIQUID_SYMBOL: &str = "stWP";
+pub const LIQUID_DECIMALS: u32 = 6;
+pub const LIQUID_NAME: &str = "Staked WattPeak";
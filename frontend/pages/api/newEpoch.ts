@@ -33,7 +33,7 @@ export default async function handler(req: NextApiRequest, res: NextApiResponse)
     const GAS_PRICE_DENOM = 'ujunox';
     const ADDRESS_PREFIX = 'juno';
 
-    // Initialize wallet
+    // Initialize wallet, NewEpoch is permissionless so any funded account can be used
     const wallet = await DirectSecp256k1HdWallet.fromMnemonic(MNEMONIC, {
       prefix: ADDRESS_PREFIX,
     });
@@ -45,6 +45,16 @@ export default async function handler(req: NextApiRequest, res: NextApiResponse)
       gasPrice,
     });
 
+    // Skip the transaction if the current epoch has not ended yet
+    const epochInfo = await client.queryContractSmart(CONTRACT_ADDRESS, { epoch_info: {} });
+    const now = Math.floor(Date.now() / 1000);
+    if (now < epochInfo.next_epoch_time) {
+      return res.status(200).json({
+        message: 'Epoch has not ended yet.',
+        next_epoch_time: epochInfo.next_epoch_time,
+      });
+    }
+
     // Prepare the message to call new_epoch
     const msg = {
       new_epoch: {},